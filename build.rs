@@ -2,16 +2,36 @@ use std::env;
 use std::process::Command;
 
 fn main() {
-    // This is ran only during build process, we expect to always have git when building the app
-    let output = Command::new("git")
-        .args(["rev-parse", "--short", "HEAD"])
+    // GIT_HASH lets builds without a git checkout, such as packaging from a source archive,
+    // still report the commit they were built from
+    let git_hash = env::var("GIT_HASH")
+        .ok()
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "--short", "HEAD"])
+                .output()
+                .ok()?;
+
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
         .output()
-        .unwrap();
-    let git_hash = String::from_utf8(output.stdout).unwrap();
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+
     println!(
         "cargo:rustc-env=PERIDIO_CLI_VERSION={} {}",
         env!("CARGO_PKG_VERSION"),
         git_hash
     );
+    println!("cargo:rustc-env=PERIDIO_CLI_GIT_HASH={git_hash}");
+    println!("cargo:rustc-env=PERIDIO_CLI_RUSTC_VERSION={rustc_version}");
     println!("cargo:rustc-env=TARGET={}", env::var("TARGET").unwrap());
 }
@@ -2,10 +2,12 @@ use std::fs;
 
 use super::Command;
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
-use crate::utils::maybe_json;
+use crate::utils::maybe_json_from_source;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
@@ -44,9 +46,9 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Artifact)
     )]
-    artifact_prn: String,
+    artifact_prn: Prn,
 
-    /// A JSON object that informs the metadata that will be associated with this artifact version's binaries when they are included in bundles.
+    /// A JSON object, @PATH to a JSON file or @- to read it from stdin, that informs the metadata that will be associated with this artifact version's binaries when they are included in bundles.
     #[arg(long, conflicts_with = "custom_metadata_path")]
     custom_metadata: Option<String>,
 
@@ -80,8 +82,8 @@ impl Command<CreateCommand> {
         };
 
         let params = CreateArtifactVersionParams {
-            artifact_prn: self.inner.artifact_prn,
-            custom_metadata: maybe_json(custom_metadata),
+            artifact_prn: self.inner.artifact_prn.to_string(),
+            custom_metadata: maybe_json_from_source(custom_metadata)?,
             description: self.inner.description,
             id: self.inner.id,
             version: self.inner.version,
@@ -99,7 +101,7 @@ impl Command<CreateCommand> {
             .await
             .context(ApiSnafu)?
         {
-            Some(artifact_version) => print_json!(&artifact_version),
+            Some(artifact_version) => print_created!(&artifact_version),
             None => panic!(),
         }
 
@@ -115,30 +117,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListArtifactVersionsParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
-
-        match api
-            .artifact_versions()
-            .list(params)
+        let api = &api;
+        let list_args = &self.inner.list_args;
+
+        list_args
+            .print(|page| {
+                let params = ListArtifactVersionsParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
+
+                async move { api.artifact_versions().list(params).await.context(ApiSnafu) }
+            })
             .await
-            .context(ApiSnafu)?
-        {
-            Some(artifact_version) => print_json!(&artifact_version),
-            None => panic!(),
-        }
-
-        Ok(())
     }
 }
 
@@ -149,13 +147,13 @@ pub struct GetCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::ArtifactVersion)
     )]
-    prn: String,
+    prn: Prn,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = GetArtifactVersionParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -185,9 +183,9 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::ArtifactVersion)
     )]
-    prn: String,
+    prn: Prn,
 
-    /// A JSON object that informs the metadata that will be associated with this artifact version's binaries when they are included in bundles.
+    /// A JSON object, @PATH to a JSON file or @- to read it from stdin, that informs the metadata that will be associated with this artifact version's binaries when they are included in bundles.
     #[arg(long)]
     pub custom_metadata: Option<String>,
 
@@ -199,8 +197,8 @@ pub struct UpdateCommand {
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = UpdateArtifactVersionParams {
-            prn: self.inner.prn,
-            custom_metadata: maybe_json(self.inner.custom_metadata),
+            prn: self.inner.prn.to_string(),
+            custom_metadata: maybe_json_from_source(self.inner.custom_metadata)?,
             description: self.inner.description,
         };
 
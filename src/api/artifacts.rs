@@ -1,13 +1,19 @@
 use std::fs;
 
+use super::client::Client;
 use super::Command;
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
-use crate::utils::maybe_json;
+use crate::utils::confirm::confirm_deletion;
+use crate::utils::maybe_json_from_source;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
+use crate::utils::StyledStr;
 use crate::ApiSnafu;
 use crate::Error;
+use crate::FileSnafu;
 use crate::GlobalOptions;
 use crate::NonExistingPathSnafu;
 use clap::Parser;
@@ -24,6 +30,7 @@ pub enum ArtifactsCommand {
     List(Command<ListCommand>),
     Get(Command<GetCommand>),
     Update(Command<UpdateCommand>),
+    Delete(Command<DeleteCommand>),
 }
 
 impl ArtifactsCommand {
@@ -33,6 +40,7 @@ impl ArtifactsCommand {
             Self::List(cmd) => cmd.run(global_options).await,
             Self::Get(cmd) => cmd.run(global_options).await,
             Self::Update(cmd) => cmd.run(global_options).await,
+            Self::Delete(cmd) => cmd.run(global_options).await,
         }
     }
 }
@@ -40,7 +48,7 @@ impl ArtifactsCommand {
 #[derive(Parser, Debug)]
 
 pub struct CreateCommand {
-    /// A JSON object that informs the metadata that will be associated with this artifact's binaries when they are included in bundles.
+    /// A JSON object, @PATH to a JSON file or @- to read it from stdin, that informs the metadata that will be associated with this artifact's binaries when they are included in bundles.
     #[arg(long, conflicts_with = "custom_metadata_path")]
     custom_metadata: Option<String>,
 
@@ -65,7 +73,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Organization)
     )]
-    organization_prn: String,
+    organization_prn: Prn,
 }
 
 impl Command<CreateCommand> {
@@ -81,11 +89,11 @@ impl Command<CreateCommand> {
         };
 
         let params = CreateArtifactParams {
-            custom_metadata: maybe_json(custom_metadata),
+            custom_metadata: maybe_json_from_source(custom_metadata)?,
             description: self.inner.description,
             id: self.inner.id,
             name: self.inner.name,
-            organization_prn: self.inner.organization_prn,
+            organization_prn: self.inner.organization_prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -95,7 +103,7 @@ impl Command<CreateCommand> {
         });
 
         match api.artifacts().create(params).await.context(ApiSnafu)? {
-            Some(artifact) => print_json!(&artifact),
+            Some(artifact) => print_created!(&artifact),
             None => panic!(),
         }
 
@@ -111,25 +119,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListArtifactsParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
-
-        match api.artifacts().list(params).await.context(ApiSnafu)? {
-            Some(artifact) => print_json!(&artifact),
-            None => panic!(),
-        }
-
-        Ok(())
+        let api = &api;
+        let list_args = &self.inner.list_args;
+
+        list_args
+            .print(|page| {
+                let params = ListArtifactsParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
+
+                async move { api.artifacts().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -140,13 +149,13 @@ pub struct GetCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Artifact)
     )]
-    prn: String,
+    prn: Prn,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = GetArtifactParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -171,9 +180,9 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Artifact)
     )]
-    prn: String,
+    prn: Prn,
 
-    /// A JSON object that informs the metadata that will be associated with this artifact's binaries when they are included in bundles.
+    /// A JSON object, @PATH to a JSON file or @- to read it from stdin, that informs the metadata that will be associated with this artifact's binaries when they are included in bundles.
     #[arg(long)]
     pub custom_metadata: Option<String>,
 
@@ -189,8 +198,8 @@ pub struct UpdateCommand {
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = UpdateArtifactParams {
-            prn: self.inner.prn,
-            custom_metadata: maybe_json(self.inner.custom_metadata),
+            prn: self.inner.prn.to_string(),
+            custom_metadata: maybe_json_from_source(self.inner.custom_metadata)?,
             description: self.inner.description,
             name: self.inner.name,
         };
@@ -209,3 +218,39 @@ impl Command<UpdateCommand> {
         Ok(())
     }
 }
+
+#[derive(Parser, Debug)]
+pub struct DeleteCommand {
+    /// The PRN of the resource to delete.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Artifact)
+    )]
+    prn: Prn,
+
+    /// Delete without asking for confirmation, required when not run interactively.
+    #[arg(long, short = 'y', default_value = "false")]
+    yes: bool,
+}
+
+impl Command<DeleteCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let prn = self.inner.prn;
+
+        if !confirm_deletion(&format!("artifact {prn}"), self.inner.yes)? {
+            return Ok(());
+        }
+
+        let client = Client::new(&global_options)?;
+
+        client.delete(&format!("/artifacts/{prn}")).await?;
+
+        let mut message = StyledStr::new();
+        message
+            .success("success: ")
+            .plain(format!("Deleted artifact {prn}"));
+        message.print_success().context(FileSnafu)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use clap::Parser;
+use serde_json::Value;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use super::client::Client;
+use super::list::collect_pages;
+use super::Command;
+use crate::utils::output;
+use crate::utils::table::Column;
+use crate::utils::{parse_time, Prn, StyledStr};
+use crate::Error;
+use crate::GlobalOptions;
+
+#[derive(Parser, Debug)]
+pub enum AuditLogsCommand {
+    List(Command<ListCommand>),
+}
+
+impl AuditLogsCommand {
+    pub async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        match self {
+            Self::List(cmd) => cmd.run(global_options).await,
+        }
+    }
+}
+
+/// Print who did what to which resource, as a table unless --output is given.
+#[derive(Parser, Debug)]
+pub struct ListCommand {
+    /// Only list entries from this time on, as RFC 3339 or a duration before now, e.g. 24h.
+    #[arg(long, value_parser = parse_time)]
+    since: Option<OffsetDateTime>,
+
+    /// Only list entries before this time, as RFC 3339 or a duration before now, e.g. 1h.
+    #[arg(long, value_parser = parse_time)]
+    until: Option<OffsetDateTime>,
+
+    /// Only list entries of actions taken by this actor, e.g. a user or API key PRN.
+    #[arg(long)]
+    actor: Option<String>,
+
+    /// Only list entries about this resource.
+    #[arg(long, value_parser = str::parse::<Prn>)]
+    resource_prn: Option<Prn>,
+
+    /// Limit the length of the page.
+    #[arg(long)]
+    limit: Option<u8>,
+
+    /// A cursor for pagination across multiple pages of results. Use the next_page value returned in a previous response (if not null) to request subsequent results.
+    #[arg(long)]
+    page: Option<String>,
+
+    /// Follow next_page until every page was fetched and print the entries of all pages together.
+    #[arg(long, default_value = "false")]
+    all: bool,
+
+    /// Stop fetching pages once this many entries were collected with --all.
+    #[arg(long, requires = "all")]
+    max_items: Option<usize>,
+}
+
+impl Command<ListCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        if let (Some(since), Some(until)) = (self.inner.since, self.inner.until) {
+            if since >= until {
+                let mut error = StyledStr::new();
+                error
+                    .error("error: ")
+                    .plain("--since must be before --until:\r\n")
+                    .warning(format!(
+                        "\t{} is not before {}",
+                        since.format(&Rfc3339).unwrap(),
+                        until.format(&Rfc3339).unwrap()
+                    ));
+                return Err(error.data_err());
+            }
+        }
+
+        let client = Client::new(&global_options)?;
+        let client = &client;
+        let query = &self.inner.query();
+
+        let list = |page: Option<String>| async move {
+            let mut query = query.clone();
+
+            if let Some(page) = page {
+                query.push(("page", page));
+            }
+
+            client.get_with_query("/audit_logs", &query).await.map(Some)
+        };
+
+        let audit_logs = if self.inner.all {
+            Value::Array(collect_pages(self.inner.page.clone(), self.inner.max_items, list).await?)
+        } else {
+            list(self.inner.page.clone()).await?.unwrap()
+        };
+
+        output::print_list(&audit_logs, &columns())
+    }
+}
+
+impl ListCommand {
+    fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+
+        if let Some(since) = self.since {
+            query.push(("since", since.format(&Rfc3339).unwrap()));
+        }
+
+        if let Some(until) = self.until {
+            query.push(("until", until.format(&Rfc3339).unwrap()));
+        }
+
+        if let Some(actor) = &self.actor {
+            query.push(("actor", actor.clone()));
+        }
+
+        if let Some(resource_prn) = &self.resource_prn {
+            query.push(("resource_prn", resource_prn.to_string()));
+        }
+
+        if let Some(limit) = self.limit {
+            query.push(("limit", limit.to_string()));
+        }
+
+        query
+    }
+}
+
+// the columns shown without --output or --columns
+fn columns() -> Vec<Column> {
+    [
+        ("timestamp", "inserted_at"),
+        ("actor", "actor_prn"),
+        ("action", "action"),
+        ("resource", "resource_prn"),
+    ]
+    .into_iter()
+    .map(|(header, path)| Column {
+        header: header.to_string(),
+        path: path.to_string(),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::table;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    const RESOURCE: &str =
+        "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef:device:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+    fn command() -> ListCommand {
+        ListCommand {
+            since: None,
+            until: None,
+            actor: None,
+            resource_prn: None,
+            limit: None,
+            page: None,
+            all: false,
+            max_items: None,
+        }
+    }
+
+    #[test]
+    fn filters_are_sent_as_query_parameters() {
+        let command = ListCommand {
+            since: Some(datetime!(2024-05-31 12:00 UTC)),
+            until: Some(datetime!(2024-06-01 12:00 UTC)),
+            actor: Some("prn:1:user:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1".to_string()),
+            resource_prn: Some(RESOURCE.parse().unwrap()),
+            limit: Some(50),
+            ..command()
+        };
+
+        assert_eq!(
+            command.query(),
+            vec![
+                ("since", "2024-05-31T12:00:00Z".to_string()),
+                ("until", "2024-06-01T12:00:00Z".to_string()),
+                (
+                    "actor",
+                    "prn:1:user:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1".to_string()
+                ),
+                ("resource_prn", RESOURCE.to_string()),
+                ("limit", "50".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unset_filters_are_left_out() {
+        assert!(command().query().is_empty());
+    }
+
+    #[test]
+    fn default_columns_are_timestamp_actor_action_and_resource() {
+        let entry = json!({
+            "inserted_at": "2024-06-01T12:00:00Z",
+            "actor_prn": "prn:1:user:a",
+            "action": "device.update",
+            "resource_prn": "prn:1:b:device:c",
+            "request_id": "ignored",
+        });
+
+        let rendered = table::render_to_width(&[&entry], &columns(), None);
+        let mut lines = rendered.lines();
+
+        assert_eq!(
+            lines.next().unwrap().split_whitespace().collect::<Vec<_>>(),
+            ["timestamp", "actor", "action", "resource"]
+        );
+        assert_eq!(
+            lines.next().unwrap().split_whitespace().collect::<Vec<_>>(),
+            [
+                "2024-06-01T12:00:00Z",
+                "prn:1:user:a",
+                "device.update",
+                "prn:1:b:device:c"
+            ]
+        );
+    }
+}
@@ -1,11 +1,28 @@
+use super::client;
+use super::relay;
 use super::Command;
+use crate::api::binary_parts::{
+    hash_mismatch_error, part_count, part_error, part_size, skip_uploaded_part,
+    upload_failures_error, upload_parts, verify_part_hash, PartFailure,
+};
+use crate::api::binary_parts::{MAX_PART_SIZE, MIN_PART_SIZE};
+use crate::api::binary_signatures::{
+    read_signing_key, sign_hash_with, signing_key_pair_from_config,
+};
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
-use crate::utils::maybe_json;
+use crate::utils::maybe_json_from_source;
+use crate::utils::output;
+use crate::utils::temp_file::TempFile;
+use crate::utils::wait::{self, Terminal, WaitArgs};
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
+use crate::utils::StyledStr;
 use crate::ApiSnafu;
 use crate::Error;
+use crate::FileSnafu;
 use crate::GlobalOptions;
 use crate::NonExistingPathSnafu;
 use backon::ConstantBuilder;
@@ -13,9 +30,7 @@ use backon::Retryable;
 use base64::engine::general_purpose;
 use base64::Engine;
 use clap::Parser;
-use futures_util::stream;
-use futures_util::StreamExt;
-use indicatif::ProgressBar;
+use indicatif::HumanBytes;
 use indicatif::ProgressState;
 use indicatif::ProgressStyle;
 use peridio_sdk::api::binaries::Binary;
@@ -39,17 +54,39 @@ use snafu::ResultExt;
 use std::cmp;
 use std::io::Read;
 use std::io::Seek;
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::thread::available_parallelism;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{fs, io};
 
+// what one pass over the parts of a binary did, see `CreateCommand::upload_binary_parts`
+#[derive(Default)]
+struct UploadPass {
+    // bytes uploaded, without the parts skipped
+    uploaded: u64,
+    skipped: usize,
+    failures: Vec<(u64, PartFailure)>,
+}
+
+// what became of a part in an upload pass, a part the API recorded with another hash aborts the
+// upload, see `CreateCommand::upload_binary_parts`
+enum PartUpload {
+    // the bytes uploaded
+    Uploaded(u64),
+    Skipped,
+    Mismatched(String),
+}
+
 #[derive(Parser, Debug)]
 pub enum BinariesCommand {
     Create(Box<Command<CreateCommand>>),
+    Download(Command<DownloadCommand>),
     List(Command<ListCommand>),
     Get(Command<GetCommand>),
     Update(Command<UpdateCommand>),
+    Sign(Command<SignCommand>),
 }
 
 impl BinariesCommand {
@@ -59,6 +96,8 @@ impl BinariesCommand {
             Self::List(cmd) => cmd.run(global_options).await,
             Self::Get(cmd) => cmd.run(global_options).await,
             Self::Update(cmd) => cmd.run(global_options).await,
+            Self::Sign(cmd) => cmd.run(global_options).await,
+            Self::Download(cmd) => cmd.run(global_options).await,
         }
     }
 }
@@ -73,9 +112,9 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::ArtifactVersion)
     )]
-    artifact_version_prn: String,
+    artifact_version_prn: Prn,
 
-    /// A JSON object that informs the metadata that will be associated with this binary when it is included in bundles.
+    /// A JSON object, @PATH to a JSON file or @- to read it from stdin, that informs the metadata that will be associated with this binary when it is included in bundles.
     #[arg(long, conflicts_with = "custom_metadata_path")]
     custom_metadata: Option<String>,
 
@@ -119,19 +158,24 @@ pub struct CreateCommand {
     )]
     content_path: Option<String>,
 
-    /// The size to use when creating binary parts. All binary parts will be equal to this size, except the last one which will be less than or equal to this size.
+    /// The size to use when creating binary parts. All binary parts will be equal to this size, except the last one which will be less than or equal to this size. A binary can have at most 10,000 parts. Every concurrent job holds one part in memory, so uploads use up to --concurrency times this size.
     #[arg(
         long,
+        visible_alias = "chunk-size",
         requires = "content_path",
         default_value = "5242880",
-        value_parser = clap::value_parser!(u64).range(5242880..50000000000),
+        value_parser = clap::value_parser!(u64).range(MIN_PART_SIZE..MAX_PART_SIZE),
     )]
     binary_part_size: Option<u64>,
 
-    /// Limit the concurrency of jobs that create and upload binary parts. [default: 2x the core count, to a maximum of 16]
+    /// Limit the concurrency of jobs that create and upload binary parts. Each job buffers one --binary-part-size part. [default: 2x the core count, to a maximum of 16]
     #[arg(long, requires = "content_path")]
     concurrency: Option<u8>,
 
+    /// Resume an interrupted upload, skipping the parts already uploaded once their size and hash match the content.
+    #[arg(long, default_value = "false", requires = "content_path")]
+    resume: bool,
+
     /// The name of a signing key pair in your Peridio CLI config. This will dictate both the private key to create a binary signature with as well as the signing key Peridio will use to verify the binary signature.
     #[arg(
         long,
@@ -159,13 +203,14 @@ pub struct CreateCommand {
         requires = "signing_key_private",
         value_parser = PRNValueParser::new(PRNType::SigningKey)
     )]
-    signing_key_prn: Option<String>,
+    signing_key_prn: Option<Prn>,
 
     /// Create the binary record but do not upload its content nor sign it.
     #[arg(
         long,
         default_value = "false",
         conflicts_with = "concurrency",
+        conflicts_with = "resume",
         conflicts_with = "binary_part_size",
         conflicts_with = "signing_key_pair"
     )]
@@ -176,7 +221,38 @@ pub struct CreateCommand {
 }
 
 impl CreateCommand {
-    async fn run(
+    /// Create a binary of the content at `content_path` and upload it, for commands that create
+    /// binaries on the way such as `firmwares migrate`.
+    pub fn for_content(
+        artifact_version_prn: Prn,
+        target: String,
+        content_path: String,
+        description: Option<String>,
+        signing_key_pair: Option<String>,
+    ) -> Self {
+        Self {
+            artifact_version_prn,
+            custom_metadata: None,
+            custom_metadata_path: None,
+            description,
+            hash: None,
+            id: None,
+            size: None,
+            target,
+            content_path: Some(content_path),
+            // the default of --binary-part-size
+            binary_part_size: Some(5242880),
+            concurrency: None,
+            resume: false,
+            signing_key_pair,
+            signing_key_private: None,
+            signing_key_prn: None,
+            skip_upload: false,
+            global_options: None,
+        }
+    }
+
+    pub async fn run(
         &mut self,
         global_options: GlobalOptions,
     ) -> Result<Option<CreateBinaryResponse>, Error> {
@@ -188,6 +264,15 @@ impl CreateCommand {
 
         self.global_options = Some(global_options.clone());
 
+        // fail before creating anything if the content can't be split into enough parts
+        if let (Some(content_path), false) = (&self.content_path, self.skip_upload) {
+            let file_size = fs::metadata(content_path)
+                .context(NonExistingPathSnafu { path: content_path })?
+                .len();
+
+            part_count(file_size, self.binary_part_size.unwrap()).map_err(part_error)?;
+        }
+
         let binary = match self.get_or_create_binary(&api).await? {
             Some(CreateBinaryResponse { binary }) => {
                 if self.skip_upload {
@@ -220,7 +305,7 @@ impl CreateCommand {
             // do signing if available
             if self.signing_key_pair.is_some() || self.signing_key_private.is_some() {
                 // wait for hashing to be signable
-                eprintln!("Waiting for cloud hashing...");
+                output::status("Waiting for cloud hashing...");
                 let binary = (|| async { self.check_for_state_change(&binary, api).await })
                     .retry(
                         &ConstantBuilder::default()
@@ -229,7 +314,7 @@ impl CreateCommand {
                     )
                     .await?;
 
-                eprintln!("Signing binary...");
+                output::status("Signing binary...");
                 let binary = self.sign_binary(&binary, api).await.unwrap();
 
                 Ok(binary)
@@ -237,7 +322,7 @@ impl CreateCommand {
                 Ok(binary)
             }
         } else if matches!(binary.state, BinaryState::Hashable) {
-            eprintln!("Updating binary to hashing...");
+            output::status("Updating binary to hashing...");
             // move to hashing
             let binary = self
                 .change_binary_status(ArgBinaryState::Hashing, binary, api)
@@ -246,7 +331,7 @@ impl CreateCommand {
 
             if self.signing_key_pair.is_some() || self.signing_key_private.is_some() {
                 // wait for hashing to be signable
-                eprintln!("Waiting for cloud hashing...");
+                output::status("Waiting for cloud hashing...");
                 let binary = (|| async { self.check_for_state_change(&binary, api).await })
                     .retry(
                         &ConstantBuilder::default()
@@ -255,7 +340,7 @@ impl CreateCommand {
                     )
                     .await?;
 
-                eprintln!("Signing binary...");
+                output::status("Signing binary...");
                 let binary = self.sign_binary(&binary, api).await.unwrap();
 
                 Ok(binary)
@@ -265,7 +350,7 @@ impl CreateCommand {
         } else if matches!(binary.state, BinaryState::Hashing) {
             if self.signing_key_pair.is_some() || self.signing_key_private.is_some() {
                 // wait for hashing to be signable
-                eprintln!("Waiting for cloud hashing...");
+                output::status("Waiting for cloud hashing...");
                 let binary = (|| async { self.check_for_state_change(binary, api).await })
                     .retry(
                         &ConstantBuilder::default()
@@ -274,7 +359,7 @@ impl CreateCommand {
                     )
                     .await?;
 
-                eprintln!("Signing binary...");
+                output::status("Signing binary...");
                 let binary = self.sign_binary(&binary, api).await.unwrap();
 
                 Ok(binary)
@@ -283,7 +368,7 @@ impl CreateCommand {
             }
         } else if matches!(binary.state, BinaryState::Signable) {
             if self.signing_key_pair.is_some() || self.signing_key_private.is_some() {
-                eprintln!("Signing binary...");
+                output::status("Signing binary...");
                 let binary = self.sign_binary(binary, api).await.unwrap();
 
                 Ok(binary)
@@ -297,7 +382,7 @@ impl CreateCommand {
 
     async fn sign_binary(&self, binary: &Binary, api: &Api) -> Result<Binary, Error> {
         let command = crate::api::binary_signatures::CreateCommand {
-            binary_prn: binary.prn.clone(),
+            binary_prn: api_prn(&binary.prn),
             binary_content_path: Some(self.content_path.clone().unwrap()),
             signature: None,
             signing_key_pair: self.signing_key_pair.clone(),
@@ -322,7 +407,8 @@ impl CreateCommand {
 
     async fn check_for_state_change(&self, binary: &Binary, api: &Api) -> Result<Binary, Error> {
         let command = GetCommand {
-            prn: binary.prn.clone(),
+            prn: api_prn(&binary.prn),
+            wait: WaitArgs::default(),
             api: Some(api.to_owned()),
         };
 
@@ -349,13 +435,14 @@ impl CreateCommand {
         api: &Api,
     ) -> Result<Binary, Error> {
         let command = UpdateCommand {
-            prn: binary.prn.clone(),
+            prn: api_prn(&binary.prn),
             custom_metadata: None,
             description: None,
             state: Some(state),
             api: Some(api.clone()),
             hash: None,
             size: None,
+            wait: WaitArgs::default(),
         };
 
         match command.run(self.global_options.clone().unwrap()).await? {
@@ -365,7 +452,7 @@ impl CreateCommand {
     }
 
     async fn process_binary_parts(&self, binary: &Binary, api: &Api) -> Result<Binary, Error> {
-        eprintln!("Evaluating binary parts...");
+        output::status("Evaluating binary parts...");
         // get server parts
         let binary_parts = self.get_binary_parts(binary, api).await.unwrap();
 
@@ -380,21 +467,26 @@ impl CreateCommand {
         };
 
         let chunks_length =
-            (file_size as f64 / self.binary_part_size.unwrap() as f64).ceil() as u64;
+            part_count(file_size, self.binary_part_size.unwrap()).map_err(part_error)?;
 
         let client = Client::new();
+        let started = Instant::now();
 
-        self.upload_binary_parts(
-            binary,
-            api,
-            file_size,
-            chunks_length,
-            &client,
-            &binary_parts,
-        )
-        .await?;
-
-        eprintln!("Validating Upload");
+        // parts that fail here are uploaded again below
+        let first_pass = self
+            .upload_binary_parts(
+                binary,
+                api,
+                file_size,
+                chunks_length,
+                &client,
+                &binary_parts,
+                self.resume,
+            )
+            .await?;
+        let mut uploaded = first_pass.uploaded;
+
+        output::status("Validating Upload");
         // list binary parts again in order to get the latest state
         let binary_parts = self.get_binary_parts(binary, api).await.unwrap();
 
@@ -406,25 +498,47 @@ impl CreateCommand {
                 .all(|x| matches!(x.state, BinaryPartState::Valid)))
         {
             // retry only once
-            eprintln!("Retrying Upload");
-            self.upload_binary_parts(
-                binary,
-                api,
-                file_size,
-                chunks_length,
-                &client,
-                &binary_parts,
-            )
-            .await?;
+            output::status("Retrying Upload");
+            let retry_pass = self
+                .upload_binary_parts(
+                    binary,
+                    api,
+                    file_size,
+                    chunks_length,
+                    &client,
+                    &binary_parts,
+                    // the parts valid by now were uploaded by the pass above
+                    true,
+                )
+                .await?;
+            uploaded += retry_pass.uploaded;
+
+            if !retry_pass.failures.is_empty() {
+                return Err(upload_failures_error(&retry_pass.failures));
+            }
+        }
+
+        let elapsed = started.elapsed().as_secs_f64();
+        output::status(&format!(
+            "Uploaded {} in {elapsed:.1}s ({}/s)",
+            HumanBytes(uploaded),
+            HumanBytes((uploaded as f64 / elapsed.max(f64::EPSILON)) as u64)
+        ));
+
+        if self.resume {
+            output::status(&format!(
+                "Skipped {} of {chunks_length} binary parts that were already uploaded",
+                first_pass.skipped
+            ));
         }
 
-        eprintln!("Updating binary to hashable...");
+        output::status("Updating binary to hashable...");
         // we created the binary parts not move it to hashable
         let binary = self
             .change_binary_status(ArgBinaryState::Hashable, binary, api)
             .await?;
 
-        eprintln!("Updating binary to hashing...",);
+        output::status("Updating binary to hashing...");
         // move to hashing
         let binary = self
             .change_binary_status(ArgBinaryState::Hashing, &binary, api)
@@ -433,6 +547,9 @@ impl CreateCommand {
         Ok(binary)
     }
 
+    /// Create and upload every part, at most `--concurrency` at a time, returning what the pass
+    /// uploaded and why each part that failed did. With `skip_uploaded` the parts valid on the
+    /// server already are skipped once they hold the same content.
     #[allow(clippy::too_many_arguments)]
     async fn upload_binary_parts(
         &self,
@@ -442,102 +559,139 @@ impl CreateCommand {
         chunks_length: u64,
         client: &Client,
         binary_parts: &[ListBinaryPart],
-    ) -> Result<(), Error> {
-        eprintln!("Creating binary parts and uploading...");
-        let pb = Arc::new(ProgressBar::new(file_size));
-        pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        skip_uploaded: bool,
+    ) -> Result<UploadPass, Error> {
+        output::status("Creating binary parts and uploading...");
+        let pb = Arc::new(output::progress_bar(file_size));
+        pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} ({eta})")
             .unwrap()
             .with_key("eta", |state: &ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
             .progress_chars("#>-"));
 
-        let result = stream::iter(1..=chunks_length)
-            .map(|index| {
-                let client = client.clone();
-                let binary_part_size = self.binary_part_size.unwrap();
-                let global_options = self.global_options.clone().unwrap();
-                let api = api.clone();
-                let binary = binary.clone();
-                let content_path = self.content_path.clone().unwrap();
-                let binary_parts = binary_parts.to_vec();
-                let pb = Arc::clone(&pb);
-                tokio::spawn(async move {
-                    // we ignore the ones we already created
-                    if let Some(binary_part) = binary_parts.iter().find(|x| x.index as u64 == index)
-                    {
-                        if matches!(binary_part.state, BinaryPartState::Valid) {
-                            pb.inc(binary_part.size);
-                            return;
-                        }
+        let uploaded: Arc<Vec<serde_json::Value>> = Arc::new(
+            binary_parts
+                .iter()
+                .filter(|part| matches!(part.state, BinaryPartState::Valid))
+                .filter_map(|part| serde_json::to_value(part).ok())
+                .collect(),
+        );
+
+        let concurrency = self.concurrency.unwrap().into();
+        let results = upload_parts(chunks_length, concurrency, |index| {
+            let client = client.clone();
+            let binary_part_size = self.binary_part_size.unwrap();
+            let global_options = self.global_options.clone().unwrap();
+            let api = api.clone();
+            let binary = binary.clone();
+            let content_path = self.content_path.clone().unwrap();
+            let uploaded = Arc::clone(&uploaded);
+            let pb = Arc::clone(&pb);
+            let task = tokio::spawn(async move {
+                // we want to open the file in each thread, this is due to concurrency issues
+                // when using `Seek` from different threads theres a race condition in the data
+                let mut file = fs::File::open(&content_path).map_err(|e| e.to_string())?;
+
+                let file_position = binary_part_size * (index - 1);
+
+                file.seek(io::SeekFrom::Start(file_position))
+                    .map_err(|e| e.to_string())?;
+
+                // the last part only holds what is left of the file
+                let n = part_size(file_size, binary_part_size, index) as usize;
+
+                if n > 0 {
+                    let mut mut_buffer = vec![0; n];
+
+                    file.read_exact(&mut mut_buffer)
+                        .map_err(|e| e.to_string())?;
+
+                    let hash = Sha256::digest(&mut_buffer);
+                    let hex_hash = format!("{hash:x}");
+
+                    if skip_uploaded_part(index, n as u64, &hex_hash, &uploaded, skip_uploaded)? {
+                        pb.inc(n as u64);
+                        return Ok(PartUpload::Skipped);
                     }
 
-                    // we want to open the file in each thread, this is due to concurrency issues
-                    // when using `Seek` from different threads theres a race condition in the data
-                    let mut file = fs::File::open(&content_path).unwrap();
-
-                    let file_position = binary_part_size * (index - 1);
-
-                    file.seek(io::SeekFrom::Start(file_position)).unwrap();
-
-                    let mut buffer = vec![0; binary_part_size.try_into().unwrap()];
-
-                    let n = file.read(&mut buffer[..]).unwrap();
-
-                    if n > 0 {
-                        let mut mut_buffer = vec![0; n];
-
-                        mut_buffer.copy_from_slice(&buffer[..n]);
-
-                        let mut hasher = Sha256::new();
-                        let _ = io::copy(&mut &mut_buffer[..], &mut hasher).unwrap();
-                        let hash = hasher.finalize();
-
-                        // push those bytes to the server
-                        let create_command = crate::api::binary_parts::CreateCommand {
-                            binary_prn: binary.prn.clone(),
-                            expected_binary_size: binary.size,
-                            index: index as u16,
-                            hash: format!("{hash:x}"),
-                            api: Some(api),
-                            size: n as u64,
-                            binary_content_path: None,
-                        };
+                    // push those bytes to the server
+                    let create_command = crate::api::binary_parts::CreateCommand {
+                        binary_prn: api_prn(&binary.prn),
+                        expected_binary_size: binary.size,
+                        index: index as u16,
+                        hash: Some(hex_hash.clone()),
+                        api: Some(api),
+                        size: Some(n as u64),
+                        chunk_size: None,
+                        binary_content_path: None,
+                    };
 
-                        let bin_part = create_command
-                            .run(global_options)
-                            .await
-                            .expect("Error while creating a binary part binary part")
-                            .expect("Cannot create a binary part");
+                    let bin_part = create_command
+                        .run(global_options.clone())
+                        .await
+                        .map_err(|error| PartFailure::from(relay::failure(&global_options, error)))?
+                        .ok_or_else(|| "the binary part was not created".to_string())?;
+
+                    // a part recorded with another hash can never become valid, retrying
+                    // the upload would not help
+                    let created =
+                        serde_json::to_value(&bin_part.binary_part).map_err(|e| e.to_string())?;
+                    if let Err(message) = verify_part_hash(index, &hex_hash, &created) {
+                        return Ok(PartUpload::Mismatched(message));
+                    }
 
-                        // do amazon request
-                        let body = Body::from(mut_buffer);
+                    // do amazon request
+                    let body = Body::from(mut_buffer);
+
+                    let hash_base64 = general_purpose::STANDARD.encode(hash);
+
+                    let res = client
+                        .put(bin_part.binary_part.presigned_upload_url)
+                        .body(body)
+                        .header("x-amz-checksum-sha256", &hash_base64)
+                        .header("content-length", n)
+                        .header("content-type", "application/octet-stream")
+                        .send()
+                        .await
+                        .map_err(PartFailure::transient)?;
+
+                    let status = res.status();
+                    if !(200..=201).contains(&status.as_u16()) {
+                        let message = format!("upload failed with {status}");
+
+                        return Err(if status.is_server_error() || status.as_u16() == 429 {
+                            PartFailure::transient(message)
+                        } else {
+                            message.into()
+                        });
+                    };
 
-                        let hash_base64 = general_purpose::STANDARD.encode(hash);
+                    pb.inc(n.try_into().unwrap());
+                };
 
-                        let res = client
-                            .put(bin_part.binary_part.presigned_upload_url)
-                            .body(body)
-                            .header("x-amz-checksum-sha256", &hash_base64)
-                            .header("content-length", n)
-                            .header("content-type", "application/octet-stream")
-                            .send()
-                            .await
-                            .unwrap();
+                Ok::<_, PartFailure>(PartUpload::Uploaded(n as u64))
+            });
 
-                        pb.inc(n.try_into().unwrap());
+            async move {
+                task.await
+                    .unwrap_or_else(|error| Err(error.to_string().into()))
+            }
+        })
+        .await;
 
-                        if !(200..=201).contains(&res.status().as_u16()) {
-                            panic!("Wasn't able to upload binary to amazon S3")
-                        };
-                    };
-                })
-            })
-            .buffer_unordered(self.concurrency.unwrap().into());
+        pb.finish_and_clear();
 
-        let _ = result.collect::<Vec<_>>().await;
+        let mut pass = UploadPass::default();
 
-        pb.finish_and_clear();
+        for (index, result) in results {
+            match result {
+                Ok(PartUpload::Uploaded(size)) => pass.uploaded += size,
+                Ok(PartUpload::Skipped) => pass.skipped += 1,
+                Ok(PartUpload::Mismatched(message)) => return Err(hash_mismatch_error(message)),
+                Err(failure) => pass.failures.push((index, failure)),
+            }
+        }
 
-        Ok(())
+        Ok(pass)
     }
 
     async fn get_binary_parts(
@@ -546,7 +700,7 @@ impl CreateCommand {
         api: &Api,
     ) -> Result<Vec<ListBinaryPart>, Error> {
         let list_command = crate::api::binary_parts::ListCommand {
-            binary_prn: binary.prn.clone(),
+            binary_prn: api_prn(&binary.prn),
             api: Some(api.clone()),
         };
 
@@ -562,11 +716,10 @@ impl CreateCommand {
     }
 
     async fn get_or_create_binary(&self, api: &Api) -> Result<Option<CreateBinaryResponse>, Error> {
-        let organization_prn =
-            Self::get_organization_prn_from_prn(self.artifact_version_prn.clone());
+        let organization_prn = self.artifact_version_prn.organization().unwrap();
 
         let (size, hash) = if let Some(content_path) = &self.content_path {
-            eprintln!("Hashing binary...");
+            output::status("Hashing binary...");
             let mut file = fs::File::open(content_path).context(NonExistingPathSnafu {
                 path: &content_path,
             })?;
@@ -594,7 +747,7 @@ impl CreateCommand {
                 next_page: _,
             }) if binaries.len() == 1 => {
                 // we found the binary, do as it was created
-                eprintln!("Binary already exists...");
+                output::status("Binary already exists...");
                 let binary = binaries.first().unwrap().clone();
 
                 // is we get a binary, check the hash with out local hash
@@ -612,7 +765,7 @@ impl CreateCommand {
             }
 
             _ => {
-                eprintln!("Creating binary...");
+                output::status("Creating binary...");
                 let custom_metadata =
                     if let Some(custom_metadata_path) = self.custom_metadata_path.clone() {
                         fs::read_to_string(&custom_metadata_path)
@@ -626,8 +779,8 @@ impl CreateCommand {
 
                 // create the binary
                 let params = CreateBinaryParams {
-                    artifact_version_prn: self.artifact_version_prn.clone(),
-                    custom_metadata: maybe_json(custom_metadata),
+                    artifact_version_prn: self.artifact_version_prn.to_string(),
+                    custom_metadata: maybe_json_from_source(custom_metadata)?,
                     description: self.description.clone(),
                     hash,
                     id: self.id.clone(),
@@ -652,12 +805,13 @@ impl CreateCommand {
             .await?;
 
         let update_command = UpdateCommand {
-            prn: binary.prn.clone(),
+            prn: api_prn(&binary.prn),
             custom_metadata: None,
             description: None,
             hash: Some(hash),
             size: Some(size),
             state: None,
+            wait: WaitArgs::default(),
             api: Some(api.clone()),
         };
 
@@ -672,21 +826,17 @@ impl CreateCommand {
 
         Ok(binary)
     }
+}
 
-    fn get_organization_prn_from_prn(prn: String) -> String {
-        // ["prn", "1", org_id, resource_name, resource_id]
-        let prn: Vec<&str> = prn.split(':').collect();
-
-        let org_id = prn[2];
-
-        format!("prn:1:{org_id}")
-    }
+// the PRN of a binary the API returned, which is always well formed
+fn api_prn(prn: &str) -> Prn {
+    prn.parse().expect("the API returned an invalid PRN")
 }
 
 impl Command<CreateCommand> {
     async fn run(mut self, global_options: GlobalOptions) -> Result<(), Error> {
         match self.inner.run(global_options).await? {
-            Some(binary) => print_json!(&binary),
+            Some(binary) => print_created!(&binary),
             None => panic!(),
         }
 
@@ -698,29 +848,95 @@ impl Command<CreateCommand> {
 pub struct ListCommand {
     #[clap(flatten)]
     list_args: ListArgs,
+
+    /// Only list binaries of this artifact version.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::ArtifactVersion)
+    )]
+    artifact_version_prn: Option<Prn>,
+
+    /// Only list binaries built for this target, e.g. aarch64-unknown-linux-gnu.
+    #[arg(long)]
+    target: Option<String>,
+
+    /// Only list binaries in this state.
+    #[arg(long, value_enum)]
+    state: Option<ArgBinaryState>,
+}
+
+impl ListCommand {
+    // the query parameters of the filters, empty when none were given
+    fn filter_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+
+        if let Some(artifact_version_prn) = &self.artifact_version_prn {
+            query.push(("artifact_version_prn", artifact_version_prn.to_string()));
+        }
+
+        if let Some(target) = &self.target {
+            query.push(("target", target.clone()));
+        }
+
+        if let Some(state) = &self.state {
+            query.push(("state", state.as_str().to_string()));
+        }
+
+        query
+    }
 }
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListBinariesParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
+        let filters = self.inner.filter_query();
+        let list_args = &self.inner.list_args;
+
+        // the SDK can't filter binaries, filtered lists are requested directly
+        if !filters.is_empty() {
+            let client = &client::Client::new(&global_options)?;
+            let filters = &filters;
+
+            return list_args
+                .print(|page| async move {
+                    let mut query = filters.clone();
+                    query.push(("search", list_args.search.clone()));
+
+                    if let Some(limit) = list_args.limit {
+                        query.push(("limit", limit.to_string()));
+                    }
+
+                    if let Some(order) = &list_args.order {
+                        query.push(("order", order.clone()));
+                    }
+
+                    if let Some(page) = page {
+                        query.push(("page", page));
+                    }
+
+                    client.get_with_query("/binaries", &query).await.map(Some)
+                })
+                .await;
+        }
 
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
+        let api = &api;
+
+        list_args
+            .print(|page| {
+                let params = ListBinariesParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
 
-        match api.binaries().list(params).await.context(ApiSnafu)? {
-            Some(binary) => print_json!(&binary),
-            None => panic!(),
-        }
-
-        Ok(())
+                async move { api.binaries().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -731,7 +947,10 @@ pub struct GetCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Binary)
     )]
-    prn: String,
+    prn: Prn,
+
+    #[command(flatten)]
+    wait: WaitArgs,
 
     #[clap(skip)]
     pub api: Option<Api>,
@@ -739,7 +958,9 @@ pub struct GetCommand {
 
 impl GetCommand {
     async fn run(self, global_options: GlobalOptions) -> Result<Option<GetBinaryResponse>, Error> {
-        let params = GetBinaryParams { prn: self.prn };
+        let params = GetBinaryParams {
+            prn: self.prn.to_string(),
+        };
 
         let api = if let Some(api) = self.api {
             api
@@ -757,6 +978,14 @@ impl GetCommand {
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        if self.inner.wait.wait {
+            let binary =
+                wait_for_binary(&global_options, &self.inner.prn, &self.inner.wait).await?;
+            print_json!(&binary);
+
+            return Ok(());
+        }
+
         match self.inner.run(global_options).await? {
             Some(binary) => print_json!(&binary),
             None => panic!(),
@@ -773,9 +1002,9 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Binary)
     )]
-    prn: String,
+    prn: Prn,
 
-    /// A JSON object that informs the metadata that will be associated with this binary when it is included in bundles.
+    /// A JSON object, @PATH to a JSON file or @- to read it from stdin, that informs the metadata that will be associated with this binary when it is included in bundles.
     #[arg(long)]
     pub custom_metadata: Option<String>,
 
@@ -795,6 +1024,9 @@ pub struct UpdateCommand {
     #[arg(long)]
     pub size: Option<u64>,
 
+    #[command(flatten)]
+    pub wait: WaitArgs,
+
     #[clap(skip)]
     pub api: Option<Api>,
 }
@@ -805,8 +1037,8 @@ impl UpdateCommand {
         global_options: GlobalOptions,
     ) -> Result<Option<UpdateBinaryResponse>, Error> {
         let params = UpdateBinaryParams {
-            prn: self.prn,
-            custom_metadata: maybe_json(self.custom_metadata),
+            prn: self.prn.to_string(),
+            custom_metadata: maybe_json_from_source(self.custom_metadata)?,
             description: self.description,
             state: self.state.map(BinaryState::from),
             hash: self.hash,
@@ -829,7 +1061,14 @@ impl UpdateCommand {
 
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        match self.inner.run(global_options).await? {
+        let prn = self.inner.prn.clone();
+        let wait = self.inner.wait.clone();
+
+        match self.inner.run(global_options.clone()).await? {
+            // e.g. hashable binaries are hashed by Peridio after the update
+            Some(_) if wait.wait => {
+                print_json!(&wait_for_binary(&global_options, &prn, &wait).await?)
+            }
             Some(binary) => print_json!(&binary),
             None => panic!(),
         }
@@ -848,6 +1087,19 @@ pub enum ArgBinaryState {
     Uploadable,
 }
 
+impl ArgBinaryState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Destroyed => "destroyed",
+            Self::Hashable => "hashable",
+            Self::Hashing => "hashing",
+            Self::Signable => "signable",
+            Self::Signed => "signed",
+            Self::Uploadable => "uploadable",
+        }
+    }
+}
+
 impl From<ArgBinaryState> for BinaryState {
     fn from(other: ArgBinaryState) -> BinaryState {
         match other {
@@ -860,3 +1112,331 @@ impl From<ArgBinaryState> for BinaryState {
         }
     }
 }
+
+// only hashing binaries change state on their own, the other states wait for someone to act
+const BINARY_TERMINAL: Terminal = Terminal {
+    pointer: "/binary/state",
+    states: &["destroyed", "hashable", "signable", "signed", "uploadable"],
+};
+
+// poll the binary until its state is terminal, see `--wait`
+async fn wait_for_binary(
+    global_options: &GlobalOptions,
+    prn: &Prn,
+    args: &WaitArgs,
+) -> Result<serde_json::Value, Error> {
+    let client = &client::Client::new(global_options)?;
+    let path = &format!("/binaries/{prn}");
+
+    wait::until_terminal(args, &BINARY_TERMINAL, move || client.get(path)).await
+}
+
+/// Sign a binary that was already uploaded with a local private key.
+///
+/// The hash Peridio computed for the binary's content is signed, so the content itself is not
+/// needed.
+#[derive(Parser, Debug)]
+pub struct SignCommand {
+    /// The PRN of the binary to sign.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Binary)
+    )]
+    prn: Prn,
+
+    /// The name of a signing key pair in your Peridio CLI config, which provides both the private key and the signing key PRN.
+    #[arg(
+        long,
+        conflicts_with = "signing_key_private",
+        conflicts_with = "signing_key_prn",
+        required_unless_present_any = ["signing_key_private", "signing_key_prn"],
+    )]
+    signing_key_pair: Option<String>,
+
+    /// A path to a PKCS#8 private key encoded as a pem to sign the binary with.
+    #[arg(long, requires = "signing_key_prn")]
+    signing_key_private: Option<String>,
+
+    /// The PRN of the signing key Peridio will use to verify the binary signature.
+    #[arg(
+        long,
+        requires = "signing_key_private",
+        value_parser = PRNValueParser::new(PRNType::SigningKey)
+    )]
+    signing_key_prn: Option<Prn>,
+}
+
+impl Command<SignCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let signing_key_private = match &self.inner.signing_key_pair {
+            Some(signing_key_pair) => {
+                signing_key_pair_from_config(&global_options, signing_key_pair)?
+                    .signing_key_private_path
+            }
+            None => self.inner.signing_key_private.clone().unwrap(),
+        };
+
+        // a broken key file fails before anything is requested
+        let signing_key = read_signing_key(&signing_key_private)?;
+
+        let api = Api::new(ApiOptions {
+            api_key: global_options.api_key.clone().unwrap(),
+            endpoint: global_options.base_url.clone(),
+            ca_bundle_path: global_options.ca_path.clone(),
+        });
+
+        let get_command = GetCommand {
+            prn: self.inner.prn.clone(),
+            wait: WaitArgs::default(),
+            api: Some(api.clone()),
+        };
+
+        let binary = match get_command.run(global_options.clone()).await? {
+            Some(GetBinaryResponse { binary }) => binary,
+            None => panic!(),
+        };
+
+        let Some(hash) = binary.hash else {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("The binary has no hash to sign yet, upload its content first:\r\n")
+                .warning(format!("\t{}", self.inner.prn));
+            return Err(error.data_err());
+        };
+
+        let signature = sign_hash_with(&signing_key, &hash);
+
+        let command = crate::api::binary_signatures::CreateCommand {
+            binary_prn: self.inner.prn,
+            binary_content_path: None,
+            signature: Some(signature),
+            // only the signing key PRN is taken from these, the signature is already made
+            signing_key_pair: self.inner.signing_key_pair,
+            signing_key_private: None,
+            signing_key_prn: self.inner.signing_key_prn,
+            api: Some(api),
+            binary_content_hash: None,
+        };
+
+        match command.run(global_options).await? {
+            Some(binary_signature) => print_created!(&binary_signature),
+            None => panic!(),
+        }
+
+        Ok(())
+    }
+}
+
+/// Download the content of a binary.
+///
+/// The content is fetched from the signed URL the API returns with the binary, streamed to disk
+/// and checked against the binary's SHA256 hash unless --no-verify is passed, a file is only
+/// written at --output-path once its content matches.
+#[derive(Parser, Debug)]
+pub struct DownloadCommand {
+    /// The PRN of the binary to download.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Binary)
+    )]
+    prn: Prn,
+
+    /// Where to write the content, missing directories are created. With - the content is written to stdout, and checked against the hash only once it was written.
+    #[arg(long)]
+    output_path: PathBuf,
+
+    /// Overwrite the file at --output-path if it exists.
+    #[arg(long)]
+    force: bool,
+
+    /// Write the content without checking it against the binary's hash.
+    #[arg(long)]
+    no_verify: bool,
+}
+
+impl Command<DownloadCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let output_path = Some(&self.inner.output_path).filter(|path| path.as_os_str() != "-");
+
+        if let Some(output_path) = output_path {
+            if output_path.exists() && !self.inner.force {
+                let mut error = StyledStr::new();
+                error
+                    .error("error: ")
+                    .plain("The file already exists, pass --force to overwrite it:\r\n")
+                    .warning(format!("\t{}", output_path.display()));
+                return Err(error.data_err());
+            }
+        }
+
+        let client = client::Client::new(&global_options)?;
+        let prn = &self.inner.prn;
+
+        let binary = client.get(&format!("/binaries/{prn}")).await?;
+        let hash = binary
+            .pointer("/binary/hash")
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_owned)
+            .filter(|_| !self.inner.no_verify);
+
+        if hash.is_none() && !self.inner.no_verify {
+            output::warn(format!(
+                "{prn} is not hashed yet, its content can't be verified"
+            ));
+        }
+
+        let Some(url) = binary
+            .pointer("/binary/url")
+            .and_then(serde_json::Value::as_str)
+        else {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("The API returned no download URL for the binary:\r\n")
+                .warning(format!("\t{prn}"));
+            return Err(error.data_err());
+        };
+
+        let mut response = client.get_signed(url).await?;
+
+        let mut temp_file = match output_path {
+            Some(output_path) => {
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent).context(FileSnafu)?;
+                }
+
+                Some(TempFile::new(output_path).context(FileSnafu)?)
+            }
+            None => None,
+        };
+
+        let pb = output::progress_bar(response.content_length().unwrap_or_default());
+        pb.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} ({eta})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+        );
+
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+
+        // one chunk at a time, so the content is never held in memory as a whole
+        while let Some(chunk) = response.chunk().await.map_err(client::network)? {
+            hasher.update(&chunk);
+            size += chunk.len() as u64;
+            pb.set_position(size);
+
+            match &mut temp_file {
+                Some(temp_file) => temp_file.file().write_all(&chunk),
+                None => io::stdout().write_all(&chunk),
+            }
+            .context(FileSnafu)?;
+        }
+
+        pb.finish_and_clear();
+
+        let downloaded = format!("{:x}", hasher.finalize());
+
+        if let Some(hash) = hash.filter(|hash| !hash.eq_ignore_ascii_case(&downloaded)) {
+            // dropping the temp file removes the partial download
+            drop(temp_file);
+
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("The downloaded content does not match the binary's hash:\r\n")
+                .warning(format!("\texpected {hash}\r\n\tgot      {downloaded}"));
+            return Err(error.data_err());
+        }
+
+        match temp_file {
+            Some(temp_file) => {
+                let output_path = temp_file.persist().context(FileSnafu)?;
+                output::status(&format!(
+                    "Downloaded {} to {}",
+                    HumanBytes(size),
+                    output_path.display()
+                ));
+            }
+            None => io::stdout().flush().context(FileSnafu)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTIFACT_VERSION_PRN: &str = "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef:artifact_version:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+    fn list(filters: &[&str]) -> Result<ListCommand, clap::Error> {
+        let args = ["list", "--search", "name:'firmware'"]
+            .iter()
+            .chain(filters);
+
+        ListCommand::try_parse_from(args)
+    }
+
+    #[test]
+    fn without_filters_the_query_is_empty() {
+        assert!(list(&[]).unwrap().filter_query().is_empty());
+    }
+
+    #[test]
+    fn each_filter_is_a_query_parameter() {
+        assert_eq!(
+            list(&["--artifact-version-prn", ARTIFACT_VERSION_PRN])
+                .unwrap()
+                .filter_query(),
+            vec![("artifact_version_prn", ARTIFACT_VERSION_PRN.to_string())]
+        );
+        assert_eq!(
+            list(&["--target", "arm64-v8"]).unwrap().filter_query(),
+            vec![("target", "arm64-v8".to_string())]
+        );
+        assert_eq!(
+            list(&["--state", "signable"]).unwrap().filter_query(),
+            vec![("state", "signable".to_string())]
+        );
+    }
+
+    #[test]
+    fn filters_combine() {
+        assert_eq!(
+            list(&[
+                "--state",
+                "signed",
+                "--target",
+                "arm64-v8",
+                "--artifact-version-prn",
+                ARTIFACT_VERSION_PRN,
+            ])
+            .unwrap()
+            .filter_query(),
+            vec![
+                ("artifact_version_prn", ARTIFACT_VERSION_PRN.to_string()),
+                ("target", "arm64-v8".to_string()),
+                ("state", "signed".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_states_are_rejected() {
+        let error = list(&["--state", "uploaded"]).unwrap_err();
+
+        assert_eq!(error.kind(), clap::error::ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn artifact_version_prns_of_other_resources_are_rejected() {
+        let binary_prn = ARTIFACT_VERSION_PRN.replace("artifact_version", "binary");
+
+        assert!(list(&["--artifact-version-prn", &binary_prn]).is_err());
+    }
+}
@@ -1,21 +1,37 @@
 use super::Command;
+use crate::print_created;
 use crate::print_json;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
+use crate::utils::{Style, StyledStr};
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
 use clap::Parser;
+use futures_util::stream;
+use futures_util::StreamExt;
 use peridio_sdk::api::binary_parts::CreateBinaryPartParams;
 use peridio_sdk::api::binary_parts::CreateBinaryPartResponse;
 use peridio_sdk::api::binary_parts::ListBinaryPartsParams;
 use peridio_sdk::api::binary_parts::ListBinaryPartsResponse;
 use peridio_sdk::api::Api;
 use peridio_sdk::api::ApiOptions;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
 use snafu::ResultExt;
 use std::fs;
+use std::future::Future;
+use std::io::{self, Read, Seek};
 use std::path::PathBuf;
 
+/// The smallest binary part the API accepts, only the last part of a binary may be smaller.
+pub const MIN_PART_SIZE: u64 = 5242880;
+/// The largest binary part the API accepts.
+pub const MAX_PART_SIZE: u64 = 50000000000;
+/// The most parts a single binary can be split into.
+pub const MAX_PARTS: u64 = 10000;
+
 #[derive(Parser, Debug)]
 pub enum BinaryPartsCommand {
     Create(Command<CreateCommand>),
@@ -41,7 +57,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Binary)
     )]
-    pub binary_prn: String,
+    pub binary_prn: Prn,
     /// The total size of the binary's content.
     #[arg(
         long,
@@ -50,14 +66,22 @@ pub struct CreateCommand {
     )]
     pub expected_binary_size: Option<u64>,
     /// The lowercase hex encoding of the SHA256 hash of the binary part's data.
-    #[arg(long)]
-    pub hash: String,
+    #[arg(long, required_unless_present("chunk_size"))]
+    pub hash: Option<String>,
     /// Uniquely identifies a binary part and defines its position within the binary being created. Can be any number from 1 to 10,000, inclusive. If you create a binary part using the same index that was used with a previous binary part, the previously uploaded binary part is overwritten.
-    #[arg(long)]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..=MAX_PARTS as i64))]
     pub index: u16,
     /// The size in bytes of the binary part.
-    #[arg(long)]
-    pub size: u64,
+    #[arg(long, required_unless_present("chunk_size"))]
+    pub size: Option<u64>,
+    /// Split --binary-content-path into parts of this size and derive --size and --hash for the part at --index. Every part is this size except the last, which holds the remainder.
+    #[arg(
+        long,
+        requires("binary_content_path"),
+        conflicts_with_all(["hash", "size"]),
+        value_parser = clap::value_parser!(u64).range(MIN_PART_SIZE..MAX_PART_SIZE),
+    )]
+    pub chunk_size: Option<u64>,
     /// The path to the file you wish to upload as the binary's content.
     #[arg(
         long,
@@ -75,19 +99,47 @@ impl CreateCommand {
         self,
         global_options: GlobalOptions,
     ) -> Result<Option<CreateBinaryPartResponse>, Error> {
-        let expected_binary_size = if let Some(binary_content_path) = self.binary_content_path {
+        let expected_binary_size = if let Some(binary_content_path) = &self.binary_content_path {
             let file = fs::File::open(binary_content_path).unwrap();
             file.metadata().unwrap().len()
         } else {
             self.expected_binary_size.unwrap()
         };
 
+        let (hash, size) = match self.chunk_size {
+            Some(chunk_size) => {
+                let part_count =
+                    part_count(expected_binary_size, chunk_size).map_err(part_error)?;
+
+                if u64::from(self.index) > part_count {
+                    return Err(part_error(format!(
+                        "index {} is past the last part, the binary has {part_count} parts of {chunk_size} bytes",
+                        self.index
+                    )));
+                }
+
+                let size = part_size(expected_binary_size, chunk_size, self.index.into());
+
+                let mut file = fs::File::open(self.binary_content_path.unwrap()).unwrap();
+                file.seek(io::SeekFrom::Start(
+                    chunk_size * (u64::from(self.index) - 1),
+                ))
+                .unwrap();
+
+                let mut buffer = vec![0; size.try_into().unwrap()];
+                file.read_exact(&mut buffer).unwrap();
+
+                (part_hash(&buffer), size)
+            }
+            None => (self.hash.unwrap(), self.size.unwrap()),
+        };
+
         let params = CreateBinaryPartParams {
-            binary_prn: self.binary_prn,
+            binary_prn: self.binary_prn.to_string(),
             index: self.index,
             expected_binary_size,
-            hash: self.hash,
-            size: self.size,
+            hash,
+            size,
         };
 
         let api = if let Some(api) = self.api {
@@ -107,7 +159,7 @@ impl CreateCommand {
 impl Command<CreateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         match self.inner.run(global_options).await? {
-            Some(binary_part) => print_json!(&binary_part),
+            Some(binary_part) => print_created!(&binary_part),
             None => panic!(),
         }
 
@@ -121,7 +173,7 @@ pub struct ListCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Binary)
     )]
-    pub binary_prn: String,
+    pub binary_prn: Prn,
 
     #[clap(skip)]
     pub api: Option<Api>,
@@ -133,7 +185,7 @@ impl ListCommand {
         global_options: GlobalOptions,
     ) -> Result<Option<ListBinaryPartsResponse>, Error> {
         let params = ListBinaryPartsParams {
-            binary_prn: self.binary_prn,
+            binary_prn: self.binary_prn.to_string(),
         };
 
         let api = if let Some(api) = self.api {
@@ -160,3 +212,270 @@ impl Command<ListCommand> {
         Ok(())
     }
 }
+
+/// The number of parts `binary_size` bytes split into at `part_size`, if the API allows that many.
+pub fn part_count(binary_size: u64, part_size: u64) -> Result<u64, String> {
+    let count = binary_size.div_ceil(part_size);
+
+    if count > MAX_PARTS {
+        let smallest_part_size = binary_size.div_ceil(MAX_PARTS).max(MIN_PART_SIZE);
+
+        return Err(format!(
+            "{binary_size} bytes split into {count} parts of {part_size} bytes, more than the {MAX_PARTS} allowed, use a part size of at least {smallest_part_size} bytes"
+        ));
+    }
+
+    Ok(count)
+}
+
+/// The size of the part at the 1-based `index`, every part is `part_size` except the last one.
+pub fn part_size(binary_size: u64, part_size: u64, index: u64) -> u64 {
+    part_size.min(binary_size.saturating_sub(part_size * (index - 1)))
+}
+
+/// The lowercase hex SHA256 hash of a part's content, as sent when creating the part.
+pub fn part_hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Check that the server recorded the hash that was sent for the part at `index`.
+///
+/// `binary_part` is the created part as returned by the API, a part without a hash passes.
+pub fn verify_part_hash(index: u64, sent: &str, binary_part: &Value) -> Result<(), String> {
+    match binary_part.get("hash").and_then(Value::as_str) {
+        Some(recorded) if recorded != sent => Err(format!(
+            "part {index}: the server recorded hash {recorded}, expected {sent}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the part at `index`, `size` bytes hashing to `hash`, was uploaded already so its
+/// upload can be skipped, an error when it was uploaded with other content.
+///
+/// Only a `resume`d upload skips parts, `uploaded` are the valid parts the API lists for the binary.
+pub fn skip_uploaded_part(
+    index: u64,
+    size: u64,
+    hash: &str,
+    uploaded: &[Value],
+    resume: bool,
+) -> Result<bool, String> {
+    if !resume {
+        return Ok(false);
+    }
+
+    let Some(part) = uploaded
+        .iter()
+        .find(|part| part.get("index").and_then(Value::as_u64) == Some(index))
+    else {
+        return Ok(false);
+    };
+
+    let same_size = part.get("size").and_then(Value::as_u64) == Some(size);
+    let same_hash = part
+        .get("hash")
+        .and_then(Value::as_str)
+        .map_or(true, |uploaded| uploaded == hash);
+
+    if same_size && same_hash {
+        Ok(true)
+    } else {
+        Err("already uploaded with different content".to_string())
+    }
+}
+
+/// Why a part failed to upload, transient when uploading it again later may succeed.
+#[derive(Debug, PartialEq)]
+pub struct PartFailure {
+    pub message: String,
+    pub transient: bool,
+}
+
+impl PartFailure {
+    pub fn transient(message: impl ToString) -> Self {
+        Self {
+            message: message.to_string(),
+            transient: true,
+        }
+    }
+}
+
+impl From<Error> for PartFailure {
+    fn from(error: Error) -> Self {
+        Self {
+            message: error.to_string(),
+            transient: error.exit_code() == 75,
+        }
+    }
+}
+
+impl From<String> for PartFailure {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            transient: false,
+        }
+    }
+}
+
+/// Run `upload` for the parts at the 1-based indexes up to `count`, at most `concurrency` at a
+/// time, returning the result of each part in the order of their indexes.
+pub async fn upload_parts<F, Fut, T>(
+    count: u64,
+    concurrency: usize,
+    mut upload: F,
+) -> Vec<(u64, Result<T, PartFailure>)>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<T, PartFailure>>,
+{
+    let mut results: Vec<_> = stream::iter(1..=count)
+        .map(|index| {
+            let part = upload(index);
+            async move { (index, part.await) }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    results.sort_by_key(|(index, _)| *index);
+
+    results
+}
+
+pub fn hash_mismatch_error(message: String) -> Error {
+    let mut error = StyledStr::new();
+    error
+        .error("error: ")
+        .plain("Binary part integrity check failed, aborting the upload:\r\n")
+        .warning(format!("\t{message}"));
+    error.data_err()
+}
+
+/// The error naming the parts that failed to upload, a temporary failure when uploading them
+/// again later may succeed.
+pub fn upload_failures_error(failures: &[(u64, PartFailure)]) -> Error {
+    let mut error = StyledStr::new();
+    error.push_str(Some(Style::Error), "error: ".to_string());
+    error.push_str(
+        None,
+        format!("Unable to upload {} binary parts:", failures.len()),
+    );
+
+    for (index, failure) in failures {
+        error.push_str(
+            Some(Style::Warning),
+            format!("\r\n\tpart {index}: {}", failure.message),
+        );
+    }
+
+    if failures.iter().all(|(_, failure)| failure.transient) {
+        return error.temp_fail_err();
+    }
+
+    error.data_err()
+}
+
+pub fn part_error(message: String) -> Error {
+    let mut error = StyledStr::new();
+    error.push_str(Some(Style::Error), "error: ".to_string());
+    error.push_str(
+        None,
+        "Unable to split the binary into parts:\r\n".to_string(),
+    );
+    error.push_str(Some(Style::Warning), format!("\t{message}"));
+    error.data_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    const ABC_HASH: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn part_hash_is_lowercase_hex_sha256() {
+        assert_eq!(part_hash(b"abc"), ABC_HASH);
+    }
+
+    #[test]
+    fn echoed_hash_passes() {
+        let hash = part_hash(b"abc");
+
+        assert_eq!(
+            verify_part_hash(1, &hash, &json!({ "hash": ABC_HASH })),
+            Ok(())
+        );
+        assert_eq!(verify_part_hash(1, &hash, &json!({ "index": 1 })), Ok(()));
+    }
+
+    #[test]
+    fn mismatched_hash_names_the_part() {
+        let corrupted = part_hash(b"abd");
+
+        let error =
+            verify_part_hash(3, &part_hash(b"abc"), &json!({ "hash": corrupted })).unwrap_err();
+
+        assert!(error.starts_with("part 3: "), "{error}");
+        assert!(error.contains(&corrupted), "{error}");
+        assert!(error.contains(ABC_HASH), "{error}");
+    }
+
+    #[tokio::test]
+    async fn parts_are_uploaded_at_most_concurrency_at_a_time() {
+        let in_flight = AtomicUsize::new(0);
+        let most_in_flight = AtomicUsize::new(0);
+
+        let results = upload_parts(10, 3, |index| {
+            let (in_flight, most_in_flight) = (&in_flight, &most_in_flight);
+
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                most_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                // later parts finish first
+                tokio::time::sleep(Duration::from_millis(50 - index * 4)).await;
+
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok::<_, PartFailure>(index)
+            }
+        })
+        .await;
+
+        assert_eq!(
+            results,
+            (1..=10).map(|index| (index, Ok(index))).collect::<Vec<_>>()
+        );
+        assert_eq!(most_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn uploaded_parts_are_skipped_only_when_resuming() {
+        let uploaded = [json!({ "index": 2, "size": 3, "hash": ABC_HASH })];
+
+        assert_eq!(
+            skip_uploaded_part(2, 3, ABC_HASH, &uploaded, true),
+            Ok(true)
+        );
+        assert_eq!(
+            skip_uploaded_part(2, 3, ABC_HASH, &uploaded, false),
+            Ok(false)
+        );
+        assert_eq!(
+            skip_uploaded_part(1, 3, ABC_HASH, &uploaded, true),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn parts_uploaded_with_other_content_are_not_skipped() {
+        let uploaded = [json!({ "index": 1, "size": 3, "hash": ABC_HASH })];
+
+        assert!(skip_uploaded_part(1, 3, &part_hash(b"abd"), &uploaded, true).is_err());
+        assert!(skip_uploaded_part(1, 4, ABC_HASH, &uploaded, true).is_err());
+    }
+}
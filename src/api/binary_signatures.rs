@@ -2,9 +2,12 @@ use std::fs;
 use std::io;
 
 use super::Command;
+use crate::config::config_v2::SigningKeyPairV2;
+use crate::print_created;
 use crate::print_json;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
 use crate::utils::Style;
 use crate::utils::StyledStr;
 use crate::ApiSnafu;
@@ -13,8 +16,12 @@ use crate::GlobalOptions;
 use crate::NonExistingPathSnafu;
 use clap::Parser;
 use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::pkcs8::DecodePublicKey;
+use ed25519_dalek::Signature;
 use ed25519_dalek::Signer;
 use ed25519_dalek::SigningKey;
+use ed25519_dalek::Verifier;
+use ed25519_dalek::VerifyingKey;
 use peridio_sdk::api::binary_signatures::CreateBinarySignatureParams;
 use peridio_sdk::api::binary_signatures::CreateBinarySignatureResponse;
 use peridio_sdk::api::binary_signatures::DeleteBinarySignatureParams;
@@ -47,7 +54,7 @@ pub struct CreateCommand {
         short = 'b',
         value_parser = PRNValueParser::new(PRNType::Binary)
     )]
-    pub binary_prn: String,
+    pub binary_prn: Prn,
     /// The path of the file to automatically create a signature for. If you instead want to compute and provide the signature yourself, use the --signature option.
     #[arg(
         long,
@@ -98,7 +105,7 @@ pub struct CreateCommand {
         required_unless_present = "signing_key_pair",
         value_parser = PRNValueParser::new(PRNType::SigningKey)
     )]
-    pub signing_key_prn: Option<String>,
+    pub signing_key_prn: Option<Prn>,
 
     #[clap(skip)]
     pub api: Option<Api>,
@@ -114,52 +121,21 @@ impl CreateCommand {
     ) -> Result<Option<CreateBinarySignatureResponse>, Error> {
         // user provides a signing_key_pair
         let (signing_key_prn, signature) = if let Some(signing_key_pair) = self.signing_key_pair {
-            if let Some(signing_key_pairs) = global_options.signing_key_pairs {
-                if let Some(key_pair) = signing_key_pairs.get(&signing_key_pair) {
-                    // first we check for a binary path is provided
-                    let signature = if let Some(binary_content_path) = self.binary_content_path {
-                        Self::sign_binary(
-                            key_pair.signing_key_private_path.clone(),
-                            binary_content_path,
-                            self.binary_content_hash.clone(),
-                        )?
-                    } else {
-                        // otherwise the user must provide a signature
-                        self.signature.unwrap()
-                    };
-
-                    (key_pair.signing_key_prn.clone(), signature)
-                } else {
-                    let mut error = StyledStr::new();
-                    error.push_str(Some(Style::Error), "error: ".to_string());
-                    error.push_str(None, "Config file field ".to_string());
-                    error.push_str(None, "'".to_string());
-                    error.push_str(
-                        Some(Style::Warning),
-                        format!("signing_key_pairs.{signing_key_pair}").to_string(),
-                    );
-                    error.push_str(None, "'".to_string());
-                    error.push_str(
-                        None,
-                        " is unset or null, but is required by the --signing-key-pair option."
-                            .to_string(),
-                    );
-                    error.print_data_err()
-                }
+            let key_pair = signing_key_pair_from_config(&global_options, &signing_key_pair)?;
+
+            // first we check for a binary path is provided
+            let signature = if let Some(binary_content_path) = self.binary_content_path {
+                Self::sign_binary(
+                    key_pair.signing_key_private_path.clone(),
+                    binary_content_path,
+                    self.binary_content_hash.clone(),
+                )?
             } else {
-                let mut error = StyledStr::new();
-                error.push_str(Some(Style::Error), "error: ".to_string());
-                error.push_str(None, "Config file field ".to_string());
-                error.push_str(None, "'".to_string());
-                error.push_str(Some(Style::Warning), "signing_key_pairs".to_string());
-                error.push_str(None, "'".to_string());
-                error.push_str(
-                    None,
-                    " is unset or null, but is required by the --signing-key-pair option."
-                        .to_string(),
-                );
-                error.print_data_err();
-            }
+                // otherwise the user must provide a signature
+                self.signature.unwrap()
+            };
+
+            (key_pair.signing_key_prn, signature)
         } else if let Some(signing_key_private_path) = self.signing_key_private {
             let binary_content_path = self.binary_content_path.unwrap();
             let signature = Self::sign_binary(
@@ -167,13 +143,16 @@ impl CreateCommand {
                 binary_content_path,
                 self.binary_content_hash.clone(),
             )?;
-            (self.signing_key_prn.unwrap(), signature)
+            (self.signing_key_prn.unwrap().to_string(), signature)
         } else {
-            (self.signing_key_prn.unwrap(), self.signature.unwrap())
+            (
+                self.signing_key_prn.unwrap().to_string(),
+                self.signature.unwrap(),
+            )
         };
 
         let params = CreateBinarySignatureParams {
-            binary_prn: self.binary_prn,
+            binary_prn: self.binary_prn.to_string(),
             signing_key_prn,
             signature,
         };
@@ -199,12 +178,6 @@ impl CreateCommand {
         binary_content_path: String,
         binary_content_hash: Option<String>,
     ) -> Result<String, Error> {
-        let signing_key_private =
-            fs::read_to_string(&signing_key_private_path).context(NonExistingPathSnafu {
-                path: &signing_key_private_path,
-            })?;
-        let signing_key: SigningKey = SigningKey::from_pkcs8_pem(&signing_key_private).unwrap();
-
         let hash = if let Some(hash) = binary_content_hash {
             hash
         } else {
@@ -215,16 +188,113 @@ impl CreateCommand {
             format!("{hash:x}")
         };
 
-        let signed_hash = signing_key.sign(hash.as_bytes());
+        sign_hash(&signing_key_private_path, &hash)
+    }
+}
+
+/// Sign the lowercase hex SHA256 `hash` of a binary's content, the way Peridio verifies it.
+pub fn sign_hash(signing_key_private_path: &str, hash: &str) -> Result<String, Error> {
+    let signing_key = read_signing_key(signing_key_private_path)?;
+
+    Ok(sign_hash_with(&signing_key, hash))
+}
+
+/// Sign `hash` with a private key that was already read, see [`sign_hash`].
+pub fn sign_hash_with(signing_key: &SigningKey, hash: &str) -> String {
+    format!("{:X}", signing_key.sign(hash.as_bytes()))
+}
+
+/// Read the Ed25519 private key of a PKCS#8 PEM file, failing with a data error when it isn't one.
+pub fn read_signing_key(signing_key_private_path: &str) -> Result<SigningKey, Error> {
+    let signing_key_private =
+        fs::read_to_string(signing_key_private_path).context(NonExistingPathSnafu {
+            path: signing_key_private_path,
+        })?;
+
+    match SigningKey::from_pkcs8_pem(&signing_key_private) {
+        Ok(signing_key) => Ok(signing_key),
+        Err(source) => {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("Not an Ed25519 private key in PKCS#8 PEM format:\r\n")
+                .warning(format!("\t{signing_key_private_path}: {source}"));
+            Err(error.data_err())
+        }
+    }
+}
+
+/// Whether the hex encoded `signature` of `hash` verifies against the PEM encoded public key.
+pub fn verify_hash(signing_key_public_pem: &str, hash: &str, signature: &str) -> bool {
+    let Ok(verifying_key) = VerifyingKey::from_public_key_pem(signing_key_public_pem) else {
+        return false;
+    };
+
+    let Some(signature) = decode_hex(signature) else {
+        return false;
+    };
+
+    let Ok(signature) = Signature::from_slice(&signature) else {
+        return false;
+    };
+
+    verifying_key.verify(hash.as_bytes(), &signature).is_ok()
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
 
-        Ok(format!("{signed_hash:X}"))
+    (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(value.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// Look up a signing key pair in the CLI config, failing with a data error when it isn't there.
+pub fn signing_key_pair_from_config(
+    global_options: &GlobalOptions,
+    signing_key_pair: &str,
+) -> Result<SigningKeyPairV2, Error> {
+    if let Some(signing_key_pairs) = &global_options.signing_key_pairs {
+        if let Some(key_pair) = signing_key_pairs.get(signing_key_pair) {
+            Ok(key_pair.clone())
+        } else {
+            let mut error = StyledStr::new();
+            error.push_str(Some(Style::Error), "error: ".to_string());
+            error.push_str(None, "Config file field ".to_string());
+            error.push_str(None, "'".to_string());
+            error.push_str(
+                Some(Style::Warning),
+                format!("signing_key_pairs.{signing_key_pair}").to_string(),
+            );
+            error.push_str(None, "'".to_string());
+            error.push_str(
+                None,
+                " is unset or null, but is required by the --signing-key-pair option.".to_string(),
+            );
+            Err(error.data_err())
+        }
+    } else {
+        let mut error = StyledStr::new();
+        error.push_str(Some(Style::Error), "error: ".to_string());
+        error.push_str(None, "Config file field ".to_string());
+        error.push_str(None, "'".to_string());
+        error.push_str(Some(Style::Warning), "signing_key_pairs".to_string());
+        error.push_str(None, "'".to_string());
+        error.push_str(
+            None,
+            " is unset or null, but is required by the --signing-key-pair option.".to_string(),
+        );
+        Err(error.data_err())
     }
 }
 
 impl Command<CreateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         match self.inner.run(global_options).await? {
-            Some(binary_signature) => print_json!(&binary_signature),
+            Some(binary_signature) => print_created!(&binary_signature),
             None => panic!(),
         }
 
@@ -239,13 +309,13 @@ pub struct DeleteCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::BinarySignature)
     )]
-    binary_signature_prn: String,
+    binary_signature_prn: Prn,
 }
 
 impl Command<DeleteCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = DeleteBinarySignatureParams {
-            binary_signature_prn: self.inner.binary_signature_prn,
+            binary_signature_prn: self.inner.binary_signature_prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -267,3 +337,52 @@ impl Command<DeleteCommand> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+    use std::io::Write;
+
+    const HASH: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn private_key_file(signing_key: &SigningKey) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let pem = signing_key.to_pkcs8_pem(LineEnding::LF).unwrap();
+        file.write_all(pem.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn signature_verifies_against_the_public_key() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let file = private_key_file(&signing_key);
+        let public_pem = signing_key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+
+        let signature = sign_hash(file.path().to_str().unwrap(), HASH).unwrap();
+
+        assert_eq!(signature.len(), 128);
+        assert!(verify_hash(&public_pem, HASH, &signature));
+        assert!(!verify_hash(
+            &public_pem,
+            &HASH.replace('b', "c"),
+            &signature
+        ));
+    }
+
+    #[test]
+    fn signature_does_not_verify_against_another_key() {
+        let file = private_key_file(&SigningKey::from_bytes(&[7; 32]));
+        let other_pem = SigningKey::from_bytes(&[8; 32])
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+
+        let signature = sign_hash(file.path().to_str().unwrap(), HASH).unwrap();
+
+        assert!(!verify_hash(&other_pem, HASH, &signature));
+    }
+}
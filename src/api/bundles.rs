@@ -1,16 +1,29 @@
+use std::collections::HashMap;
+
 use super::Command;
+use crate::api::binary_signatures::{sign_hash, signing_key_pair_from_config, verify_hash};
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::table::{self, Column};
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
+use crate::utils::{Style, StyledStr};
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
+use crate::JsonSerializationSnafu;
 use clap::Parser;
+use peridio_sdk::api::binaries::{BinaryState, GetBinaryParams, UpdateBinaryParams};
+use peridio_sdk::api::binary_signatures::CreateBinarySignatureParams;
 use peridio_sdk::api::bundles::UpdateBundleParams;
 use peridio_sdk::api::bundles::{CreateBundleParams, GetBundleParams, ListBundlesParams};
+use peridio_sdk::api::signing_keys::GetSigningKeyParams;
 use peridio_sdk::api::Api;
 use peridio_sdk::api::ApiOptions;
+use serde_json::{json, Value};
 use snafu::ResultExt;
 
 #[derive(Parser, Debug)]
@@ -19,6 +32,8 @@ pub enum BundlesCommand {
     List(Command<ListCommand>),
     Get(Command<GetCommand>),
     Update(Command<UpdateCommand>),
+    Verify(Command<VerifyCommand>),
+    Sign(Command<SignCommand>),
 }
 
 impl BundlesCommand {
@@ -28,6 +43,8 @@ impl BundlesCommand {
             Self::List(cmd) => cmd.run(global_options).await,
             Self::Get(cmd) => cmd.run(global_options).await,
             Self::Update(cmd) => cmd.run(global_options).await,
+            Self::Verify(cmd) => cmd.run(global_options).await,
+            Self::Sign(cmd) => cmd.run(global_options).await,
         }
     }
 }
@@ -48,7 +65,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Organization)
     )]
-    organization_prn: String,
+    organization_prn: Prn,
 
     /// The name of the bundle.
     #[arg(long)]
@@ -60,7 +77,7 @@ impl Command<CreateCommand> {
         let params = CreateBundleParams {
             artifact_version_prns: self.inner.artifact_version_prns,
             id: self.inner.id,
-            organization_prn: self.inner.organization_prn,
+            organization_prn: self.inner.organization_prn.to_string(),
             name: self.inner.name,
         };
 
@@ -71,7 +88,7 @@ impl Command<CreateCommand> {
         });
 
         match api.bundles().create(params).await.context(ApiSnafu)? {
-            Some(bundle) => print_json!(&bundle),
+            Some(bundle) => print_created!(&bundle),
             None => panic!(),
         }
 
@@ -87,25 +104,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListBundlesParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
+        let api = &api;
+        let list_args = &self.inner.list_args;
 
-        match api.bundles().list(params).await.context(ApiSnafu)? {
-            Some(bundle) => print_json!(&bundle),
-            None => panic!(),
-        }
+        list_args
+            .print(|page| {
+                let params = ListBundlesParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
 
-        Ok(())
+                async move { api.bundles().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -116,13 +134,13 @@ pub struct GetCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Bundle)
     )]
-    prn: String,
+    prn: Prn,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = GetBundleParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -147,7 +165,7 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Bundle)
     )]
-    prn: String,
+    prn: Prn,
 
     /// The resource's name, meant to be displayable to users.
     #[arg(long)]
@@ -157,7 +175,7 @@ pub struct UpdateCommand {
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = UpdateBundleParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
             name: self.inner.name,
         };
 
@@ -175,3 +193,353 @@ impl Command<UpdateCommand> {
         Ok(())
     }
 }
+
+/// Check that every binary in a bundle has a valid signature from a trusted signing key.
+#[derive(Parser, Debug)]
+pub struct VerifyCommand {
+    /// The PRN of the bundle to verify.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Bundle)
+    )]
+    prn: Prn,
+
+    /// The PRN of a signing key whose signatures are trusted. May be given multiple times. Defaults to the signing keys of the signing key pairs in your Peridio CLI config, or any signing key when there are none.
+    #[arg(
+        long = "trusted-signing-key-prn",
+        value_parser = PRNValueParser::new(PRNType::SigningKey)
+    )]
+    trusted_signing_key_prns: Vec<Prn>,
+}
+
+impl Command<VerifyCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let trusted_signing_key_prns = if self.inner.trusted_signing_key_prns.is_empty() {
+            global_options
+                .signing_key_pairs
+                .iter()
+                .flat_map(|signing_key_pairs| signing_key_pairs.values())
+                .map(|signing_key_pair| signing_key_pair.signing_key_prn.clone())
+                .collect()
+        } else {
+            self.inner
+                .trusted_signing_key_prns
+                .iter()
+                .map(Prn::to_string)
+                .collect()
+        };
+
+        let api = Api::new(ApiOptions {
+            api_key: global_options.api_key.unwrap(),
+            endpoint: global_options.base_url,
+            ca_bundle_path: global_options.ca_path,
+        });
+
+        let mut signing_keys = HashMap::new();
+        let mut checks = Vec::new();
+
+        for binary_prn in bundle_binary_prns(&api, &self.inner.prn.to_string()).await? {
+            let binary = get_binary(&api, &binary_prn).await?;
+
+            let result =
+                trusted_signature(&api, &binary, &trusted_signing_key_prns, &mut signing_keys)
+                    .await?
+                    .map(|signing_key_prn| format!("signed by {signing_key_prn}"));
+
+            checks.push(BinaryCheck::new(binary_prn, &binary, result));
+        }
+
+        print_checks(&checks, "verification")
+    }
+}
+
+/// Sign every binary in a bundle that is not yet signed by the given signing key.
+#[derive(Parser, Debug)]
+pub struct SignCommand {
+    /// The PRN of the bundle to sign.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Bundle)
+    )]
+    prn: Prn,
+
+    /// The name of a signing key pair as defined in your Peridio CLI config.
+    #[arg(
+        long,
+        short = 's',
+        conflicts_with = "signing_key_private",
+        conflicts_with = "signing_key_prn",
+        required_unless_present_any = ["signing_key_private", "signing_key_prn"],
+    )]
+    signing_key_pair: Option<String>,
+
+    /// The path of the file containing the private key to sign the binaries with.
+    #[arg(
+        long,
+        conflicts_with = "signing_key_pair",
+        required_unless_present = "signing_key_pair",
+        requires = "signing_key_prn"
+    )]
+    signing_key_private: Option<String>,
+
+    /// The PRN of the signing key to tell Peridio to verify the signatures with.
+    #[arg(
+        long,
+        conflicts_with = "signing_key_pair",
+        required_unless_present = "signing_key_pair",
+        requires = "signing_key_private",
+        value_parser = PRNValueParser::new(PRNType::SigningKey)
+    )]
+    signing_key_prn: Option<Prn>,
+}
+
+impl Command<SignCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let (signing_key_prn, signing_key_private_path) =
+            if let Some(signing_key_pair) = &self.inner.signing_key_pair {
+                let key_pair = signing_key_pair_from_config(&global_options, signing_key_pair)?;
+                (key_pair.signing_key_prn, key_pair.signing_key_private_path)
+            } else {
+                (
+                    self.inner.signing_key_prn.unwrap().to_string(),
+                    self.inner.signing_key_private.unwrap(),
+                )
+            };
+
+        let api = Api::new(ApiOptions {
+            api_key: global_options.api_key.unwrap(),
+            endpoint: global_options.base_url,
+            ca_bundle_path: global_options.ca_path,
+        });
+
+        let mut signing_keys = HashMap::new();
+        let mut checks = Vec::new();
+
+        for binary_prn in bundle_binary_prns(&api, &self.inner.prn.to_string()).await? {
+            let binary = get_binary(&api, &binary_prn).await?;
+
+            let signed =
+                trusted_signature(&api, &binary, &[signing_key_prn.clone()], &mut signing_keys)
+                    .await?;
+
+            let result = match (signed, binary.get("hash").and_then(Value::as_str)) {
+                (Ok(_), _) => Ok("already signed".to_string()),
+                (Err(_), None) => Err(format!(
+                    "not hashed yet, state is {}",
+                    table::cell(binary.get("state"))
+                )),
+                (Err(_), Some(hash)) => {
+                    let params = CreateBinarySignatureParams {
+                        binary_prn: binary_prn.clone(),
+                        signing_key_prn: signing_key_prn.clone(),
+                        signature: sign_hash(&signing_key_private_path, hash)?,
+                    };
+
+                    api.binary_signatures()
+                        .create(params)
+                        .await
+                        .context(ApiSnafu)?;
+
+                    // a signable binary becomes available for releases once it is marked signed
+                    if binary.get("state").and_then(Value::as_str) == Some("signable") {
+                        let params = UpdateBinaryParams {
+                            prn: binary_prn.clone(),
+                            custom_metadata: None,
+                            description: None,
+                            state: Some(BinaryState::Signed),
+                            hash: None,
+                            size: None,
+                        };
+
+                        api.binaries().update(params).await.context(ApiSnafu)?;
+                    }
+
+                    Ok("signed".to_string())
+                }
+            };
+
+            checks.push(BinaryCheck::new(binary_prn, &binary, result));
+        }
+
+        print_checks(&checks, "signing")
+    }
+}
+
+struct BinaryCheck {
+    binary_prn: String,
+    target: String,
+    result: Result<String, String>,
+}
+
+impl BinaryCheck {
+    fn new(binary_prn: String, binary: &Value, result: Result<String, String>) -> Self {
+        Self {
+            binary_prn,
+            target: table::cell(binary.get("target")),
+            result,
+        }
+    }
+}
+
+// bundles list their binaries either as PRNs or as objects with a `prn`
+async fn bundle_binary_prns(api: &Api, bundle_prn: &str) -> Result<Vec<String>, Error> {
+    let params = GetBundleParams {
+        prn: bundle_prn.to_string(),
+    };
+
+    let bundle = match api.bundles().get(params).await.context(ApiSnafu)? {
+        Some(bundle) => serde_json::to_value(bundle).context(JsonSerializationSnafu)?,
+        None => panic!(),
+    };
+
+    let binaries = bundle
+        .get("bundle")
+        .and_then(|bundle| bundle.get("binaries"))
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(binaries
+        .iter()
+        .filter_map(|binary| match binary {
+            Value::String(prn) => Some(prn.clone()),
+            binary => binary.get("prn").and_then(Value::as_str).map(str::to_owned),
+        })
+        .collect())
+}
+
+async fn get_binary(api: &Api, binary_prn: &str) -> Result<Value, Error> {
+    let params = GetBinaryParams {
+        prn: binary_prn.to_string(),
+    };
+
+    match api.binaries().get(params).await.context(ApiSnafu)? {
+        Some(response) => {
+            let response = serde_json::to_value(response).context(JsonSerializationSnafu)?;
+            Ok(response.get("binary").cloned().unwrap_or(response))
+        }
+        None => panic!(),
+    }
+}
+
+// the first trusted signing key with a valid signature of the binary's hash, or why there is none
+async fn trusted_signature(
+    api: &Api,
+    binary: &Value,
+    trusted_signing_key_prns: &[String],
+    signing_keys: &mut HashMap<String, Option<String>>,
+) -> Result<Result<String, String>, Error> {
+    let Some(hash) = binary.get("hash").and_then(Value::as_str) else {
+        return Ok(Err("not hashed yet".to_string()));
+    };
+
+    let signatures = binary
+        .get("signatures")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    if signatures.is_empty() {
+        return Ok(Err("unsigned".to_string()));
+    }
+
+    let mut reason = "no signature from a trusted signing key".to_string();
+
+    for signature in signatures.iter() {
+        let (Some(signing_key_prn), Some(signature)) = (
+            signature.get("signing_key_prn").and_then(Value::as_str),
+            signature.get("signature").and_then(Value::as_str),
+        ) else {
+            continue;
+        };
+
+        if !trusted_signing_key_prns.is_empty()
+            && !trusted_signing_key_prns
+                .iter()
+                .any(|prn| prn == signing_key_prn)
+        {
+            continue;
+        }
+
+        if !signing_keys.contains_key(signing_key_prn) {
+            let params = GetSigningKeyParams {
+                prn: signing_key_prn.to_string(),
+            };
+
+            let public_key = match api.signing_keys().get(params).await.context(ApiSnafu)? {
+                Some(response) => serde_json::to_value(response)
+                    .context(JsonSerializationSnafu)?
+                    .get("signing_key")
+                    .and_then(|signing_key| signing_key.get("value"))
+                    .and_then(Value::as_str)
+                    .map(str::to_owned),
+                None => None,
+            };
+
+            signing_keys.insert(signing_key_prn.to_string(), public_key);
+        }
+
+        match &signing_keys[signing_key_prn] {
+            Some(public_key) if verify_hash(public_key, hash, signature) => {
+                return Ok(Ok(signing_key_prn.to_string()));
+            }
+            _ => reason = format!("invalid signature from {signing_key_prn}"),
+        }
+    }
+
+    Ok(Err(reason))
+}
+
+fn print_checks(checks: &[BinaryCheck], action: &str) -> Result<(), Error> {
+    let rows: Vec<Value> = checks
+        .iter()
+        .map(|check| {
+            let (result, detail) = match &check.result {
+                Ok(detail) => ("pass", detail),
+                Err(detail) => ("fail", detail),
+            };
+
+            json!({
+                "binary_prn": check.binary_prn,
+                "target": check.target,
+                "result": result,
+                "detail": detail,
+            })
+        })
+        .collect();
+
+    match output::format() {
+        None | Some(OutputFormat::Human) | Some(OutputFormat::Table) => {
+            let columns = [
+                ("Binary", "binary_prn"),
+                ("Target", "target"),
+                ("Result", "result"),
+                ("Detail", "detail"),
+            ]
+            .map(|(header, path)| Column {
+                header: header.to_string(),
+                path: path.to_string(),
+            });
+
+            output::print_rendered(&table::render(&rows.iter().collect::<Vec<_>>(), &columns))?
+        }
+        _ => print_json!(&rows),
+    }
+
+    let failed = checks.iter().filter(|check| check.result.is_err()).count();
+
+    if failed > 0 {
+        // keep the report in --output-file, failing would discard it
+        output::finish()?;
+
+        let mut error = StyledStr::new();
+        error.push_str(Some(Style::Error), "error: ".to_string());
+        error.push_str(
+            None,
+            format!("{failed} of {} binaries failed {action}", checks.len()),
+        );
+        return Err(error.data_err());
+    }
+
+    Ok(())
+}
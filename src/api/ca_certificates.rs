@@ -1,6 +1,9 @@
 use super::Command;
-use crate::utils::{PRNType, PRNValueParser};
-use crate::{print_json, ApiSnafu, Error, GlobalOptions, NonExistingPathSnafu};
+use crate::utils::expiry::warn_if_expiring;
+use crate::utils::{output, PRNType, PRNValueParser, Prn, Style, StyledStr};
+use crate::{
+    print_json, ApiSnafu, Error, GlobalOptions, JsonSerializationSnafu, NonExistingPathSnafu,
+};
 use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
 use peridio_sdk::api::ca_certificates::CaCertificateJitp;
@@ -13,7 +16,10 @@ use peridio_sdk::api::ca_certificates::UpdateCaCertificateParams;
 use peridio_sdk::api::{Api, ApiOptions};
 use snafu::ResultExt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use x509_parser::pem::parse_x509_pem;
 
 #[derive(Parser, Debug)]
 pub enum CaCertificatesCommand {
@@ -73,7 +79,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Cohort)
     )]
-    jitp_cohort_prn: Option<String>,
+    jitp_cohort_prn: Option<Prn>,
 }
 
 impl Command<CreateCommand> {
@@ -82,13 +88,21 @@ impl Command<CreateCommand> {
             fs::read_to_string(&self.inner.certificate_path).context(NonExistingPathSnafu {
                 path: &self.inner.certificate_path,
             })?;
-        let verification_cert = fs::read_to_string(self.inner.verification_certificate_path)
+        let verification_cert = fs::read_to_string(&self.inner.verification_certificate_path)
             .context(NonExistingPathSnafu {
-                path: &self.inner.certificate_path,
+                path: &self.inner.verification_certificate_path,
             })?;
 
-        let cert_base64 = general_purpose::STANDARD.encode(cert);
-        let verification_cert_base64 = general_purpose::STANDARD.encode(verification_cert);
+        let summary = check_certificate(&self.inner.certificate_path, &cert)?;
+        check_certificate(
+            &self.inner.verification_certificate_path,
+            &verification_cert,
+        )?;
+
+        print_summary(&summary);
+
+        let cert_base64 = encode_certificate(&cert);
+        let verification_cert_base64 = encode_certificate(&verification_cert);
 
         let jitp = if let (Some(description), true, Some(product_name)) = (
             self.inner.jitp_description,
@@ -100,7 +114,7 @@ impl Command<CreateCommand> {
                 tags: self.inner.jitp_tags,
                 target: self.inner.jitp_target,
                 product_name,
-                cohort_prn: self.inner.jitp_cohort_prn,
+                cohort_prn: self.inner.jitp_cohort_prn.map(|prn| prn.to_string()),
             })
         } else {
             None
@@ -126,7 +140,7 @@ impl Command<CreateCommand> {
             .await
             .context(ApiSnafu)?
         {
-            Some(ca_certificate) => print_json!(&ca_certificate),
+            Some(ca_certificate) => print_created!(&ca_certificate),
             None => panic!(),
         }
 
@@ -172,6 +186,10 @@ impl Command<DeleteCommand> {
 pub struct GetCommand {
     #[arg(long)]
     ca_certificate_serial: String,
+
+    /// Warn when the certificate expires within this many days.
+    #[arg(long, default_value_t = 30)]
+    expiry_warn_days: u32,
 }
 
 impl Command<GetCommand> {
@@ -188,7 +206,12 @@ impl Command<GetCommand> {
         });
 
         match api.ca_certificates().get(params).await.context(ApiSnafu)? {
-            Some(ca_certificate) => print_json!(&ca_certificate),
+            Some(ca_certificate) => {
+                let value =
+                    serde_json::to_value(&ca_certificate).context(JsonSerializationSnafu)?;
+                warn_if_expiring(&value, self.inner.expiry_warn_days);
+                print_json!(&value)
+            }
             None => panic!(),
         }
 
@@ -255,7 +278,7 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Cohort)
     )]
-    jitp_cohort_prn: Option<String>,
+    jitp_cohort_prn: Option<Prn>,
 }
 
 impl Command<UpdateCommand> {
@@ -273,7 +296,7 @@ impl Command<UpdateCommand> {
                 tags: self.inner.jitp_tags,
                 target: self.inner.jitp_target,
                 product_name,
-                cohort_prn: self.inner.jitp_cohort_prn,
+                cohort_prn: self.inner.jitp_cohort_prn.map(|prn| prn.to_string()),
             }))
         } else {
             //do nothing
@@ -338,3 +361,117 @@ impl Command<CreateVerificationCodeCommand> {
         Ok(())
     }
 }
+
+/// What is shown about a CA certificate before it is registered.
+#[derive(Debug, PartialEq)]
+struct CertificateSummary {
+    subject: String,
+    not_after: OffsetDateTime,
+}
+
+fn parse_certificate(pem: &str) -> Result<CertificateSummary, String> {
+    let (_, pem) = parse_x509_pem(pem.as_bytes()).map_err(|error| error.to_string())?;
+
+    if pem.label != "CERTIFICATE" {
+        return Err(format!("expected a CERTIFICATE, found a {}", pem.label));
+    }
+
+    let certificate = pem.parse_x509().map_err(|error| error.to_string())?;
+
+    Ok(CertificateSummary {
+        subject: certificate.subject().to_string(),
+        not_after: certificate.validity().not_after.to_datetime(),
+    })
+}
+
+// fails with a data error rather than letting the API reject the certificate
+fn check_certificate(path: &Path, pem: &str) -> Result<CertificateSummary, Error> {
+    match parse_certificate(pem) {
+        Ok(summary) => Ok(summary),
+        Err(reason) => {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("Not a PEM encoded X.509 certificate:\r\n")
+                .warning(format!("\t{}: {reason}", path.display()));
+            Err(error.data_err())
+        }
+    }
+}
+
+fn print_summary(summary: &CertificateSummary) {
+    let not_after = summary.not_after.format(&Rfc3339).unwrap();
+
+    if summary.not_after < OffsetDateTime::now_utc() {
+        output::warn(format!("The CA certificate expired at {not_after}"));
+    }
+
+    if output::quiet() {
+        return;
+    }
+
+    let mut message = StyledStr::new();
+    message.plain("Registering the CA certificate:\r\n\tsubject: ");
+    message.push_str(Some(Style::Hint), summary.subject.clone());
+    message.plain("\r\n\texpires: ");
+    message.push_str(Some(Style::Hint), not_after);
+    message.print_err().unwrap();
+}
+
+// the API takes the PEM file as is, base64 encoded
+fn encode_certificate(pem: &str) -> String {
+    general_purpose::STANDARD.encode(pem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{date_time_ymd, CertificateParams, DnType, KeyPair};
+
+    fn certificate_pem() -> String {
+        let key_pair = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "Example CA");
+        params.not_after = date_time_ymd(2040, 1, 2);
+
+        params.self_signed(&key_pair).unwrap().pem()
+    }
+
+    #[test]
+    fn subject_and_expiry_are_parsed() {
+        let summary = parse_certificate(&certificate_pem()).unwrap();
+
+        assert_eq!(summary.subject, "CN=Example CA");
+        assert_eq!(
+            summary.not_after.format(&Rfc3339).unwrap(),
+            "2040-01-02T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn invalid_pem_is_rejected() {
+        assert!(parse_certificate("not a certificate").is_err());
+
+        let key_pem = KeyPair::generate().unwrap().serialize_pem();
+        assert_eq!(
+            parse_certificate(&key_pem).unwrap_err(),
+            "expected a CERTIFICATE, found a PRIVATE KEY"
+        );
+
+        let corrupt = "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+        assert!(parse_certificate(corrupt).is_err());
+    }
+
+    #[test]
+    fn certificate_is_sent_as_base64_pem() {
+        let pem = certificate_pem();
+        let encoded = encode_certificate(&pem);
+
+        assert_eq!(
+            general_purpose::STANDARD.decode(encoded).unwrap(),
+            pem.as_bytes()
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use directories::ProjectDirs;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, ETAG};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::utils::temp_file::TempFile;
+use crate::GlobalOptions;
+
+/// Successful GET responses kept on disk for `--cache`, one file per API key and URL.
+///
+/// The cache only ever saves requests: an entry that can't be read or written is treated as
+/// missing, so a broken cache directory never fails a command.
+pub struct Cache {
+    directory: PathBuf,
+    ttl: Duration,
+    refresh: bool,
+}
+
+/// A cached response body and the `ETag` it was served with.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Entry {
+    // milliseconds since the Unix epoch
+    stored_at: u128,
+    pub etag: Option<String>,
+    pub body: String,
+}
+
+impl Entry {
+    /// The headers a command reads off a response, as far as the entry kept them.
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        if let Some(etag) = self.etag.as_deref().and_then(|etag| etag.parse().ok()) {
+            headers.insert(ETAG, etag);
+        }
+
+        headers
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Lookup {
+    /// Younger than `--cache-ttl`, served without a request.
+    Fresh(Entry),
+    /// Older than `--cache-ttl`, revalidated with its `ETag` if it has one.
+    Stale(Entry),
+    Missing,
+}
+
+impl Cache {
+    /// The cache `--cache` asks for, `None` without it or when there is no cache directory.
+    pub fn new(global_options: &GlobalOptions) -> Option<Self> {
+        if !global_options.cache {
+            return None;
+        }
+
+        let directory = global_options.cache_dir.clone().or_else(|| {
+            ProjectDirs::from("", "", "peridio").map(|dirs| dirs.cache_dir().join("responses"))
+        })?;
+
+        Some(Self {
+            directory,
+            ttl: global_options.cache_ttl,
+            refresh: global_options.no_cache,
+        })
+    }
+
+    pub fn lookup(&self, api_key: &str, url: &str) -> Lookup {
+        if self.refresh {
+            return Lookup::Missing;
+        }
+
+        let entry = fs::read(self.path(api_key, url))
+            .ok()
+            .and_then(|entry| serde_json::from_slice::<Entry>(&entry).ok());
+
+        match entry {
+            Some(entry) if now().saturating_sub(entry.stored_at) < self.ttl.as_millis() => {
+                Lookup::Fresh(entry)
+            }
+            Some(entry) => Lookup::Stale(entry),
+            None => Lookup::Missing,
+        }
+    }
+
+    /// Keep `body` for `url`, unless it is not text.
+    pub fn store(&self, api_key: &str, url: &str, etag: Option<String>, body: &[u8]) {
+        let Ok(body) = String::from_utf8(body.to_vec()) else {
+            return;
+        };
+
+        let entry = Entry {
+            stored_at: now(),
+            etag,
+            body,
+        };
+
+        // only the current user may read the responses, and a reader never sees half an entry
+        let stored = create_directory(&self.directory).and_then(|()| {
+            let mut file = TempFile::new(&self.path(api_key, url))?;
+            file.file()
+                .write_all(&serde_json::to_vec(&entry).unwrap_or_default())?;
+            file.persist().map(drop)
+        });
+
+        if let Err(error) = stored {
+            tracing::debug!("unable to cache {url}: {error}");
+        }
+    }
+
+    /// Forget the response kept for `url`, once a request may have changed what it returns.
+    pub fn invalidate(&self, api_key: &str, url: &str) {
+        match fs::remove_file(self.path(api_key, url)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                tracing::debug!("unable to drop the cached {url}: {error}");
+            }
+            _ => {}
+        }
+    }
+
+    // the API key is part of the name, so one key is never served what another could read
+    fn path(&self, api_key: &str, url: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(api_key);
+        hasher.update([0]);
+        hasher.update(url);
+
+        self.directory.join(format!("{:x}.json", hasher.finalize()))
+    }
+}
+
+fn create_directory(directory: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(directory)
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://api.peridio.com/orgs/acme";
+
+    fn cache(directory: &tempfile::TempDir, ttl: Duration) -> Cache {
+        Cache {
+            directory: directory.path().to_path_buf(),
+            ttl,
+            refresh: false,
+        }
+    }
+
+    #[test]
+    fn entries_are_fresh_until_the_ttl() {
+        let directory = tempfile::tempdir().unwrap();
+
+        cache(&directory, Duration::from_secs(60)).store(
+            "key",
+            URL,
+            Some("\"v1\"".to_string()),
+            b"{}",
+        );
+
+        let Lookup::Fresh(entry) = cache(&directory, Duration::from_secs(60)).lookup("key", URL)
+        else {
+            panic!("the entry is not fresh");
+        };
+        assert_eq!(entry.body, "{}");
+        assert_eq!(entry.headers()[ETAG], "\"v1\"");
+
+        assert!(matches!(
+            cache(&directory, Duration::ZERO).lookup("key", URL),
+            Lookup::Stale(_)
+        ));
+    }
+
+    #[test]
+    fn entries_are_kept_per_api_key_and_url() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = cache(&directory, Duration::from_secs(60));
+
+        cache.store("key", URL, None, b"{}");
+
+        assert_eq!(cache.lookup("other key", URL), Lookup::Missing);
+        assert_eq!(
+            cache.lookup("key", &format!("{URL}?page=2")),
+            Lookup::Missing
+        );
+    }
+
+    #[test]
+    fn invalidated_entries_are_missing() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = cache(&directory, Duration::from_secs(60));
+
+        cache.store("key", URL, None, b"{}");
+        cache.invalidate("key", URL);
+
+        assert_eq!(cache.lookup("key", URL), Lookup::Missing);
+
+        // nothing to forget
+        cache.invalidate("key", URL);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn entries_are_readable_by_the_user_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let cache = cache(&directory, Duration::from_secs(60));
+
+        cache.store("key", URL, None, b"{}");
+
+        let mode = fs::metadata(cache.path("key", URL))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn refresh_skips_the_entries() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut cache = cache(&directory, Duration::from_secs(60));
+
+        cache.store("key", URL, None, b"{}");
+        cache.refresh = true;
+
+        assert_eq!(cache.lookup("key", URL), Lookup::Missing);
+    }
+}
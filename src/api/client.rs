@@ -0,0 +1,717 @@
+use std::fs;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH};
+use reqwest::{Certificate, Method, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use snafu::ResultExt;
+use uuid::Uuid;
+
+use super::cache::{Cache, Lookup};
+use super::error::{retry_after, ApiError};
+use crate::utils::{logging, rate_limit, trace, Style, StyledStr};
+use crate::{Error, FileSnafu, GlobalOptions, HttpSnafu, RequestSnafu};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.peridio.com";
+
+const HEALTH_PATH: &str = "/health";
+
+// how many times an update is re-applied after someone else changed the resource
+const CONFLICT_RETRIES: usize = 3;
+
+// the headers the API and the load balancers in front of it send a request's ID in
+const REQUEST_ID_HEADERS: [&str; 2] = ["x-request-id", "x-amzn-requestid"];
+
+// the preflight only has to tell whether the backend answers at all
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A thin HTTP client for endpoints the SDK does not cover yet.
+///
+/// It authenticates and resolves the base URL and CA bundle the same way the SDK does, so
+/// commands built on it behave like the rest of the CLI.
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    idempotency_key: Option<String>,
+    retry: RetryPolicy,
+    cache: Option<Cache>,
+    relayed: bool,
+    timeout: Option<Duration>,
+}
+
+/// The body of a response, read already when it is JSON or an error, and otherwise left on the
+/// connection so large content can be passed on as it arrives.
+pub enum ResponseBody {
+    Complete(Vec<u8>),
+    Streaming(Response),
+}
+
+impl ResponseBody {
+    /// Read what is left of the body.
+    pub async fn read(self) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Complete(body) => Ok(body),
+            Self::Streaming(response) => Ok(response.bytes().await.map_err(network)?.to_vec()),
+        }
+    }
+}
+
+/// How transient failures of requests that only read are retried, see `--max-retries`.
+#[derive(Clone, Copy, Debug)]
+struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl RetryPolicy {
+    // the delay before retrying after `attempt` failed attempts, `None` when giving up. Without a
+    // status the request failed on the network.
+    fn delay(
+        &self,
+        attempt: u32,
+        status: Option<StatusCode>,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if attempt > self.max_retries {
+            return None;
+        }
+
+        if status.is_some_and(|status| {
+            status != StatusCode::TOO_MANY_REQUESTS && !status.is_server_error()
+        }) {
+            return None;
+        }
+
+        if let Some(retry_after) = retry_after {
+            return Some(retry_after);
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+
+        // up to half of the backoff again, so clients that failed together don't retry together
+        let (random, _) = Uuid::new_v4().as_u64_pair();
+        let jitter = backoff.mul_f64(random as f64 / u64::MAX as f64 / 2.0);
+
+        Some(backoff + jitter)
+    }
+}
+
+impl Client {
+    pub fn new(global_options: &GlobalOptions) -> Result<Self, Error> {
+        let mut builder = reqwest::ClientBuilder::new().use_rustls_tls();
+
+        // behind the relay, which times out, rate limits and logs every attempt it sends on
+        let relayed = global_options.api_base_url.is_some();
+
+        if let Some(timeout) = global_options.timeout.filter(|_| !relayed) {
+            builder = builder.timeout(timeout);
+        }
+
+        if let Some(connect_timeout) = global_options.connect_timeout.filter(|_| !relayed) {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+
+        if let Some(ca_path) = &global_options.ca_path {
+            let pem = fs::read(ca_path).context(FileSnafu)?;
+            let certificate = Certificate::from_pem(&pem).context(HttpSnafu)?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        let base_url = global_options
+            .base_url
+            .clone()
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+
+        Ok(Self {
+            http: builder.build().context(HttpSnafu)?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: global_options.api_key.clone().unwrap_or_default(),
+            idempotency_key: global_options.idempotency_key.clone(),
+            retry: RetryPolicy {
+                max_retries: global_options.max_retries,
+                base_delay: Duration::from_millis(global_options.retry_base_delay),
+            },
+            cache: Cache::new(global_options),
+            relayed,
+            timeout: global_options.timeout,
+        })
+    }
+
+    /// Check that the backend is reachable and healthy, see [`preflight`].
+    pub async fn health(&self) -> Result<(), String> {
+        let response = self
+            .request(Method::GET, HEALTH_PATH)
+            .timeout(HEALTH_TIMEOUT)
+            .send()
+            .await
+            .map_err(|error| format!("backend unreachable at {}: {error}", self.base_url))?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "backend unhealthy at {}: {}",
+                self.base_url,
+                response.status()
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub async fn get(&self, path: &str) -> Result<Value, Error> {
+        self.send(self.request(Method::GET, path), true).await
+    }
+
+    pub async fn get_with_query(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<Value, Error> {
+        self.send(self.request(Method::GET, path).query(query), true)
+            .await
+    }
+
+    /// Request `path` and return the successful response without reading its body, so large
+    /// content can be streamed with `Response::chunk`.
+    pub async fn get_stream(&self, path: &str) -> Result<Response, Error> {
+        let response = self.execute(self.request(Method::GET, path), true).await?;
+
+        successful(response).await
+    }
+
+    /// Request a presigned `url` outside the API, such as where a binary's content is stored,
+    /// and return the successful response without reading its body. The URL carries its own
+    /// authorization, so the API key is not sent along.
+    pub async fn get_signed(&self, url: &str) -> Result<Response, Error> {
+        let mut request = self.http.get(url);
+
+        // the relay times out requests to the API only
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+
+        let response = self.execute(request, true).await?;
+
+        successful(response).await
+    }
+
+    /// Send a request with the `headers` of its sender and return the response whatever its
+    /// status, see `relay`. Headers this client sets itself, such as the authorization, are not
+    /// taken from `headers`.
+    pub async fn forward(
+        &self,
+        method: Method,
+        path_and_query: &str,
+        headers: &HeaderMap,
+        body: Vec<u8>,
+    ) -> Result<(StatusCode, HeaderMap, ResponseBody), Error> {
+        let idempotent = matches!(method, Method::GET | Method::HEAD | Method::OPTIONS);
+        let mut request = self
+            .request(method, path_and_query)
+            .body(body)
+            .build()
+            .context(HttpSnafu)?;
+
+        for (name, value) in headers {
+            if !request.headers().contains_key(name) {
+                request.headers_mut().insert(name, value.clone());
+            }
+        }
+
+        self.fetch(
+            RequestBuilder::from_parts(self.http.clone(), request),
+            idempotent,
+        )
+        .await
+    }
+
+    pub async fn post(&self, path: &str, body: &Value) -> Result<Value, Error> {
+        self.send(self.request(Method::POST, path).json(body), false)
+            .await
+    }
+
+    pub async fn patch(&self, path: &str, body: &Value) -> Result<Value, Error> {
+        self.send(self.request(Method::PATCH, path).json(body), false)
+            .await
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<Value, Error> {
+        self.send(self.request(Method::DELETE, path), false).await
+    }
+
+    /// Update the resource at `path` without overwriting concurrent changes.
+    ///
+    /// The resource is fetched and `changes` builds the patch body from its current state, which
+    /// is sent with `If-Match` set to the fetched version. When someone else changed the resource
+    /// in the meantime the server answers 412, and the update is re-applied to a fresh copy a
+    /// bounded number of times, or fails right away unless `retry_conflict` is set. Returning
+    /// `None` from `changes` skips the update.
+    ///
+    /// Only updates that derive their changes from the current state, such as merged tags, need
+    /// this. Updates through the SDK send the given fields as they are, without reading first, so
+    /// there is no version to guard them with.
+    pub async fn update<F>(
+        &self,
+        path: &str,
+        retry_conflict: bool,
+        mut changes: F,
+    ) -> Result<Option<Value>, Error>
+    where
+        F: FnMut(&Value) -> Option<Value>,
+    {
+        let mut conflicts = 0;
+
+        loop {
+            // a cached copy could carry an outdated version, failing every attempt with a 412
+            let request = self
+                .request(Method::GET, path)
+                .header(CACHE_CONTROL, "no-cache");
+            let (headers, current) = self.send_raw(request, true).await?;
+
+            let Some(body) = changes(&current) else {
+                return Ok(None);
+            };
+
+            let mut request = self.request(Method::PATCH, path).json(&body);
+
+            if let Some(version) = version(&headers, &current) {
+                request = request.header(IF_MATCH, version);
+            }
+
+            match self.send(request, false).await {
+                Err(Error::Request {
+                    source: ApiError::Rejected { status, .. },
+                    ..
+                }) if status == StatusCode::PRECONDITION_FAILED => {
+                    conflicts += 1;
+
+                    if !retry_conflict || conflicts > CONFLICT_RETRIES {
+                        return Err(Error::UpdateConflict {
+                            path: path.to_string(),
+                        });
+                    }
+                }
+                result => return result.map(Some),
+            }
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut request = self
+            .http
+            .request(method.clone(), format!("{}{path}", self.base_url))
+            .bearer_auth(&self.api_key);
+
+        if let Some(traceparent) = trace::traceparent() {
+            request = request.header("traceparent", traceparent);
+        }
+
+        if let Some(idempotency_key) = &self.idempotency_key {
+            if method == Method::POST {
+                request = request.header("idempotency-key", idempotency_key);
+            }
+        }
+
+        request
+    }
+
+    async fn send(&self, request: RequestBuilder, idempotent: bool) -> Result<Value, Error> {
+        self.send_raw(request, idempotent)
+            .await
+            .map(|(_, body)| body)
+    }
+
+    // send the request and parse the JSON body of a successful response
+    async fn send_raw(
+        &self,
+        request: RequestBuilder,
+        idempotent: bool,
+    ) -> Result<(HeaderMap, Value), Error> {
+        let (status, headers, body) = self.fetch(request, idempotent).await?;
+        let body = body.read().await?;
+        let body = String::from_utf8_lossy(&body);
+
+        if !status.is_success() {
+            return Err(ApiError::from_response(status, &headers, &body)).context(RequestSnafu {
+                request_id: request_id(&headers),
+            });
+        }
+
+        if body.is_empty() {
+            return Ok((headers, Value::Null));
+        }
+
+        let body = serde_json::from_str(&body).context(crate::JsonSerializationSnafu)?;
+
+        Ok((headers, body))
+    }
+
+    // send the request, serving GET requests from `--cache` when it has a fresh response, and
+    // revalidating a stale one with its ETag, unless they ask for `Cache-Control: no-cache`.
+    // Other requests drop what is kept for their URL. JSON and errors are read whole, other
+    // content is left to stream.
+    async fn fetch(
+        &self,
+        request: RequestBuilder,
+        idempotent: bool,
+    ) -> Result<(StatusCode, HeaderMap, ResponseBody), Error> {
+        let mut request = request.build().context(HttpSnafu)?;
+        let url = request.url().to_string();
+        let reads = matches!(*request.method(), Method::GET | Method::HEAD);
+
+        if let Some(cache) = self.cache.as_ref().filter(|_| !reads) {
+            cache.invalidate(&self.api_key, &url);
+        }
+
+        let cache = self
+            .cache
+            .as_ref()
+            .filter(|_| request.method() == Method::GET);
+        let refresh = request
+            .headers()
+            .get(CACHE_CONTROL)
+            .is_some_and(|value| value == "no-cache");
+
+        let stale = match cache
+            .filter(|_| !refresh)
+            .map(|cache| cache.lookup(&self.api_key, &url))
+        {
+            Some(Lookup::Fresh(entry)) => {
+                tracing::info!("GET {url} cached");
+
+                return Ok((
+                    StatusCode::OK,
+                    entry.headers(),
+                    ResponseBody::Complete(entry.body.into_bytes()),
+                ));
+            }
+            Some(Lookup::Stale(entry)) => Some(entry),
+            _ => None,
+        };
+
+        if let Some(etag) = stale
+            .as_ref()
+            .and_then(|entry| entry.etag.as_deref()?.parse().ok())
+        {
+            request.headers_mut().insert(IF_NONE_MATCH, etag);
+        }
+
+        let response = self
+            .execute(
+                RequestBuilder::from_parts(self.http.clone(), request),
+                idempotent,
+            )
+            .await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+
+        if status.is_success() && !is_json(&headers) {
+            return Ok((status, headers, ResponseBody::Streaming(response)));
+        }
+
+        let body = response.bytes().await.map_err(network)?.to_vec();
+
+        tracing::debug!("response body: {}", logging::body(&body));
+
+        let Some(cache) = cache else {
+            return Ok((status, headers, ResponseBody::Complete(body)));
+        };
+
+        match stale {
+            Some(entry) if status == StatusCode::NOT_MODIFIED => {
+                cache.store(
+                    &self.api_key,
+                    &url,
+                    entry.etag.clone(),
+                    entry.body.as_bytes(),
+                );
+
+                Ok((
+                    StatusCode::OK,
+                    entry.headers(),
+                    ResponseBody::Complete(entry.body.into_bytes()),
+                ))
+            }
+            _ => {
+                if status.is_success() {
+                    let etag = headers
+                        .get(ETAG)
+                        .and_then(|etag| etag.to_str().ok())
+                        .map(str::to_owned);
+                    cache.store(&self.api_key, &url, etag, &body);
+                }
+
+                Ok((status, headers, ResponseBody::Complete(body)))
+            }
+        }
+    }
+
+    // send the request, retrying transient failures of `idempotent` requests
+    async fn execute(&self, request: RequestBuilder, idempotent: bool) -> Result<Response, Error> {
+        let mut request = request.build().context(HttpSnafu)?;
+        let mut attempt = 0;
+
+        // the relay logs and retries requests to the API, not those to presigned URLs
+        let relayed = self.relayed && request.url().as_str().starts_with(&self.base_url);
+
+        // the server recognizes a create it already handled by its idempotency key
+        let idempotent =
+            idempotent || (request.method() == Method::POST && self.idempotency_key.is_some());
+
+        loop {
+            let retry = request.try_clone().filter(|_| idempotent);
+            let method = request.method().clone();
+            let url = request.url().clone();
+
+            if !relayed {
+                rate_limit::acquire().await;
+
+                tracing::info!("{method} {url}");
+                tracing::debug!("request headers:\n{}", logging::headers(request.headers()));
+                if let Some(body) = request.body().and_then(reqwest::Body::as_bytes) {
+                    tracing::debug!("request body: {}", logging::body(body));
+                }
+            }
+
+            let response = self.http.execute(request).await;
+            attempt += 1;
+
+            let delay = match &response {
+                // the relay logged and retried the request already
+                _ if relayed => None,
+                Ok(response) => {
+                    tracing::info!("{method} {url} {}", response.status());
+
+                    if let Some(request_id) = request_id(response.headers()) {
+                        tracing::debug!("request id: {request_id}");
+                    }
+
+                    tracing::debug!(
+                        "response headers:\n{}",
+                        logging::headers(response.headers())
+                    );
+
+                    if response.status().is_success() {
+                        None
+                    } else {
+                        self.retry.delay(
+                            attempt,
+                            Some(response.status()),
+                            retry_after(response.headers()),
+                        )
+                    }
+                }
+                Err(error) => {
+                    tracing::info!("{method} {url} failed: {error}");
+                    self.retry.delay(attempt, None, None)
+                }
+            };
+
+            match (retry, delay) {
+                (Some(retry), Some(delay)) => {
+                    tokio::time::sleep(delay).await;
+                    request = retry;
+                }
+                _ => return response.map_err(network),
+            }
+        }
+    }
+}
+
+// the response when it succeeded, otherwise the error its body describes
+async fn successful(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let headers = response.headers().clone();
+    let body = response.text().await.map_err(network)?;
+
+    tracing::debug!("response body: {}", logging::body(body.as_bytes()));
+
+    Err(ApiError::from_response(status, &headers, &body)).context(RequestSnafu {
+        request_id: request_id(&headers),
+    })
+}
+
+/// A request that got no response, which the server has no request ID for.
+pub fn network(source: reqwest::Error) -> Error {
+    Error::Request {
+        source: ApiError::Network(source),
+        request_id: None,
+    }
+}
+
+/// The ID the server logged the request under, to quote in support tickets.
+pub fn request_id(headers: &HeaderMap) -> Option<String> {
+    REQUEST_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(str::to_owned)
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.contains("json"))
+}
+
+// the ETag header, or the resource's version field for endpoints that don't send one
+fn version(headers: &HeaderMap, resource: &Value) -> Option<String> {
+    if let Some(etag) = headers.get(ETAG).and_then(|etag| etag.to_str().ok()) {
+        return Some(etag.to_string());
+    }
+
+    let resource = resource.get("data").unwrap_or(resource);
+
+    match resource.get("version") {
+        Some(Value::String(version)) => Some(format!("\"{version}\"")),
+        Some(Value::Number(version)) => Some(format!("\"{version}\"")),
+        _ => None,
+    }
+}
+
+/// Fail fast with a clear message when the backend is down, instead of deep inside the first
+/// request of a long operation.
+pub async fn preflight(global_options: &GlobalOptions) -> Result<(), Error> {
+    if let Err(message) = Client::new(global_options)?.health().await {
+        let mut error = StyledStr::new();
+        error.push_str(Some(Style::Error), "error: ".to_string());
+        error.push_str(None, "Preflight check failed:\r\n".to_string());
+        error.push_str(Some(Style::Warning), format!("\t{message}"));
+        return Err(error.temp_fail_err());
+    }
+
+    Ok(())
+}
+
+/// The path of the organization named `name`.
+///
+/// The SDK's organization endpoints are nested under `/orgs/{name}`, so the organization itself
+/// is read and updated there too. Only names are passed, the API documents no path taking the
+/// organization's PRN.
+pub fn organization_path(name: &str) -> String {
+    format!("/orgs/{name}")
+}
+
+/// The PRN of the organization commands run in, `--organization-prn` or looked up by name.
+pub async fn organization_prn(global_options: &GlobalOptions) -> Result<String, Error> {
+    if let Some(prn) = &global_options.organization_prn {
+        return Ok(prn.to_string());
+    }
+
+    let name = global_options.organization_name.clone().unwrap();
+    let organization = Client::new(global_options)?
+        .get(&organization_path(&name))
+        .await?;
+
+    match organization_field(&organization, "prn") {
+        Some(prn) => Ok(prn.to_string()),
+        None => {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("The organization has no PRN:\r\n")
+                .warning(format!("\t{name}"));
+            Err(error.data_err())
+        }
+    }
+}
+
+fn organization_field<'a>(organization: &'a Value, field: &str) -> Option<&'a str> {
+    organization
+        .get("organization")
+        .or_else(|| organization.get("data"))
+        .unwrap_or(organization)
+        .get(field)?
+        .as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::RETRY_AFTER;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 3,
+        base_delay: Duration::from_millis(100),
+    };
+
+    #[test]
+    fn transient_failures_are_retried_max_retries_times() {
+        for status in [
+            Some(StatusCode::TOO_MANY_REQUESTS),
+            Some(StatusCode::SERVICE_UNAVAILABLE),
+            None,
+        ] {
+            let retries = (1..)
+                .take_while(|attempt| POLICY.delay(*attempt, status, None).is_some())
+                .count();
+
+            assert_eq!(retries, 3, "{status:?}");
+        }
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        assert_eq!(POLICY.delay(1, Some(StatusCode::BAD_REQUEST), None), None);
+        assert_eq!(POLICY.delay(1, Some(StatusCode::NOT_FOUND), None), None);
+    }
+
+    #[test]
+    fn backoff_grows_exponentially() {
+        let first = POLICY.delay(1, None, None).unwrap();
+        let third = POLICY.delay(3, None, None).unwrap();
+
+        assert!(first >= Duration::from_millis(100) && first <= Duration::from_millis(150));
+        assert!(third >= Duration::from_millis(400) && third <= Duration::from_millis(600));
+    }
+
+    #[test]
+    fn retry_after_is_honored() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+
+        assert_eq!(
+            POLICY.delay(
+                1,
+                Some(StatusCode::TOO_MANY_REQUESTS),
+                retry_after(&headers)
+            ),
+            Some(Duration::from_secs(7))
+        );
+    }
+
+    #[test]
+    fn organization_name_is_read_from_the_response() {
+        let expected = Some("example");
+
+        assert_eq!(
+            organization_field(
+                &serde_json::json!({ "organization": { "name": "example" } }),
+                "name"
+            ),
+            expected
+        );
+        assert_eq!(
+            organization_field(
+                &serde_json::json!({ "data": { "name": "example" } }),
+                "name"
+            ),
+            expected
+        );
+        assert_eq!(
+            organization_field(&serde_json::json!({ "name": "example" }), "name"),
+            expected
+        );
+        assert_eq!(
+            organization_field(&serde_json::json!({ "organization": {} }), "name"),
+            None
+        );
+    }
+}
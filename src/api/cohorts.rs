@@ -1,8 +1,15 @@
+use std::collections::HashMap;
+
 use super::Command;
-use crate::api::list::ListArgs;
+use crate::api::list::{collect_pages, ListArgs};
+use crate::api::resolve::prn_or_name;
+use crate::print_created;
 use crate::print_json;
+use crate::utils::output::{self, OutputFormat};
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
+use crate::utils::{Style, StyledStr};
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
@@ -12,6 +19,7 @@ use peridio_sdk::api::cohorts::{
 };
 use peridio_sdk::api::Api;
 use peridio_sdk::api::ApiOptions;
+use serde_json::Value;
 use snafu::ResultExt;
 
 #[derive(Parser, Debug)]
@@ -20,6 +28,7 @@ pub enum CohortsCommand {
     List(Command<ListCommand>),
     Get(Command<GetCommand>),
     Update(Command<UpdateCommand>),
+    Tree(Command<TreeCommand>),
 }
 
 impl CohortsCommand {
@@ -29,6 +38,7 @@ impl CohortsCommand {
             Self::List(cmd) => cmd.run(global_options).await,
             Self::Get(cmd) => cmd.run(global_options).await,
             Self::Update(cmd) => cmd.run(global_options).await,
+            Self::Tree(cmd) => cmd.run(global_options).await,
         }
     }
 }
@@ -49,14 +59,14 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Organization)
     )]
-    organization_prn: String,
+    organization_prn: Prn,
 
     /// The PRN of the product you wish to create the resource within.
     #[arg(
         long,
         value_parser = PRNValueParser::new(PRNType::Product)
     )]
-    product_prn: String,
+    product_prn: Prn,
 }
 
 impl Command<CreateCommand> {
@@ -64,8 +74,8 @@ impl Command<CreateCommand> {
         let params = CreateCohortParams {
             description: self.inner.description,
             name: self.inner.name,
-            organization_prn: self.inner.organization_prn,
-            product_prn: self.inner.product_prn,
+            organization_prn: self.inner.organization_prn.to_string(),
+            product_prn: self.inner.product_prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -75,7 +85,7 @@ impl Command<CreateCommand> {
         });
 
         match api.cohorts().create(params).await.context(ApiSnafu)? {
-            Some(cohort) => print_json!(&cohort),
+            Some(cohort) => print_created!(&cohort),
             None => panic!(),
         }
 
@@ -91,25 +101,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListCohortsParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
+        let api = &api;
+        let list_args = &self.inner.list_args;
 
-        match api.cohorts().list(params).await.context(ApiSnafu)? {
-            Some(cohort) => print_json!(&cohort),
-            None => panic!(),
-        }
+        list_args
+            .print(|page| {
+                let params = ListCohortsParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
 
-        Ok(())
+                async move { api.cohorts().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -118,16 +129,27 @@ pub struct GetCommand {
     /// The PRN of the resource to get.
     #[arg(
         long,
-        value_parser = PRNValueParser::new(PRNType::Cohort)
+        value_parser = PRNValueParser::new(PRNType::Cohort),
+        required_unless_present = "cohort_name"
     )]
-    prn: String,
+    prn: Option<Prn>,
+
+    /// The name of the cohort to get, looked up among the organization's cohorts.
+    #[arg(long, conflicts_with = "prn")]
+    cohort_name: Option<String>,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = GetCohortParams {
-            prn: self.inner.prn,
-        };
+        let prn = prn_or_name(
+            &global_options,
+            PRNType::Cohort,
+            self.inner.prn,
+            self.inner.cohort_name,
+        )
+        .await?;
+
+        let params = GetCohortParams { prn };
 
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
@@ -149,9 +171,14 @@ pub struct UpdateCommand {
     /// The PRN of the resource to update.
     #[arg(
         long,
-        value_parser = PRNValueParser::new(PRNType::Cohort)
+        value_parser = PRNValueParser::new(PRNType::Cohort),
+        required_unless_present = "cohort_name"
     )]
-    prn: String,
+    prn: Option<Prn>,
+
+    /// The name (currently) of the cohort to update, looked up among the organization's cohorts.
+    #[arg(long, conflicts_with = "prn")]
+    cohort_name: Option<String>,
 
     /// An arbitrary string attached to the resource. Often useful for displaying to users.
     #[arg(long)]
@@ -164,8 +191,16 @@ pub struct UpdateCommand {
 
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let prn = prn_or_name(
+            &global_options,
+            PRNType::Cohort,
+            self.inner.prn,
+            self.inner.cohort_name,
+        )
+        .await?;
+
         let params = UpdateCohortParams {
-            prn: self.inner.prn,
+            prn,
             description: self.inner.description,
             name: self.inner.name,
         };
@@ -184,3 +219,254 @@ impl Command<UpdateCommand> {
         Ok(())
     }
 }
+
+/// Show cohorts nested below their parent cohort.
+///
+/// Every page of cohorts matching --search is fetched. Cohorts whose parent is not among them
+/// are shown at the top level.
+#[derive(Parser, Debug)]
+pub struct TreeCommand {
+    /// A search query per the Peridio API's search query language. It is recommended to quote the value of this option.
+    #[arg(long)]
+    search: String,
+}
+
+impl Command<TreeCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let api = Api::new(ApiOptions {
+            api_key: global_options.api_key.unwrap(),
+            endpoint: global_options.base_url,
+            ca_bundle_path: global_options.ca_path,
+        });
+        let api = &api;
+        let search = &self.inner.search;
+
+        let cohorts = collect_pages(None, None, |page| {
+            let params = ListCohortsParams {
+                limit: None,
+                order: None,
+                search: search.clone(),
+                page,
+            };
+
+            async move { api.cohorts().list(params).await.context(ApiSnafu) }
+        })
+        .await?;
+
+        let (tree, cycles) = nest(&cohorts);
+
+        for prn in cycles {
+            output::warn(format!(
+                "Cohort {prn} is part of a parent cycle, it is shown at the top level"
+            ));
+        }
+
+        match output::format() {
+            None | Some(OutputFormat::Human) => {
+                render(&tree).print_out().unwrap();
+            }
+            _ => print_json!(&tree),
+        }
+
+        Ok(())
+    }
+}
+
+// the cohort's parent, the cohort is a root without one
+fn parent_prn(cohort: &Value) -> Option<&str> {
+    cohort.get("parent_cohort_prn")?.as_str()
+}
+
+/// Nest `cohorts` below their parents, each with a `children` array.
+///
+/// Also returns the PRNs of cohorts that had to be shown at the top level to break a cycle of
+/// parents, which no root leads to.
+fn nest(cohorts: &[Value]) -> (Vec<Value>, Vec<String>) {
+    let prns: Vec<Option<&str>> = cohorts
+        .iter()
+        .map(|cohort| cohort.get("prn").and_then(Value::as_str))
+        .collect();
+
+    let mut children: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+
+    for (index, cohort) in cohorts.iter().enumerate() {
+        match parent_prn(cohort) {
+            Some(parent) if prns.contains(&Some(parent)) => {
+                children.entry(parent).or_default().push(index)
+            }
+            _ => roots.push(index),
+        }
+    }
+
+    let mut visited = vec![false; cohorts.len()];
+    let mut tree: Vec<Value> = roots
+        .into_iter()
+        .map(|index| subtree(index, cohorts, &prns, &children, &mut visited))
+        .collect();
+
+    let mut cycles = Vec::new();
+
+    for index in 0..cohorts.len() {
+        if !visited[index] {
+            cycles.push(prns[index].unwrap_or_default().to_string());
+            tree.push(subtree(index, cohorts, &prns, &children, &mut visited));
+        }
+    }
+
+    (tree, cycles)
+}
+
+fn subtree(
+    index: usize,
+    cohorts: &[Value],
+    prns: &[Option<&str>],
+    children: &HashMap<&str, Vec<usize>>,
+    visited: &mut [bool],
+) -> Value {
+    visited[index] = true;
+
+    let nested: Vec<Value> = prns[index]
+        .and_then(|prn| children.get(prn))
+        .into_iter()
+        .flatten()
+        .filter_map(|&child| {
+            // a cycle leads back to a cohort that is already shown
+            (!visited[child]).then(|| subtree(child, cohorts, prns, children, visited))
+        })
+        .collect();
+
+    let mut cohort = cohorts[index].clone();
+    if let Value::Object(object) = &mut cohort {
+        object.insert("children".to_string(), Value::Array(nested));
+    }
+
+    cohort
+}
+
+// one line per cohort, its name indented by its depth and its PRN
+fn lines(tree: &[Value], depth: usize) -> Vec<(String, Option<&str>)> {
+    let mut result = Vec::new();
+
+    for cohort in tree {
+        let name = cohort.get("name").and_then(Value::as_str).unwrap_or("");
+        let prn = cohort.get("prn").and_then(Value::as_str);
+        result.push((format!("{}{name}", "  ".repeat(depth)), prn));
+
+        if let Some(Value::Array(children)) = cohort.get("children") {
+            result.extend(lines(children, depth + 1));
+        }
+    }
+
+    result
+}
+
+fn render(tree: &[Value]) -> StyledStr {
+    let mut rendered = StyledStr::new();
+
+    for (index, (name, prn)) in lines(tree, 0).into_iter().enumerate() {
+        if index > 0 {
+            rendered.plain("\r\n");
+        }
+
+        rendered.plain(name);
+
+        if let Some(prn) = prn {
+            rendered.push_str(Some(Style::Hint), format!("  {prn}"));
+        }
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cohort(name: &str, parent: Option<&str>) -> Value {
+        json!({
+            "name": name,
+            "prn": format!("prn:{name}"),
+            "parent_cohort_prn": parent.map(|parent| format!("prn:{parent}")),
+        })
+    }
+
+    fn names(tree: &[Value]) -> Vec<Value> {
+        tree.iter()
+            .map(|cohort| {
+                json!([
+                    cohort["name"],
+                    names(cohort["children"].as_array().unwrap())
+                ])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cohorts_are_nested_below_their_parents() {
+        let cohorts = [
+            cohort("fleet", None),
+            cohort("beta", Some("fleet")),
+            cohort("canary", Some("beta")),
+            cohort("stable", Some("fleet")),
+            cohort("lab", None),
+        ];
+
+        let (tree, cycles) = nest(&cohorts);
+
+        assert_eq!(
+            names(&tree),
+            vec![
+                json!(["fleet", [["beta", [["canary", []]]], ["stable", []]]]),
+                json!(["lab", []]),
+            ]
+        );
+        assert!(cycles.is_empty());
+    }
+
+    #[test]
+    fn tree_is_indented_by_depth() {
+        let (tree, _) = nest(&[
+            cohort("fleet", None),
+            cohort("beta", Some("fleet")),
+            cohort("canary", Some("beta")),
+            cohort("lab", None),
+        ]);
+
+        assert_eq!(
+            lines(&tree, 0),
+            vec![
+                ("fleet".to_string(), Some("prn:fleet")),
+                ("  beta".to_string(), Some("prn:beta")),
+                ("    canary".to_string(), Some("prn:canary")),
+                ("lab".to_string(), Some("prn:lab")),
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles_are_broken_and_reported() {
+        let cohorts = [
+            cohort("root", None),
+            cohort("a", Some("b")),
+            cohort("b", Some("a")),
+        ];
+
+        let (tree, cycles) = nest(&cohorts);
+
+        assert_eq!(
+            names(&tree),
+            vec![json!(["root", []]), json!(["a", [["b", []]]])]
+        );
+        assert_eq!(cycles, vec!["prn:a"]);
+    }
+
+    #[test]
+    fn cohorts_with_a_parent_outside_the_results_are_roots() {
+        let (tree, cycles) = nest(&[cohort("beta", Some("fleet"))]);
+
+        assert_eq!(names(&tree), vec![json!(["beta", []])]);
+        assert!(cycles.is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+use std::io;
+
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+
+use crate::Error;
+
+#[derive(Parser, Debug)]
+pub struct CompletionsCommand {
+    /// The shell to generate the completion script for.
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+impl CompletionsCommand {
+    pub fn run(self) -> Result<(), Error> {
+        let mut command = crate::Program::command();
+        let name = command.get_name().to_string();
+
+        clap_complete::generate(self.shell, &mut command, name, &mut io::stdout());
+
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
+use super::client::Client;
+use super::ApiError;
 use super::Command;
+use crate::print_created;
 use crate::print_json;
+use crate::utils::StyledStr;
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
@@ -14,6 +18,7 @@ use peridio_sdk::api::deployments::UpdateDeploymentCondition;
 use peridio_sdk::api::deployments::UpdateDeploymentParams;
 use peridio_sdk::api::Api;
 use peridio_sdk::api::ApiOptions;
+use serde_json::{json, Value};
 use snafu::ResultExt;
 use uuid::Uuid;
 
@@ -21,6 +26,8 @@ use uuid::Uuid;
 pub enum DeploymentsCommand {
     Create(Command<CreateCommand>),
     Delete(Command<DeleteCommand>),
+    Disable(Command<ToggleCommand>),
+    Enable(Command<ToggleCommand>),
     Get(Command<GetCommand>),
     List(Command<ListCommand>),
     Update(Command<UpdateCommand>),
@@ -31,6 +38,8 @@ impl DeploymentsCommand {
         match self {
             Self::Create(cmd) => cmd.run(global_options).await,
             Self::Delete(cmd) => cmd.run(global_options).await,
+            Self::Disable(cmd) => cmd.run(global_options, false).await,
+            Self::Enable(cmd) => cmd.run(global_options, true).await,
             Self::Get(cmd) => cmd.run(global_options).await,
             Self::List(cmd) => cmd.run(global_options).await,
             Self::Update(cmd) => cmd.run(global_options).await,
@@ -87,7 +96,7 @@ impl Command<CreateCommand> {
         });
 
         match api.deployments().create(params).await.context(ApiSnafu)? {
-            Some(deployment) => print_json!(&deployment),
+            Some(deployment) => print_created!(&deployment),
             None => panic!(),
         }
 
@@ -274,3 +283,70 @@ impl Command<UpdateCommand> {
         Ok(())
     }
 }
+
+/// Activate or deactivate a deployment, leaving its other fields as they are.
+#[derive(Parser, Debug)]
+pub struct ToggleCommand {
+    /// The name of the deployment.
+    #[arg(long)]
+    deployment_name: String,
+
+    /// The name of the product the deployment belongs to.
+    #[arg(long)]
+    product_name: String,
+}
+
+impl Command<ToggleCommand> {
+    async fn run(self, global_options: GlobalOptions, active: bool) -> Result<(), Error> {
+        let client = Client::new(&global_options)?;
+        let path = format!(
+            "/orgs/{}/products/{}/deployments/{}",
+            global_options.organization_name.unwrap(),
+            self.inner.product_name,
+            self.inner.deployment_name
+        );
+
+        let deployment = match client.patch(&path, &toggle(active)).await {
+            Err(Error::Request {
+                source: ApiError::NotFound,
+                ..
+            }) => {
+                let mut error = StyledStr::new();
+                error
+                    .error("error: ")
+                    .plain(format!(
+                        "No deployment is named {} in the product:\r\n",
+                        self.inner.deployment_name
+                    ))
+                    .warning(format!("\t{}", self.inner.product_name));
+
+                // UNAVAILABLE, like other missing resources
+                return Err(error.exit_err(69));
+            }
+            response => response?,
+        };
+
+        print_json!(&deployment);
+
+        Ok(())
+    }
+}
+
+// only is_active is sent, so concurrent changes to other fields are not overwritten
+fn toggle(active: bool) -> Value {
+    json!({ "deployment": { "is_active": active } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_only_patches_is_active() {
+        assert_eq!(toggle(true), json!({ "deployment": { "is_active": true } }));
+        assert_eq!(
+            toggle(false),
+            json!({ "deployment": { "is_active": false } })
+        );
+    }
+}
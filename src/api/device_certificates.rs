@@ -1,8 +1,11 @@
 use super::Command;
+use crate::print_created;
 use crate::print_json;
+use crate::utils::expiry::warn_if_expiring;
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
+use crate::JsonSerializationSnafu;
 use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
 use peridio_sdk::api::device_certificates::CreateDeviceCertificateParams;
@@ -89,7 +92,7 @@ impl Command<CreateCommand> {
             .await
             .context(ApiSnafu)?
         {
-            Some(device_certificate) => print_json!(&device_certificate),
+            Some(device_certificate) => print_created!(&device_certificate),
             None => panic!(),
         }
 
@@ -154,6 +157,10 @@ pub struct GetCommand {
     /// The serial number of the certificate you wish to get.
     #[arg(long)]
     certificate_serial: String,
+
+    /// Warn when the certificate expires within this many days.
+    #[arg(long, default_value_t = 30)]
+    expiry_warn_days: u32,
 }
 
 impl Command<GetCommand> {
@@ -177,7 +184,12 @@ impl Command<GetCommand> {
             .await
             .context(ApiSnafu)?
         {
-            Some(device_certificate) => print_json!(&device_certificate),
+            Some(device_certificate) => {
+                let value =
+                    serde_json::to_value(&device_certificate).context(JsonSerializationSnafu)?;
+                warn_if_expiring(&value, self.inner.expiry_warn_days);
+                print_json!(&value)
+            }
             None => panic!(),
         }
 
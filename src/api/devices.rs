@@ -1,14 +1,32 @@
 use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::time::Duration;
 
+use super::client::Client;
+use super::ApiError;
 use super::Command;
+use crate::print_created;
 use crate::print_json;
+use crate::utils::maybe_json_from_source;
+use crate::utils::merge_json;
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::parse_duration;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
+use crate::utils::StyledStr;
 use crate::ApiSnafu;
 use crate::Error;
+use crate::FileSnafu;
 use crate::GlobalOptions;
+use crate::JsonInputSnafu;
+use crate::JsonSerializationSnafu;
+use crate::NonExistingPathSnafu;
 use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
+use futures_util::{stream, StreamExt};
+use peridio_sdk::api::device_certificates::ListDeviceCertificateParams;
 use peridio_sdk::api::devices::GetUpdateDeviceParams;
 use peridio_sdk::api::devices::{
     AuthenticateDeviceParams, CreateDeviceParams, DeleteDeviceParams, GetDeviceParams,
@@ -16,7 +34,13 @@ use peridio_sdk::api::devices::{
 };
 use peridio_sdk::api::Api;
 use peridio_sdk::api::ApiOptions;
+use serde_json::{json, Map, Value};
 use snafu::ResultExt;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// how many devices have their certificates fetched at the same time
+const CERTIFICATE_CONCURRENCY: usize = 8;
 
 #[derive(Parser, Debug)]
 pub enum DevicesCommand {
@@ -27,6 +51,8 @@ pub enum DevicesCommand {
     List(Command<ListCommand>),
     Update(Command<UpdateCommand>),
     GetUpdate(Command<GetUpdateCommand>),
+    Bulk(Command<BulkCommand>),
+    Console(Command<ConsoleCommand>),
 }
 
 impl DevicesCommand {
@@ -39,10 +65,133 @@ impl DevicesCommand {
             Self::List(cmd) => cmd.run(global_options).await,
             Self::Update(cmd) => cmd.run(global_options).await,
             Self::GetUpdate(cmd) => cmd.run(global_options).await,
+            Self::Bulk(cmd) => cmd.run(global_options).await,
+            Self::Console(cmd) => cmd.run(global_options).await,
         }
     }
 }
 
+/// Request a web console shell on a device and print the URL to connect to it.
+#[derive(Parser, Debug)]
+pub struct ConsoleCommand {
+    /// The PRN of the device to open the shell on.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Device)
+    )]
+    prn: Prn,
+
+    /// Open the URL in the default browser instead of printing it.
+    #[arg(long, default_value = "false")]
+    open: bool,
+}
+
+impl Command<ConsoleCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let client = Client::new(&global_options)?;
+        let body = json!({
+            "web_console_shell": {
+                "device_prn": self.inner.prn.to_string(),
+            }
+        });
+
+        let shell = match client.post("/web_console_shells", &body).await {
+            Err(Error::Request {
+                source: ApiError::NotFound,
+                ..
+            }) => {
+                let mut error = StyledStr::new();
+                error
+                    .error("error: ")
+                    .plain("No device has the PRN:\r\n")
+                    .warning(format!("\t{}", self.inner.prn));
+
+                // UNAVAILABLE, like other missing resources
+                return Err(error.exit_err(69));
+            }
+            response => response?,
+        };
+
+        let Some(url) = shell
+            .pointer("/web_console_shell/url")
+            .and_then(Value::as_str)
+        else {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("The API returned no URL for the web console shell of the device:\r\n")
+                .warning(format!("\t{}", self.inner.prn));
+            return Err(error.data_err());
+        };
+
+        if self.inner.open {
+            match open_browser(url) {
+                Ok(()) => {
+                    output::status(&format!("Opened {url}"));
+
+                    return Ok(());
+                }
+                Err(error) => output::warn(format!(
+                    "Unable to open a browser, open the URL yourself: {error}"
+                )),
+            }
+        }
+
+        match output::format() {
+            None | Some(OutputFormat::Human) => output::print_rendered(url),
+            _ => {
+                print_json!(&shell);
+
+                Ok(())
+            }
+        }
+    }
+}
+
+// launch the default browser of the platform on `url` without waiting for it. No shell is
+// involved, and only https URLs are opened, so the API can't have anything else run.
+fn open_browser(url: &str) -> io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let url = browser_url(url)?;
+
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let mut command = Command::new("xdg-open");
+
+    command
+        .arg(url.as_str())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+// the URL to open, normalized so it holds no spaces or quotes
+fn browser_url(url: &str) -> io::Result<reqwest::Url> {
+    let url = reqwest::Url::parse(url)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+
+    if url.scheme() != "https" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("only https URLs are opened, not {url}"),
+        ));
+    }
+
+    Ok(url)
+}
+
 #[derive(Parser, Debug)]
 pub struct CreateCommand {
     /// An arbitrary string attached to the resource. Often useful for displaying to users.
@@ -82,7 +231,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Cohort)
     )]
-    cohort_prn: Option<String>,
+    cohort_prn: Option<Prn>,
 }
 
 impl Command<CreateCommand> {
@@ -96,7 +245,7 @@ impl Command<CreateCommand> {
             last_communication: self.inner.last_communication,
             tags: self.inner.tags,
             target: self.inner.target,
-            cohort_prn: self.inner.cohort_prn,
+            cohort_prn: self.inner.cohort_prn.map(|prn| prn.to_string()),
         };
 
         let api = Api::new(ApiOptions {
@@ -106,7 +255,7 @@ impl Command<CreateCommand> {
         });
 
         match api.devices().create(params).await.context(ApiSnafu)? {
-            Some(device) => print_json!(&device),
+            Some(device) => print_created!(&device),
             None => panic!(),
         }
 
@@ -156,10 +305,88 @@ pub struct GetCommand {
     /// The name of the product you wish to get the resource within.
     #[arg(long)]
     product_name: String,
+
+    /// Related resources to include in the device. May be given multiple times.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    include: Vec<DeviceInclude>,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum DeviceInclude {
+    Firmware,
+    Deployment,
+    Cohort,
+}
+
+impl DeviceInclude {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Firmware => "firmware",
+            Self::Deployment => "deployment",
+            Self::Cohort => "cohort",
+        }
+    }
+}
+
+// the `include` query parameter, each resource once in the given order
+fn include_query(include: &[DeviceInclude]) -> Vec<(&'static str, String)> {
+    let mut names: Vec<&str> = Vec::new();
+
+    for include in include {
+        if !names.contains(&include.as_str()) {
+            names.push(include.as_str());
+        }
+    }
+
+    if names.is_empty() {
+        Vec::new()
+    } else {
+        vec![("include", names.join(","))]
+    }
+}
+
+// move the resources of an `included` object next to the fields of the device they belong to
+fn merge_included(mut response: Value) -> Value {
+    let Some(Value::Object(included)) = response
+        .as_object_mut()
+        .and_then(|object| object.remove("included"))
+    else {
+        return response;
+    };
+
+    let device = if response.get("data").is_some() {
+        &mut response["data"]
+    } else {
+        &mut response
+    };
+
+    if let Value::Object(device) = device {
+        device.extend(included);
+    }
+
+    response
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        if !self.inner.include.is_empty() {
+            let client = Client::new(&global_options)?;
+            let path = format!(
+                "/orgs/{}/products/{}/devices/{}",
+                global_options.organization_name.unwrap(),
+                self.inner.product_name,
+                self.inner.device_identifier
+            );
+
+            let device = client
+                .get_with_query(&path, &include_query(&self.inner.include))
+                .await?;
+
+            print_json!(&merge_included(device));
+
+            return Ok(());
+        }
+
         let params = GetDeviceParams {
             device_identifier: self.inner.device_identifier,
             organization_name: global_options.organization_name.unwrap(),
@@ -186,13 +413,76 @@ pub struct ListCommand {
     /// The name of the product
     #[arg(long)]
     product_name: String,
+
+    /// Only list devices with a certificate that expires within this duration, e.g. 30d, adding its expiry as cert_expires_at.
+    ///
+    /// Certificates are fetched with one request per device.
+    #[arg(long, value_parser = parse_duration)]
+    cert_expiring_within: Option<Duration>,
+
+    /// Only list devices in this cohort.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Cohort)
+    )]
+    cohort_prn: Option<Prn>,
+
+    /// Only list devices with this tag. May be given multiple times to only list devices with every given tag.
+    #[arg(long)]
+    tag: Vec<String>,
+
+    /// Only list devices with this connection status.
+    #[arg(long, value_enum)]
+    status: Option<DeviceStatus>,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum DeviceStatus {
+    Online,
+    Offline,
+}
+
+impl DeviceStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Online => "online",
+            Self::Offline => "offline",
+        }
+    }
+}
+
+impl ListCommand {
+    // the query parameters of the filters, empty when none were given
+    fn filter_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+
+        if let Some(cohort_prn) = &self.cohort_prn {
+            query.push(("cohort_prn", cohort_prn.to_string()));
+        }
+
+        // the API only returns devices that have all of the tags
+        if !self.tag.is_empty() {
+            query.push(("tags", self.tag.join(",")));
+        }
+
+        if let Some(status) = &self.status {
+            query.push(("status", status.as_str().to_string()));
+        }
+
+        query
+    }
 }
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListDeviceParams {
-            organization_name: global_options.organization_name.unwrap(),
-            product_name: self.inner.product_name,
+        let organization_name = global_options.organization_name.clone().unwrap();
+        let query = self.inner.filter_query();
+
+        // the SDK can't filter devices, filtered lists are requested directly
+        let client = if query.is_empty() {
+            None
+        } else {
+            Some(Client::new(&global_options)?)
         };
 
         let api = Api::new(ApiOptions {
@@ -201,15 +491,114 @@ impl Command<ListCommand> {
             ca_bundle_path: global_options.ca_path,
         });
 
-        match api.devices().list(params).await.context(ApiSnafu)? {
-            Some(devices) => print_json!(&devices),
-            None => panic!(),
+        let devices = match client {
+            Some(client) => {
+                let path = format!(
+                    "/orgs/{organization_name}/products/{}/devices",
+                    self.inner.product_name
+                );
+
+                client.get_with_query(&path, &query).await?
+            }
+            None => {
+                let params = ListDeviceParams {
+                    organization_name: organization_name.clone(),
+                    product_name: self.inner.product_name.clone(),
+                };
+
+                match api.devices().list(params).await.context(ApiSnafu)? {
+                    Some(devices) => {
+                        serde_json::to_value(devices).context(JsonSerializationSnafu)?
+                    }
+                    None => panic!(),
+                }
+            }
+        };
+
+        let Some(within) = self.inner.cert_expiring_within else {
+            print_json!(&devices);
+            return Ok(());
+        };
+
+        let devices = output::envelope_items(&devices)
+            .cloned()
+            .unwrap_or_default();
+
+        if devices.len() > CERTIFICATE_CONCURRENCY {
+            output::warn(format!(
+                "Fetching certificates for {} devices, one request per device",
+                devices.len()
+            ));
         }
 
+        let deadline = OffsetDateTime::now_utc() + within;
+
+        let expiring = stream::iter(devices)
+            .map(|device| {
+                let api = &api;
+                let organization_name = organization_name.clone();
+                let product_name = self.inner.product_name.clone();
+
+                async move {
+                    let Some(device_identifier) = device
+                        .get("identifier")
+                        .and_then(Value::as_str)
+                        .map(str::to_owned)
+                    else {
+                        return Ok(None);
+                    };
+
+                    let params = ListDeviceCertificateParams {
+                        organization_name,
+                        product_name,
+                        device_identifier,
+                    };
+
+                    let certificates = match api
+                        .device_certificates()
+                        .list(params)
+                        .await
+                        .context(ApiSnafu)?
+                    {
+                        Some(certificates) => {
+                            serde_json::to_value(certificates).context(JsonSerializationSnafu)?
+                        }
+                        None => Value::Null,
+                    };
+
+                    Ok::<_, Error>(earliest_expiry(&certificates).map(|expiry| (device, expiry)))
+                }
+            })
+            .buffered(CERTIFICATE_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut expiring_devices = Vec::new();
+
+        for result in expiring {
+            if let Some((mut device, expiry)) = result? {
+                if expiry <= deadline {
+                    device["cert_expires_at"] = json!(expiry.format(&Rfc3339).unwrap());
+                    expiring_devices.push(device);
+                }
+            }
+        }
+
+        print_json!(&json!({ "data": expiring_devices }));
+
         Ok(())
     }
 }
 
+// the soonest `not_after` among a device's certificates
+fn earliest_expiry(certificates: &Value) -> Option<OffsetDateTime> {
+    output::envelope_items(certificates)?
+        .iter()
+        .filter_map(|certificate| certificate.get("not_after").and_then(Value::as_str))
+        .filter_map(|not_after| OffsetDateTime::parse(not_after, &Rfc3339).ok())
+        .min()
+}
+
 #[derive(Parser, Debug)]
 pub struct UpdateCommand {
     /// An arbitrary string attached to the resource. Often useful for displaying to users.
@@ -236,13 +625,294 @@ pub struct UpdateCommand {
     #[arg(long, num_args = 0.., value_delimiter = ',')]
     tags: Option<Vec<String>>,
 
+    /// A tag to add to the tags the device already has. May be given multiple times.
+    #[arg(long, conflicts_with = "tags")]
+    add_tag: Vec<String>,
+
+    /// A tag to remove from the tags the device has. May be given multiple times.
+    #[arg(long, conflicts_with = "tags")]
+    remove_tag: Vec<String>,
+
+    /// A JSON object, @PATH to a JSON file or @- to read it from stdin, to set as the device's metadata.
+    #[arg(long)]
+    metadata: Option<String>,
+
+    /// Deep-merge --metadata into the device's current metadata instead of replacing it. Keys set to null are removed.
+    #[arg(long, requires = "metadata", default_value = "false")]
+    merge_data: bool,
+
+    /// Append arrays of --metadata to the device's current ones instead of replacing them.
+    #[arg(long, requires = "merge_data", default_value = "false")]
+    merge_arrays: bool,
+
     /// The target of the device.
     #[arg(long)]
     target: Option<String>,
 }
 
+impl UpdateCommand {
+    // whether the update depends on the device's current state, which the SDK can't fetch and
+    // patch in one go
+    fn merges(&self) -> bool {
+        !self.add_tag.is_empty() || !self.remove_tag.is_empty() || self.metadata.is_some()
+    }
+
+    // the fields of `current` the flags change, fields already set to their value are left out
+    fn changes(
+        &self,
+        current: &Value,
+        metadata: &Option<Map<String, Value>>,
+    ) -> Map<String, Value> {
+        let mut changes = Map::new();
+
+        let mut set = |field: &str, value: Value| {
+            if current.get(field) != Some(&value) {
+                changes.insert(field.to_string(), value);
+            }
+        };
+
+        if let Some(description) = &self.description {
+            set("description", json!(description));
+        }
+
+        if let Some(healthy) = self.healthy {
+            set("healthy", json!(healthy));
+        }
+
+        if let Some(last_communication) = &self.last_communication {
+            set("last_communication", json!(last_communication));
+        }
+
+        if let Some(target) = &self.target {
+            set("target", json!(target));
+        }
+
+        if let Some(metadata) = metadata {
+            let patch = Value::Object(metadata.clone());
+
+            if self.merge_data {
+                let mut merged = current
+                    .get("metadata")
+                    .filter(|metadata| metadata.is_object())
+                    .cloned()
+                    .unwrap_or_else(|| json!({}));
+                merge_json(&mut merged, &patch, self.merge_arrays);
+
+                set("metadata", merged);
+            } else {
+                set("metadata", patch);
+            }
+        }
+
+        if let Some(tags) = &self.tags {
+            set("tags", json!(tags));
+        } else if !self.add_tag.is_empty() || !self.remove_tag.is_empty() {
+            set(
+                "tags",
+                json!(merged_tags(current, &self.add_tag, &self.remove_tag)),
+            );
+        }
+
+        changes
+    }
+}
+
+// the tags of `current` with `add` added and `remove` removed, keeping their order
+fn merged_tags(current: &Value, add: &[String], remove: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = current
+        .get("tags")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_owned)
+        .collect();
+
+    for tag in add {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+
+    tags.retain(|tag| !remove.contains(tag));
+
+    tags
+}
+
+/// Add or remove a tag, or move to a cohort, every device of a list.
+#[derive(Parser, Debug)]
+pub struct BulkCommand {
+    /// The operation to perform on every device.
+    #[arg(long, value_enum)]
+    operation: BulkOperation,
+
+    /// The name of the product the devices belong to.
+    #[arg(long)]
+    product_name: String,
+
+    /// A file with the identifiers of the devices, one per line or as a JSON array. Reads stdin when omitted or -.
+    #[arg(long)]
+    file: Option<PathBuf>,
+
+    /// The tag to add or remove.
+    #[arg(
+        long,
+        required_if_eq_any([("operation", "add-tag"), ("operation", "remove-tag")])
+    )]
+    tag: Option<String>,
+
+    /// The PRN of the cohort to move the devices to.
+    #[arg(
+        long,
+        required_if_eq("operation", "move-cohort"),
+        value_parser = PRNValueParser::new(PRNType::Cohort)
+    )]
+    cohort_prn: Option<Prn>,
+
+    /// How many devices are updated at the same time.
+    #[arg(long, default_value = "8", value_parser = clap::value_parser!(u8).range(1..))]
+    concurrency: u8,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum BulkOperation {
+    AddTag,
+    RemoveTag,
+    MoveCohort,
+}
+
+impl BulkCommand {
+    // the changes the operation makes to a device, `None` when it has nothing to change
+    fn changes(&self, current: &Value) -> Option<Value> {
+        let tag = self.tag.iter().cloned().collect::<Vec<_>>();
+
+        let (field, value) = match self.operation {
+            BulkOperation::AddTag => ("tags", json!(merged_tags(current, &tag, &[]))),
+            BulkOperation::RemoveTag => ("tags", json!(merged_tags(current, &[], &tag))),
+            BulkOperation::MoveCohort => (
+                "cohort_prn",
+                json!(self.cohort_prn.as_ref().map(Prn::to_string)),
+            ),
+        };
+
+        (current.get(field) != Some(&value)).then(|| json!({ "device": { field: value } }))
+    }
+}
+
+impl Command<BulkCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let identifiers = match &self.inner.file {
+            Some(path) if path.as_os_str() != "-" => {
+                fs::read_to_string(path).context(NonExistingPathSnafu { path })?
+            }
+            _ => {
+                let mut input = String::new();
+                io::stdin().read_to_string(&mut input).context(FileSnafu)?;
+                input
+            }
+        };
+        let identifiers = device_identifiers(&identifiers)?;
+
+        let client = Client::new(&global_options)?;
+        let organization_name = global_options.organization_name.unwrap();
+        let retry_conflict = !global_options.no_retry_conflict;
+        let bulk = &self.inner;
+
+        let results = stream::iter(identifiers)
+            .map(|identifier| {
+                let client = &client;
+                let path = format!(
+                    "/orgs/{organization_name}/products/{}/devices/{identifier}",
+                    bulk.product_name
+                );
+
+                async move {
+                    let result = client
+                        .update(&path, retry_conflict, |response| {
+                            bulk.changes(response.get("data").unwrap_or(response))
+                        })
+                        .await;
+
+                    (identifier, result)
+                }
+            })
+            .buffered(bulk.concurrency.into())
+            .collect::<Vec<_>>()
+            .await;
+
+        let rows: Vec<Value> = results
+            .iter()
+            .map(|(identifier, result)| {
+                let (result, detail) = match result {
+                    Ok(Some(_)) => ("pass", "updated".to_string()),
+                    Ok(None) => ("pass", "unchanged".to_string()),
+                    Err(error) => ("fail", error.to_string()),
+                };
+
+                json!({
+                    "device_identifier": identifier,
+                    "result": result,
+                    "detail": detail,
+                })
+            })
+            .collect();
+
+        print_json!(&rows);
+
+        let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+
+        output::status(&format!(
+            "{} of {} devices succeeded, {failed} failed",
+            results.len() - failed,
+            results.len()
+        ));
+
+        if failed > 0 {
+            // keep the report in --output-file, failing would discard it
+            output::finish()?;
+
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain(format!("{failed} of {} devices failed", results.len()));
+
+            // a temporary failure is worth retrying, even if other devices failed for good
+            let temporary = results
+                .iter()
+                .any(|(_, result)| matches!(result, Err(error) if error.exit_code() == 75));
+            if temporary {
+                return Err(error.temp_fail_err());
+            }
+
+            return Err(error.data_err());
+        }
+
+        Ok(())
+    }
+}
+
+// identifiers one per line, or as a JSON array of strings
+fn device_identifiers(input: &str) -> Result<Vec<String>, Error> {
+    if input.trim_start().starts_with('[') {
+        return serde_json::from_str(input).context(JsonInputSnafu {
+            input: "the device list",
+        });
+    }
+
+    Ok(input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        if self.inner.merges() {
+            return self.merge(global_options).await;
+        }
+
         let params = UpdateDeviceParams {
             device_identifier: self.inner.device_identifier,
             organization_name: global_options.organization_name.unwrap(),
@@ -267,6 +937,35 @@ impl Command<UpdateCommand> {
 
         Ok(())
     }
+
+    // apply the changes to the device's current tags and metadata
+    async fn merge(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let metadata = maybe_json_from_source(self.inner.metadata.clone())?;
+
+        let client = Client::new(&global_options)?;
+        let path = format!(
+            "/orgs/{}/products/{}/devices/{}",
+            global_options.organization_name.unwrap(),
+            self.inner.product_name,
+            self.inner.device_identifier
+        );
+
+        let device = client
+            .update(&path, !global_options.no_retry_conflict, |response| {
+                let current = response.get("data").unwrap_or(response);
+                let changes = self.inner.changes(current, &metadata);
+
+                (!changes.is_empty()).then(|| json!({ "device": changes }))
+            })
+            .await?;
+
+        match device {
+            Some(device) => print_json!(&device),
+            None => output::status("The device already has the requested tags and metadata"),
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -329,7 +1028,7 @@ pub struct GetUpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Device)
     )]
-    device_prn: String,
+    device_prn: Prn,
 
     /// The PRN of the release to consider as the device's current release during bundle resolution.
     #[arg(
@@ -337,7 +1036,7 @@ pub struct GetUpdateCommand {
         value_parser = PRNValueParser::new(PRNType::Release),
         required_unless_present_any = ["bundle_prn", "release_version"]
     )]
-    release_prn: Option<String>,
+    release_prn: Option<Prn>,
 
     /// The PRN of the bundle to consider as the device's current bundle during bundle resolution.
     #[arg(
@@ -345,7 +1044,7 @@ pub struct GetUpdateCommand {
         value_parser = PRNValueParser::new(PRNType::Bundle),
         required_unless_present_any = ["release_prn", "release_version"]
     )]
-    bundle_prn: Option<String>,
+    bundle_prn: Option<Prn>,
 
     /// The version to consider as the device's current release version during bundle resolution.
     #[arg(long, required_unless_present_any = ["release_prn", "bundle_prn"])]
@@ -359,9 +1058,9 @@ pub struct GetUpdateCommand {
 impl Command<GetUpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = GetUpdateDeviceParams {
-            device_prn: self.inner.device_prn,
-            release_prn: self.inner.release_prn,
-            bundle_prn: self.inner.bundle_prn,
+            device_prn: self.inner.device_prn.to_string(),
+            release_prn: self.inner.release_prn.map(|prn| prn.to_string()),
+            bundle_prn: self.inner.bundle_prn.map(|prn| prn.to_string()),
             release_version: self.inner.release_version,
             write: self.inner.write,
         };
@@ -380,3 +1079,283 @@ impl Command<GetUpdateCommand> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COHORT_PRN: &str =
+        "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef:cohort:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+    #[test]
+    fn only_https_urls_are_opened_in_a_browser() {
+        assert_eq!(
+            browser_url("https://console.peridio.com/shells/1?a=1&b=2")
+                .unwrap()
+                .as_str(),
+            "https://console.peridio.com/shells/1?a=1&b=2"
+        );
+        assert_eq!(
+            browser_url("https://console.peridio.com/a b\"c")
+                .unwrap()
+                .as_str(),
+            "https://console.peridio.com/a%20b%22c"
+        );
+
+        for url in [
+            "http://console.peridio.com/shells/1",
+            "file:///etc/passwd",
+            "calc.exe",
+            "-a https://console.peridio.com",
+        ] {
+            assert!(browser_url(url).is_err(), "{url}");
+        }
+    }
+
+    fn filter_query(filters: &[&str]) -> Vec<(&'static str, String)> {
+        let args = ["list", "--product-name", "product"].iter().chain(filters);
+
+        ListCommand::try_parse_from(args).unwrap().filter_query()
+    }
+
+    fn update(flags: &[&str]) -> UpdateCommand {
+        let args = [
+            "update",
+            "--product-name",
+            "product",
+            "--device-identifier",
+            "device",
+        ]
+        .iter()
+        .chain(flags);
+
+        UpdateCommand::try_parse_from(args).unwrap()
+    }
+
+    #[test]
+    fn tags_are_added_and_removed() {
+        let current = json!({ "tags": ["lab", "beta"] });
+
+        let changes = update(&[
+            "--add-tag",
+            "field",
+            "--add-tag",
+            "lab",
+            "--remove-tag",
+            "beta",
+        ])
+        .changes(&current, &None);
+
+        assert_eq!(Value::Object(changes), json!({ "tags": ["lab", "field"] }));
+    }
+
+    #[test]
+    fn metadata_and_fields_are_changed() {
+        let current = json!({ "tags": [], "target": "arm" });
+        let metadata = json!({ "site": "berlin" }).as_object().cloned();
+
+        let command = update(&["--metadata", r#"{"site": "berlin"}"#, "--target", "x86"]);
+
+        assert!(command.merges());
+        assert_eq!(
+            Value::Object(command.changes(&current, &metadata)),
+            json!({ "metadata": { "site": "berlin" }, "target": "x86" })
+        );
+    }
+
+    #[test]
+    fn merged_metadata_keeps_the_other_keys() {
+        let current = json!({ "metadata": { "site": "berlin", "sensors": ["temperature"] } });
+        let flags = ["--metadata", r#"{"sensors": ["humidity"], "site": null}"#];
+        let metadata = json!({ "sensors": ["humidity"], "site": null })
+            .as_object()
+            .cloned();
+
+        assert_eq!(
+            Value::Object(update(&flags).changes(&current, &metadata)),
+            json!({ "metadata": { "sensors": ["humidity"], "site": null } })
+        );
+
+        let merged = [flags.as_slice(), &["--merge-data"]].concat();
+        assert_eq!(
+            Value::Object(update(&merged).changes(&current, &metadata)),
+            json!({ "metadata": { "sensors": ["humidity"] } })
+        );
+
+        let concatenated = [merged.as_slice(), &["--merge-arrays"]].concat();
+        assert_eq!(
+            Value::Object(update(&concatenated).changes(&current, &metadata)),
+            json!({ "metadata": { "sensors": ["temperature", "humidity"] } })
+        );
+    }
+
+    #[test]
+    fn merge_arrays_requires_merge_data() {
+        let args = ["update", "--product-name", "product", "--device-identifier"];
+
+        assert!(UpdateCommand::try_parse_from(args.iter().chain(&[
+            "device",
+            "--metadata",
+            "{}",
+            "--merge-arrays"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn unchanged_fields_are_left_out() {
+        let current = json!({ "tags": ["lab"], "metadata": { "site": "berlin" } });
+        let metadata = json!({ "site": "berlin" }).as_object().cloned();
+
+        let changes =
+            update(&["--add-tag", "lab", "--remove-tag", "beta"]).changes(&current, &metadata);
+
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn replacing_tags_does_not_merge() {
+        assert!(!update(&["--tags", "lab,beta"]).merges());
+    }
+
+    #[test]
+    fn includes_are_one_query_parameter() {
+        let command = GetCommand::try_parse_from([
+            "get",
+            "--product-name",
+            "product",
+            "--device-identifier",
+            "device",
+            "--include",
+            "firmware,cohort",
+            "--include",
+            "deployment",
+            "--include",
+            "firmware",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            include_query(&command.include),
+            vec![("include", "firmware,cohort,deployment".to_string())]
+        );
+        assert!(include_query(&[]).is_empty());
+    }
+
+    #[test]
+    fn unknown_include_is_rejected() {
+        let error = GetCommand::try_parse_from([
+            "get",
+            "--product-name",
+            "product",
+            "--device-identifier",
+            "device",
+            "--include",
+            "owner",
+        ])
+        .unwrap_err();
+
+        assert_eq!(error.kind(), clap::error::ErrorKind::InvalidValue);
+        assert!(error.to_string().contains("firmware, deployment, cohort"));
+    }
+
+    #[test]
+    fn included_resources_are_merged_into_the_device() {
+        let response = json!({
+            "data": { "identifier": "device" },
+            "included": { "firmware": { "version": "1.0.0" } },
+        });
+
+        assert_eq!(
+            merge_included(response),
+            json!({ "data": { "identifier": "device", "firmware": { "version": "1.0.0" } } })
+        );
+    }
+
+    #[test]
+    fn device_identifiers_are_lines_or_json() {
+        assert_eq!(
+            device_identifiers("first\n\n  second \n").unwrap(),
+            vec!["first", "second"]
+        );
+        assert_eq!(
+            device_identifiers(r#"["first", "second"]"#).unwrap(),
+            vec!["first", "second"]
+        );
+        assert!(device_identifiers("[first").is_err());
+    }
+
+    #[test]
+    fn bulk_operations_change_one_field() {
+        let bulk = |flags: &[&str]| {
+            let args = ["bulk", "--product-name", "product"].iter().chain(flags);
+
+            BulkCommand::try_parse_from(args).unwrap()
+        };
+        let current = json!({ "tags": ["lab"], "cohort_prn": COHORT_PRN });
+
+        assert_eq!(
+            bulk(&["--operation", "add-tag", "--tag", "beta"]).changes(&current),
+            Some(json!({ "device": { "tags": ["lab", "beta"] } }))
+        );
+        assert_eq!(
+            bulk(&["--operation", "remove-tag", "--tag", "lab"]).changes(&current),
+            Some(json!({ "device": { "tags": [] } }))
+        );
+        assert_eq!(
+            bulk(&["--operation", "move-cohort", "--cohort-prn", COHORT_PRN]).changes(&current),
+            None
+        );
+    }
+
+    #[test]
+    fn no_filters_have_no_query() {
+        assert!(filter_query(&[]).is_empty());
+    }
+
+    #[test]
+    fn each_filter_is_a_query_parameter() {
+        assert_eq!(
+            filter_query(&["--cohort-prn", COHORT_PRN]),
+            vec![("cohort_prn", COHORT_PRN.to_string())]
+        );
+        assert_eq!(
+            filter_query(&["--tag", "lab"]),
+            vec![("tags", "lab".to_string())]
+        );
+        assert_eq!(
+            filter_query(&["--status", "offline"]),
+            vec![("status", "offline".to_string())]
+        );
+    }
+
+    #[test]
+    fn filters_combine() {
+        assert_eq!(
+            filter_query(&[
+                "--tag",
+                "lab",
+                "--status",
+                "online",
+                "--tag",
+                "beta",
+                "--cohort-prn",
+                COHORT_PRN,
+            ]),
+            vec![
+                ("cohort_prn", COHORT_PRN.to_string()),
+                ("tags", "lab,beta".to_string()),
+                ("status", "online".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_status_is_rejected() {
+        let error =
+            ListCommand::try_parse_from(["list", "--product-name", "product", "--status", "busy"])
+                .unwrap_err();
+
+        assert_eq!(error.kind(), clap::error::ErrorKind::InvalidValue);
+    }
+}
@@ -0,0 +1,408 @@
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::utils::StyledStr;
+
+/// Why a request of the CLI's own client failed, see `Client`.
+///
+/// Commands match on the variants instead of on statuses and bodies, and errors they don't
+/// handle themselves are rendered and exited with by `main`, see `styled` and `exit_code`.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The API key is invalid (401) or lacks the permissions (403).
+    Unauthorized { status: StatusCode },
+    /// The resource does not exist (404).
+    NotFound,
+    /// The API rejected the request and explained why in its error envelope (4xx).
+    Validation {
+        status: StatusCode,
+        envelope: ErrorEnvelope,
+    },
+    /// Too many requests were sent (429).
+    RateLimited { retry_after: Option<Duration> },
+    /// The API failed to handle the request (5xx).
+    Server { status: StatusCode },
+    /// Any other unsuccessful response, such as a 412 or a 4xx without an error envelope.
+    Rejected { status: StatusCode, body: String },
+    /// The request did not get a response.
+    Network(reqwest::Error),
+}
+
+impl ApiError {
+    /// Classify an unsuccessful response.
+    pub fn from_response(status: StatusCode, headers: &HeaderMap, body: &str) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::Unauthorized { status },
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited {
+                retry_after: retry_after(headers),
+            },
+            status if status.is_server_error() => Self::Server { status },
+            // a conflicting update is retried by `Client::update`, whatever the body says
+            StatusCode::PRECONDITION_FAILED => Self::Rejected {
+                status,
+                body: body.to_string(),
+            },
+            status if status.is_client_error() => match ErrorEnvelope::parse(body) {
+                Some(envelope) => Self::Validation { status, envelope },
+                None => Self::Rejected {
+                    status,
+                    body: body.to_string(),
+                },
+            },
+            status => Self::Rejected {
+                status,
+                body: body.to_string(),
+            },
+        }
+    }
+
+    /// The status of the response, `None` without one.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Unauthorized { status }
+            | Self::Validation { status, .. }
+            | Self::Server { status }
+            | Self::Rejected { status, .. } => Some(*status),
+            Self::NotFound => Some(StatusCode::NOT_FOUND),
+            Self::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            Self::Network(_) => None,
+        }
+    }
+
+    /// The sysexits style code to exit with, see `EXIT_CODES`.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            // NOPERM
+            Self::Unauthorized { .. } => 77,
+            // UNAVAILABLE
+            Self::NotFound => 69,
+            // DATAERR
+            Self::Validation { .. } => 65,
+            Self::Rejected { status, .. } if status.is_client_error() => 65,
+            // TEMPFAIL
+            Self::RateLimited { .. } | Self::Server { .. } => 75,
+            Self::Network(source) if source.is_timeout() || source.is_connect() => 75,
+            _ => 1,
+        }
+    }
+
+    /// The error as printed to stderr.
+    pub fn styled(&self) -> StyledStr {
+        let mut error = StyledStr::new();
+        error.error("error: ");
+
+        match self {
+            Self::Validation { status, envelope } => {
+                error.plain(envelope.summary(*status));
+
+                if !envelope.fields.is_empty() {
+                    error.plain(":");
+                }
+
+                for FieldError { field, message } in &envelope.fields {
+                    error
+                        .plain("\r\n\t")
+                        .warning(field)
+                        .plain(format!(": {message}"));
+                }
+            }
+            Self::Rejected { status, body } => error
+                .plain(format!("Request failed with {status}:\r\n"))
+                .warning(format!("\t{body}")),
+            Self::Network(source) => error
+                .plain(format!("{}:\r\n", network_failure(source)))
+                .warning(format!("\t{source}")),
+            _ => error.plain(self.to_string()),
+        };
+
+        error
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unauthorized { status } => {
+                write!(f, "The API key is not authorized ({status})")
+            }
+            Self::NotFound => write!(f, "The resource was not found"),
+            Self::Validation { status, envelope } => {
+                write!(f, "{}", envelope.summary(*status))?;
+
+                for (index, FieldError { field, message }) in envelope.fields.iter().enumerate() {
+                    let separator = if index == 0 { ":" } else { "," };
+                    write!(f, "{separator} {field} {message}")?;
+                }
+
+                Ok(())
+            }
+            Self::RateLimited {
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "Too many requests, retry in {} seconds",
+                retry_after.as_secs()
+            ),
+            Self::RateLimited { retry_after: None } => {
+                write!(f, "Too many requests, retry later")
+            }
+            Self::Server { status } => {
+                write!(f, "The API failed to handle the request ({status})")
+            }
+            Self::Rejected { status, body } => write!(f, "Request failed with {status}: {body}"),
+            Self::Network(source) => write!(f, "{}: {source}", network_failure(source)),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Network(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn network_failure(source: &reqwest::Error) -> &'static str {
+    match (source.is_timeout(), source.is_connect()) {
+        (true, true) => "Timed out connecting to the API",
+        (true, false) => "The API did not respond in time",
+        _ => "Unable to reach the API",
+    }
+}
+
+/// The error body of the API, `{"errors": {"detail": "..", "field": [".."]}}`, optionally with
+/// a top-level `code` and `message`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ErrorEnvelope {
+    pub code: Option<String>,
+    pub message: Option<String>,
+    pub fields: Vec<FieldError>,
+}
+
+/// A message about one field of the request, nested fields are named by their path such as
+/// `metadata.serial` or `tags[1]`.
+#[derive(Debug, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl ErrorEnvelope {
+    /// The envelope of `body`, `None` when it is not one.
+    pub fn parse(body: &str) -> Option<Self> {
+        let body = serde_json::from_str::<Value>(body).ok()?;
+        let errors = body.get("errors").and_then(Value::as_object);
+        let string = |value: Option<&Value>| value.and_then(Value::as_str).map(str::to_owned);
+
+        let mut envelope = Self {
+            code: string(body.get("code")),
+            message: string(body.get("message"))
+                .or_else(|| string(errors.and_then(|errors| errors.get("detail")))),
+            fields: Vec::new(),
+        };
+
+        if errors.is_none() && envelope.message.is_none() {
+            return None;
+        }
+
+        for (field, value) in errors.into_iter().flatten() {
+            if field != "detail" {
+                envelope.push_field(field.clone(), value);
+            }
+        }
+
+        Some(envelope)
+    }
+
+    // collect the messages of `value`, descending into nested objects and lists
+    fn push_field(&mut self, field: String, value: &Value) {
+        match value {
+            Value::String(message) => self.fields.push(FieldError {
+                field,
+                message: message.clone(),
+            }),
+            Value::Array(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    match value {
+                        Value::String(_) => self.push_field(field.clone(), value),
+                        _ => self.push_field(format!("{field}[{index}]"), value),
+                    }
+                }
+            }
+            Value::Object(fields) => {
+                for (nested, value) in fields {
+                    self.push_field(format!("{field}.{nested}"), value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // the sentence that leads the field messages
+    fn summary(&self, status: StatusCode) -> String {
+        let message = self
+            .message
+            .as_deref()
+            .unwrap_or("The request was rejected");
+
+        match &self.code {
+            Some(code) => format!("{message} ({status}, {code})"),
+            None => format!("{message} ({status})"),
+        }
+    }
+}
+
+// the seconds of a Retry-After header, the HTTP date form is not used by the API
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(status: u16, body: &str) -> ApiError {
+        ApiError::from_response(
+            StatusCode::from_u16(status).unwrap(),
+            &HeaderMap::new(),
+            body,
+        )
+    }
+
+    #[test]
+    fn statuses_are_classified() {
+        assert!(matches!(
+            classify(401, ""),
+            ApiError::Unauthorized { status } if status == StatusCode::UNAUTHORIZED
+        ));
+        assert!(matches!(classify(403, ""), ApiError::Unauthorized { .. }));
+        assert!(matches!(classify(404, ""), ApiError::NotFound));
+        assert!(matches!(
+            classify(503, ""),
+            ApiError::Server { status } if status == StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(matches!(
+            classify(412, r#"{"errors": {"detail": "Precondition Failed"}}"#),
+            ApiError::Rejected { .. }
+        ));
+        assert!(matches!(
+            classify(412, "stale"),
+            ApiError::Rejected { status, body } if status == StatusCode::PRECONDITION_FAILED && body == "stale"
+        ));
+    }
+
+    #[test]
+    fn envelopes_list_every_field() {
+        let error = classify(
+            422,
+            r#"{"errors": {"name": ["can't be blank"], "tags": ["is invalid", "is too long"]}}"#,
+        );
+
+        let ApiError::Validation { ref envelope, .. } = error else {
+            panic!("{error:?}");
+        };
+        assert_eq!(
+            envelope.fields,
+            [
+                FieldError {
+                    field: "name".to_string(),
+                    message: "can't be blank".to_string()
+                },
+                FieldError {
+                    field: "tags".to_string(),
+                    message: "is invalid".to_string()
+                },
+                FieldError {
+                    field: "tags".to_string(),
+                    message: "is too long".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "The request was rejected (422 Unprocessable Entity): name can't be blank, tags is invalid, tags is too long"
+        );
+        assert_eq!(error.exit_code(), 65);
+    }
+
+    #[test]
+    fn nested_fields_are_named_by_their_path() {
+        let envelope = ErrorEnvelope::parse(
+            r#"{"errors": {"metadata": {"serial": ["is invalid"]}, "targets": [{}, {"prn": ["does not exist"]}]}}"#,
+        )
+        .unwrap();
+
+        let fields: Vec<_> = envelope.fields.iter().map(|error| &error.field).collect();
+        assert_eq!(fields, ["metadata.serial", "targets[1].prn"]);
+    }
+
+    #[test]
+    fn envelopes_keep_the_code_and_message() {
+        let envelope = ErrorEnvelope::parse(
+            r#"{"code": "release_locked", "message": "The release is locked", "errors": {}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(envelope.code.as_deref(), Some("release_locked"));
+        assert_eq!(
+            envelope.summary(StatusCode::CONFLICT),
+            "The release is locked (409 Conflict, release_locked)"
+        );
+
+        let envelope = ErrorEnvelope::parse(r#"{"errors": {"detail": "Bad Request"}}"#).unwrap();
+        assert_eq!(envelope.message.as_deref(), Some("Bad Request"));
+        assert!(envelope.fields.is_empty());
+    }
+
+    #[test]
+    fn bodies_without_an_envelope_are_kept_as_is() {
+        assert_eq!(ErrorEnvelope::parse("not json"), None);
+        assert_eq!(ErrorEnvelope::parse(r#"{"data": []}"#), None);
+
+        assert!(matches!(
+            classify(400, "not json"),
+            ApiError::Rejected { status, ref body }
+                if status == StatusCode::BAD_REQUEST && body == "not json"
+        ));
+        assert_eq!(classify(400, "not json").exit_code(), 65);
+    }
+
+    #[test]
+    fn rate_limits_keep_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+
+        let error = ApiError::from_response(StatusCode::TOO_MANY_REQUESTS, &headers, "");
+
+        assert!(matches!(
+            error,
+            ApiError::RateLimited { retry_after: Some(retry_after) }
+                if retry_after == Duration::from_secs(7)
+        ));
+        assert_eq!(error.to_string(), "Too many requests, retry in 7 seconds");
+        assert_eq!(error.exit_code(), 75);
+    }
+
+    #[test]
+    fn exit_codes_follow_the_kind_of_failure() {
+        assert_eq!(classify(401, "").exit_code(), 77);
+        assert_eq!(classify(404, "").exit_code(), 69);
+        assert_eq!(classify(409, "").exit_code(), 65);
+        assert_eq!(classify(500, "").exit_code(), 75);
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::client::Client;
+use super::Command;
+use crate::print_json;
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::{parse_duration, parse_time, PRNType, PRNValueParser, Prn, Style, StyledStr};
+use crate::Error;
+use crate::GlobalOptions;
+use crate::JsonSerializationSnafu;
+use clap::Parser;
+use serde_json::Value;
+use snafu::ResultExt;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// the longest pause between attempts to reach the API again
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+// the SDK has no events endpoint, this path and its `since`, `until`, `device_prn` and `type`
+// filters follow the conventions of the rest of the API and are not confirmed by its docs
+const EVENTS_PATH: &str = "/events";
+
+#[derive(Parser, Debug)]
+pub enum EventsCommand {
+    Follow(Command<FollowCommand>),
+    List(Command<ListCommand>),
+}
+
+impl EventsCommand {
+    pub async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        match self {
+            Self::Follow(cmd) => cmd.run(global_options).await,
+            Self::List(cmd) => cmd.run(global_options).await,
+        }
+    }
+}
+
+/// Print recent events, then keep printing new ones as they happen.
+#[derive(Parser, Debug)]
+pub struct FollowCommand {
+    /// How far back to print events from before following new ones, e.g. 30s, 15m, 2h or 1d.
+    #[arg(long, default_value = "5m", value_parser = parse_duration)]
+    backfill: Duration,
+
+    /// How often to poll for new events, e.g. 5s.
+    #[arg(long, default_value = "5s", value_parser = parse_duration)]
+    interval: Duration,
+
+    /// Only follow events about this device.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Device)
+    )]
+    device_prn: Option<Prn>,
+}
+
+impl Command<FollowCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let client = Client::new(&global_options)?;
+
+        let backfill = time::Duration::try_from(self.inner.backfill).ok();
+        let Some(mut since) =
+            backfill.and_then(|backfill| OffsetDateTime::now_utc().checked_sub(backfill))
+        else {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("--backfill is too far in the past:\r\n")
+                .warning(format!("\t{}s", self.inner.backfill.as_secs()));
+            return Err(error.data_err());
+        };
+
+        // events at the boundary of two polls are returned by both, so the ids of the last poll
+        // are enough to skip those printed already
+        let mut seen = HashSet::new();
+        let mut reconnect_delay = Duration::from_secs(1);
+
+        loop {
+            let mut query = vec![("since", since.format(&Rfc3339).unwrap())];
+
+            if let Some(device_prn) = &self.inner.device_prn {
+                query.push(("device_prn", device_prn.to_string()));
+            }
+
+            match client.get_with_query(EVENTS_PATH, &query).await {
+                Ok(response) => {
+                    reconnect_delay = Duration::from_secs(1);
+                    let mut polled = HashSet::new();
+
+                    for event in events(&response) {
+                        // events without an id can't be told apart, so they are always printed
+                        if let Some(id) = event_id(event) {
+                            let printed = seen.contains(&id);
+                            polled.insert(id);
+
+                            if printed {
+                                continue;
+                            }
+                        }
+
+                        if let Some(inserted_at) =
+                            event.get("inserted_at").and_then(Value::as_str).and_then(
+                                |inserted_at| OffsetDateTime::parse(inserted_at, &Rfc3339).ok(),
+                            )
+                        {
+                            since = since.max(inserted_at);
+                        }
+
+                        print_event(event)?;
+                    }
+
+                    seen = polled;
+
+                    tokio::time::sleep(self.inner.interval).await;
+                }
+                Err(error) => {
+                    let mut warning = StyledStr::new();
+                    warning.push_str(Some(Style::Warning), "warning: ".to_string());
+                    warning.push_str(
+                        None,
+                        format!(
+                            "Polling events failed, retrying in {}s: {error}",
+                            reconnect_delay.as_secs()
+                        ),
+                    );
+                    warning.print_err().unwrap();
+
+                    tokio::time::sleep(reconnect_delay).await;
+                    reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
+    }
+}
+
+/// Print the events in a time range.
+#[derive(Parser, Debug)]
+pub struct ListCommand {
+    /// Only list events from this time on, as RFC 3339 or a duration before now, e.g. 24h.
+    #[arg(long, value_parser = parse_time)]
+    since: Option<OffsetDateTime>,
+
+    /// Only list events before this time, as RFC 3339 or a duration before now, e.g. 1h.
+    #[arg(long, value_parser = parse_time)]
+    until: Option<OffsetDateTime>,
+
+    /// Only list events about this device.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Device)
+    )]
+    device_prn: Option<Prn>,
+
+    /// Only list events of this type.
+    #[arg(long = "type")]
+    event_type: Option<String>,
+}
+
+impl Command<ListCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        if let (Some(since), Some(until)) = (self.inner.since, self.inner.until) {
+            if since >= until {
+                let mut error = StyledStr::new();
+                error
+                    .error("error: ")
+                    .plain("--since must be before --until:\r\n")
+                    .warning(format!(
+                        "\t{} is not before {}",
+                        since.format(&Rfc3339).unwrap(),
+                        until.format(&Rfc3339).unwrap()
+                    ));
+                return Err(error.data_err());
+            }
+        }
+
+        let client = Client::new(&global_options)?;
+        let events = client
+            .get_with_query(EVENTS_PATH, &self.inner.query())
+            .await?;
+
+        print_json!(&events);
+
+        Ok(())
+    }
+}
+
+impl ListCommand {
+    fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+
+        if let Some(since) = self.since {
+            query.push(("since", since.format(&Rfc3339).unwrap()));
+        }
+
+        if let Some(until) = self.until {
+            query.push(("until", until.format(&Rfc3339).unwrap()));
+        }
+
+        if let Some(device_prn) = &self.device_prn {
+            query.push(("device_prn", device_prn.to_string()));
+        }
+
+        if let Some(event_type) = &self.event_type {
+            query.push(("type", event_type.clone()));
+        }
+
+        query
+    }
+}
+
+fn events(response: &Value) -> Vec<&Value> {
+    match response {
+        Value::Array(events) => events.iter().collect(),
+        response => output::envelope_items(response)
+            .map(|events| events.iter().collect())
+            .unwrap_or_default(),
+    }
+}
+
+fn event_id(event: &Value) -> Option<String> {
+    event
+        .get("id")
+        .or_else(|| event.get("prn"))
+        .map(|id| match id {
+            Value::String(id) => id.clone(),
+            id => id.to_string(),
+        })
+}
+
+fn print_event(event: &Value) -> Result<(), Error> {
+    match output::format() {
+        None | Some(OutputFormat::Human) | Some(OutputFormat::Table) => {
+            let field = |name: &str| {
+                event
+                    .get(name)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()
+            };
+
+            output::print_rendered(&format!(
+                "{} {} {}",
+                console::style(field("inserted_at")).dim(),
+                console::style(field("type")).green(),
+                field("resource_prn")
+            ))
+        }
+        // one event per line, so the stream can be piped as it arrives
+        _ => output::print_rendered(&serde_json::to_string(event).context(JsonSerializationSnafu)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    const DEVICE: &str =
+        "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef:device:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+    #[test]
+    fn filters_are_sent_as_query_parameters() {
+        let command = ListCommand {
+            since: Some(datetime!(2024-05-31 12:00 UTC)),
+            until: Some(datetime!(2024-06-01 6:30 +2)),
+            device_prn: Some(DEVICE.parse().unwrap()),
+            event_type: Some("device.connected".to_string()),
+        };
+
+        assert_eq!(
+            command.query(),
+            vec![
+                ("since", "2024-05-31T12:00:00Z".to_string()),
+                ("until", "2024-06-01T06:30:00+02:00".to_string()),
+                ("device_prn", DEVICE.to_string()),
+                ("type", "device.connected".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unset_filters_are_left_out() {
+        let command = ListCommand {
+            since: None,
+            until: None,
+            device_prn: None,
+            event_type: Some("device.connected".to_string()),
+        };
+
+        assert_eq!(
+            command.query(),
+            vec![("type", "device.connected".to_string())]
+        );
+    }
+}
@@ -1,14 +1,30 @@
-use super::Command;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+
+use super::client::{self, Client};
+use super::relay::{self, PlannedRequest};
+use super::{binaries, Command};
+use crate::print_created;
 use crate::print_json;
+use crate::utils::confirm::confirm_upload;
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::temp_file::TempFile;
+use crate::utils::{PRNType, PRNValueParser, Prn, Style, StyledStr};
 use crate::ApiSnafu;
 use crate::Error;
+use crate::FileSnafu;
 use crate::GlobalOptions;
+use crate::NonExistingPathSnafu;
 use clap::Parser;
+use flate2::read::DeflateDecoder;
+use peridio_sdk::api::binaries::CreateBinaryResponse;
 use peridio_sdk::api::firmwares::{
     CreateFirmwareParams, DeleteFirmwareParams, GetFirmwareParams, ListFirmwareParams,
 };
 use peridio_sdk::api::Api;
 use peridio_sdk::api::ApiOptions;
+use serde_json::{json, Value};
 use snafu::ResultExt;
 use uuid::Uuid;
 
@@ -18,6 +34,7 @@ pub enum FirmwaresCommand {
     Delete(Command<DeleteCommand>),
     Get(Command<GetCommand>),
     List(Command<ListCommand>),
+    Migrate(Command<MigrateCommand>),
 }
 
 impl FirmwaresCommand {
@@ -27,6 +44,7 @@ impl FirmwaresCommand {
             Self::Delete(cmd) => cmd.run(global_options).await,
             Self::Get(cmd) => cmd.run(global_options).await,
             Self::List(cmd) => cmd.run(global_options).await,
+            Self::Migrate(cmd) => cmd.run(global_options).await,
         }
     }
 }
@@ -46,10 +64,34 @@ pub struct CreateCommand {
     /// This is the amount of time the firmware can go without being associated to neither a deployment nor a device. After this time, the firmware will be deleted.
     #[arg(long)]
     ttl: Option<u32>,
+
+    /// Upload without asking to confirm the firmware's metadata.
+    #[arg(long, short = 'y')]
+    yes: bool,
 }
 
 impl Command<CreateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let archive = fs::read(&self.inner.firmware_path).context(NonExistingPathSnafu {
+            path: &self.inner.firmware_path,
+        })?;
+
+        let metadata = match read_metadata(&archive) {
+            Ok(metadata) => metadata,
+            Err(reason) => {
+                let mut error = StyledStr::new();
+                error
+                    .error("error: ")
+                    .plain("Not a firmware archive:\r\n")
+                    .warning(format!("\t{}: {reason}", self.inner.firmware_path));
+                return Err(error.data_err());
+            }
+        };
+
+        if !confirm_upload("firmware", &metadata.fields(), self.inner.yes) {
+            return Ok(());
+        }
+
         let params = CreateFirmwareParams {
             firmware_path: self.inner.firmware_path,
             organization_name: global_options.organization_name.unwrap(),
@@ -64,7 +106,7 @@ impl Command<CreateCommand> {
         });
 
         match api.firmwares().create(params).await.context(ApiSnafu)? {
-            Some(firmware) => print_json!(&firmware),
+            Some(firmware) => print_created!(&firmware),
             None => panic!(),
         }
 
@@ -167,3 +209,537 @@ impl Command<ListCommand> {
         Ok(())
     }
 }
+
+/// Move a firmware to the artifact model: create an artifact, an artifact version and a binary
+/// with the firmware's content, and print the PRNs of the new resources.
+///
+/// The artifact is named after the firmware's product and reused when it exists already, the
+/// artifact version is the firmware's version. With --dry-run the requests are printed instead.
+#[derive(Parser, Debug)]
+pub struct MigrateCommand {
+    /// The PRN of the firmware to migrate.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Firmware)
+    )]
+    prn: Prn,
+
+    /// The target of the binary, the firmware's architecture by default.
+    #[arg(long)]
+    target: Option<String>,
+
+    /// The name of a signing key pair in your Peridio CLI config to sign the binary with.
+    #[arg(long, short = 's')]
+    signing_key_pair: Option<String>,
+}
+
+/// What a migration creates a firmware's artifact, artifact version and binary from.
+#[derive(Debug, PartialEq)]
+struct Migration {
+    firmware_prn: String,
+    product: String,
+    version: String,
+    target: String,
+    // the artifact named after the product, if there is one already
+    artifact_prn: Option<String>,
+}
+
+impl Migration {
+    fn description(&self) -> String {
+        format!("Migrated from firmware {}", self.firmware_prn)
+    }
+
+    fn artifact(&self) -> Value {
+        json!({
+            "artifact": {
+                "name": self.product,
+                "description": self.description(),
+            }
+        })
+    }
+
+    fn artifact_version(&self, artifact_prn: &str) -> Value {
+        json!({
+            "artifact_version": {
+                "artifact_prn": artifact_prn,
+                "version": self.version,
+                "description": self.description(),
+            }
+        })
+    }
+
+    // the requests of the migration in order, the PRNs and content of resources it has yet to
+    // create or download are placeholders
+    fn plan(&self, base_url: &str) -> Vec<PlannedRequest> {
+        let mut requests = Vec::new();
+
+        if self.artifact_prn.is_none() {
+            requests.push(PlannedRequest {
+                method: "POST".to_string(),
+                url: format!("{base_url}/artifacts"),
+                body: self.artifact(),
+            });
+        }
+
+        requests.push(PlannedRequest {
+            method: "POST".to_string(),
+            url: format!("{base_url}/artifact_versions"),
+            body: self.artifact_version(
+                self.artifact_prn
+                    .as_deref()
+                    .unwrap_or("<the created artifact's PRN>"),
+            ),
+        });
+
+        requests.push(PlannedRequest {
+            method: "GET".to_string(),
+            url: format!("{base_url}/firmwares/{}/content", self.firmware_prn),
+            body: Value::Null,
+        });
+
+        requests.push(PlannedRequest {
+            method: "POST".to_string(),
+            url: format!("{base_url}/binaries"),
+            body: json!({
+                "binary": {
+                    "artifact_version_prn": "<the created artifact version's PRN>",
+                    "target": self.target,
+                    "description": self.description(),
+                    "hash": "<the SHA256 of the firmware's content>",
+                    "size": "<the size of the firmware's content>",
+                }
+            }),
+        });
+
+        requests
+    }
+}
+
+impl Command<MigrateCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let client = Client::new(&global_options)?;
+        let prn = &self.inner.prn;
+
+        let firmware = client.get(&format!("/firmwares/{prn}")).await?;
+        let field = |name: &str| {
+            firmware
+                .pointer(&format!("/firmware/{name}"))
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+        };
+
+        let (Some(product), Some(version)) = (field("product"), field("version")) else {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("The firmware has no product or version to create the artifact from:\r\n")
+                .warning(format!("\t{prn}"));
+            return Err(error.data_err());
+        };
+
+        let Some(target) = self.inner.target.clone().or_else(|| field("architecture")) else {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("The firmware has no architecture, pass --target for its binary:\r\n")
+                .warning(format!("\t{prn}"));
+            return Err(error.data_err());
+        };
+
+        let artifacts = client
+            .get_with_query(
+                "/artifacts",
+                &[
+                    ("search", format!("name:'{product}'")),
+                    ("limit", "1".to_string()),
+                ],
+            )
+            .await?;
+
+        let migration = Migration {
+            firmware_prn: prn.to_string(),
+            product,
+            version,
+            target,
+            artifact_prn: artifacts
+                .pointer("/artifacts/0/prn")
+                .and_then(Value::as_str)
+                .map(str::to_owned),
+        };
+
+        if global_options.dry_run {
+            return relay::print_plan(&migration.plan(&relay::api_base_url(&global_options)));
+        }
+
+        let artifact_prn = match &migration.artifact_prn {
+            Some(artifact_prn) => artifact_prn.clone(),
+            None => {
+                let artifact = client.post("/artifacts", &migration.artifact()).await?;
+                created_prn(&artifact, "artifact")?
+            }
+        };
+
+        let artifact_version = client
+            .post(
+                "/artifact_versions",
+                &migration.artifact_version(&artifact_prn),
+            )
+            .await?;
+        let artifact_version_prn = created_prn(&artifact_version, "artifact_version")?;
+
+        // the binary is uploaded from a file, removed again once it is dropped
+        let content_path = env::temp_dir().join(format!("peridio-firmware-{}", Uuid::new_v4()));
+        let mut content = TempFile::new(&content_path).context(FileSnafu)?;
+        let mut response = client
+            .get_stream(&format!("/firmwares/{prn}/content"))
+            .await?;
+
+        while let Some(chunk) = response.chunk().await.map_err(client::network)? {
+            content.file().write_all(&chunk).context(FileSnafu)?;
+        }
+        content.file().flush().context(FileSnafu)?;
+
+        let mut create = binaries::CreateCommand::for_content(
+            artifact_version_prn.parse().unwrap(),
+            migration.target.clone(),
+            content.path().to_string_lossy().into_owned(),
+            Some(migration.description()),
+            self.inner.signing_key_pair,
+        );
+
+        let binary_prn = match create.run(global_options).await? {
+            Some(CreateBinaryResponse { binary }) => binary.prn,
+            None => panic!(),
+        };
+
+        let migrated = json!({
+            "firmware_prn": migration.firmware_prn,
+            "artifact_prn": artifact_prn,
+            "artifact_version_prn": artifact_version_prn,
+            "binary_prn": binary_prn,
+        });
+
+        match output::format() {
+            None | Some(OutputFormat::Human) => render_migrated(&migrated).print_out().unwrap(),
+            _ => print_json!(&migrated),
+        }
+
+        Ok(())
+    }
+}
+
+// the PRN of the `resource` the API created, which commands take PRNs as
+fn created_prn(response: &Value, resource: &str) -> Result<String, Error> {
+    match response
+        .pointer(&format!("/{resource}/prn"))
+        .and_then(Value::as_str)
+        .filter(|prn| prn.parse::<Prn>().is_ok())
+    {
+        Some(prn) => Ok(prn.to_string()),
+        None => {
+            let mut error = StyledStr::new();
+            error.error("error: ").plain(format!(
+                "The API returned no PRN for the created {resource}"
+            ));
+            Err(error.data_err())
+        }
+    }
+}
+
+fn render_migrated(migrated: &Value) -> StyledStr {
+    let mut rendered = StyledStr::new();
+
+    for (index, (label, key)) in [
+        ("firmware", "firmware_prn"),
+        ("artifact", "artifact_prn"),
+        ("artifact version", "artifact_version_prn"),
+        ("binary", "binary_prn"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if index > 0 {
+            rendered.plain("\r\n");
+        }
+
+        rendered.plain(format!("{label}: "));
+        rendered.push_str(
+            Some(Style::Hint),
+            migrated[key].as_str().unwrap_or("-").to_string(),
+        );
+    }
+
+    rendered
+}
+
+/// The `meta-*` fields fwup embeds in the `meta.conf` of a firmware archive.
+#[derive(Debug, Default, PartialEq)]
+struct FirmwareMetadata {
+    product: Option<String>,
+    version: Option<String>,
+    platform: Option<String>,
+    architecture: Option<String>,
+}
+
+impl FirmwareMetadata {
+    fn fields(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("product", &self.product),
+            ("version", &self.version),
+            ("platform", &self.platform),
+            ("architecture", &self.architecture),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name, value.as_deref().unwrap_or("-")))
+        .collect()
+    }
+}
+
+// zip record signatures and the fixed sizes their variable length fields follow
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+const END_OF_CENTRAL_DIRECTORY_LENGTH: usize = 22;
+const CENTRAL_DIRECTORY_HEADER_LENGTH: usize = 46;
+const LOCAL_FILE_HEADER_LENGTH: usize = 30;
+
+/// Parse the metadata out of a `.fw` file, which is a zip archive holding a `meta.conf`.
+///
+/// The entry is located through the central directory since fwup streams its archives, so
+/// the sizes in the local headers may be zero.
+fn read_metadata(archive: &[u8]) -> Result<FirmwareMetadata, String> {
+    let meta_conf = read_entry(archive, "meta.conf")?;
+    let meta_conf = String::from_utf8(meta_conf).map_err(|_| "meta.conf is not UTF-8")?;
+
+    Ok(parse_meta_conf(&meta_conf))
+}
+
+fn read_entry(archive: &[u8], name: &str) -> Result<Vec<u8>, String> {
+    let not_zip = || "not a zip archive".to_string();
+    let truncated = || "the archive is truncated".to_string();
+
+    // the end of central directory record may be followed by a comment of up to 64 KiB
+    let end = (0..=archive
+        .len()
+        .checked_sub(END_OF_CENTRAL_DIRECTORY_LENGTH)
+        .ok_or_else(not_zip)?)
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|&offset| u32_at(archive, offset) == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(not_zip)?;
+
+    let entries = u16_at(archive, end + 10).ok_or_else(truncated)?;
+    let mut offset = u32_at(archive, end + 16).ok_or_else(truncated)? as usize;
+
+    for _ in 0..entries {
+        if u32_at(archive, offset) != Some(CENTRAL_DIRECTORY_HEADER) {
+            return Err(truncated());
+        }
+
+        let field = |at| {
+            u16_at(archive, offset + at)
+                .map(usize::from)
+                .ok_or_else(truncated)
+        };
+        let method = field(10)?;
+        let compressed_size = u32_at(archive, offset + 20).ok_or_else(truncated)? as usize;
+        let name_length = field(28)?;
+        let extra_length = field(30)?;
+        let comment_length = field(32)?;
+        let local_offset = u32_at(archive, offset + 42).ok_or_else(truncated)? as usize;
+
+        let entry_name = archive
+            .get(offset + CENTRAL_DIRECTORY_HEADER_LENGTH..)
+            .and_then(|rest| rest.get(..name_length))
+            .ok_or_else(truncated)?;
+
+        if entry_name == name.as_bytes() {
+            if u32_at(archive, local_offset) != Some(LOCAL_FILE_HEADER) {
+                return Err(truncated());
+            }
+
+            let local_name_length = u16_at(archive, local_offset + 26).ok_or_else(truncated)?;
+            let local_extra_length = u16_at(archive, local_offset + 28).ok_or_else(truncated)?;
+            let start = local_offset
+                + LOCAL_FILE_HEADER_LENGTH
+                + usize::from(local_name_length)
+                + usize::from(local_extra_length);
+            let data = archive
+                .get(start..)
+                .and_then(|rest| rest.get(..compressed_size))
+                .ok_or_else(truncated)?;
+
+            return match method {
+                0 => Ok(data.to_vec()),
+                8 => {
+                    let mut content = Vec::new();
+                    DeflateDecoder::new(data)
+                        .read_to_end(&mut content)
+                        .map_err(|_| format!("{name} is corrupt"))?;
+                    Ok(content)
+                }
+                method => Err(format!(
+                    "{name} uses unsupported compression method {method}"
+                )),
+            };
+        }
+
+        offset += CENTRAL_DIRECTORY_HEADER_LENGTH + name_length + extra_length + comment_length;
+    }
+
+    Err(format!("no {name} in the archive"))
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    let bytes = bytes.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+// meta.conf lines look like `meta-product = "Example"`, anything else is skipped
+fn parse_meta_conf(meta_conf: &str) -> FirmwareMetadata {
+    let mut metadata = FirmwareMetadata::default();
+
+    for line in meta_conf.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value)
+            .replace("\\\"", "\"");
+
+        let field = match key.trim() {
+            "meta-product" => &mut metadata.product,
+            "meta-version" => &mut metadata.version,
+            "meta-platform" => &mut metadata.platform,
+            "meta-architecture" => &mut metadata.architecture,
+            _ => continue,
+        };
+
+        *field = Some(value);
+    }
+
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(artifact_prn: Option<&str>) -> Migration {
+        Migration {
+            firmware_prn: "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef:firmware:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1".to_string(),
+            product: "gateway".to_string(),
+            version: "1.2.0".to_string(),
+            target: "arm64-v8".to_string(),
+            artifact_prn: artifact_prn.map(str::to_owned),
+        }
+    }
+
+    fn requests(plan: &[PlannedRequest]) -> Vec<String> {
+        plan.iter()
+            .map(|request| format!("{} {}", request.method, request.url))
+            .collect()
+    }
+
+    #[test]
+    fn migration_plans_the_artifact_version_and_binary() {
+        let migration = migration(None);
+        let plan = migration.plan("https://api.peridio.com");
+
+        assert_eq!(
+            requests(&plan),
+            [
+                "POST https://api.peridio.com/artifacts".to_string(),
+                "POST https://api.peridio.com/artifact_versions".to_string(),
+                format!(
+                    "GET https://api.peridio.com/firmwares/{}/content",
+                    migration.firmware_prn
+                ),
+                "POST https://api.peridio.com/binaries".to_string(),
+            ]
+        );
+        assert_eq!(plan[0].body["artifact"]["name"], "gateway");
+        assert_eq!(plan[1].body["artifact_version"]["version"], "1.2.0");
+        assert_eq!(plan[3].body["binary"]["target"], "arm64-v8");
+    }
+
+    #[test]
+    fn migration_reuses_an_existing_artifact() {
+        let artifact_prn = "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef:artifact:7c2e9f10-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+        let plan = migration(Some(artifact_prn)).plan("https://api.peridio.com");
+
+        assert_eq!(plan.len(), 3);
+        assert_eq!(plan[0].url, "https://api.peridio.com/artifact_versions");
+        assert_eq!(
+            plan[0].body["artifact_version"]["artifact_prn"],
+            artifact_prn
+        );
+    }
+
+    // a fwup archive with a deflated meta.conf and a stored rootfs.img
+    const FIXTURE: &[u8] = include_bytes!("../../tests/fixtures/firmware.fw");
+
+    #[test]
+    fn metadata_is_read_from_meta_conf() {
+        assert_eq!(
+            read_metadata(FIXTURE).unwrap(),
+            FirmwareMetadata {
+                product: Some("Example \"Fleet\"".to_string()),
+                version: Some("1.2.3".to_string()),
+                platform: Some("rpi4".to_string()),
+                architecture: Some("arm".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn missing_fields_are_none() {
+        let metadata = parse_meta_conf("meta-product=\"Only\"\nfile-resource rootfs.img {\n}\n");
+
+        assert_eq!(metadata.product.as_deref(), Some("Only"));
+        assert_eq!(metadata.version, None);
+        assert_eq!(metadata.fields()[1], ("version", "-"));
+    }
+
+    #[test]
+    fn non_archives_are_rejected() {
+        assert_eq!(read_metadata(b"").unwrap_err(), "not a zip archive");
+        assert_eq!(
+            read_metadata(b"#!/bin/sh\necho not firmware\n").unwrap_err(),
+            "not a zip archive"
+        );
+    }
+
+    #[test]
+    fn archives_without_meta_conf_are_rejected() {
+        // an empty zip archive is only its end of central directory record
+        let mut empty = END_OF_CENTRAL_DIRECTORY.to_le_bytes().to_vec();
+        empty.resize(END_OF_CENTRAL_DIRECTORY_LENGTH, 0);
+
+        assert_eq!(
+            read_metadata(&empty).unwrap_err(),
+            "no meta.conf in the archive"
+        );
+    }
+
+    #[test]
+    fn truncated_archives_are_rejected() {
+        let end = FIXTURE.len() - END_OF_CENTRAL_DIRECTORY_LENGTH;
+        let mut truncated = FIXTURE[..100].to_vec();
+        truncated.extend_from_slice(&FIXTURE[end..]);
+
+        assert_eq!(
+            read_metadata(&truncated).unwrap_err(),
+            "the archive is truncated"
+        );
+    }
+}
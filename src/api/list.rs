@@ -1,4 +1,14 @@
+use std::cmp::Ordering;
+use std::future::Future;
+
 use clap::Args;
+use serde::Serialize;
+use serde_json::{json, Value};
+use snafu::ResultExt;
+
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::{glob, table};
+use crate::{print_json, Error, JsonSerializationSnafu};
 
 #[derive(Args, Debug)]
 pub struct ListArgs {
@@ -14,4 +24,346 @@ pub struct ListArgs {
     /// A cursor for pagination across multiple pages of results. Don't include this parameter on the first call. Use the next_page value returned in a previous response (if not null) to request subsequent results.
     #[arg(long)]
     pub page: Option<String>,
+    /// Follow next_page until every page was fetched and print the items of all pages as a single array.
+    #[arg(long, default_value = "false")]
+    pub all: bool,
+    /// Stop fetching pages once this many items were collected with --all.
+    #[arg(long, requires = "all")]
+    pub max_items: Option<usize>,
+    /// Only print items whose PRN matches this shell-style pattern, where * matches any run of characters and ? a single one. The API doesn't filter by it: items are filtered client-side after each page was fetched, so a page can hold fewer items than --limit, and --max-items counts items before filtering.
+    #[arg(long)]
+    pub prn_glob: Option<String>,
+    /// Sort the items of all pages by a field, as FIELD, FIELD:asc or FIELD:desc where FIELD is a dotted path such as firmware_metadata.version. Sorting happens client-side once every page was fetched, items without the field come last.
+    #[arg(long, requires = "all", value_parser = parse_sort)]
+    pub sort: Option<Sort>,
+    /// Only print how many items there are, of the page or with --all of every page, after --prn-glob. JSON and YAML output print {"count": N}.
+    #[arg(long, default_value = "false")]
+    pub count: bool,
+}
+
+/// A `--sort` value, the dotted path of the field to sort by and the direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sort {
+    path: String,
+    descending: bool,
+}
+
+impl Sort {
+    /// Sort `items` by the field, keeping the order of items that compare equal.
+    fn apply(&self, items: &mut [Value]) {
+        items.sort_by(|a, b| match (field(a, &self.path), field(b, &self.path)) {
+            (Some(a), Some(b)) if self.descending => compare(b, a),
+            (Some(a), Some(b)) => compare(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+    }
+}
+
+pub fn parse_sort(value: &str) -> Result<Sort, String> {
+    let (path, descending) = match value.rsplit_once(':') {
+        Some((path, "asc")) => (path, false),
+        Some((path, "desc")) => (path, true),
+        Some((_, direction)) => return Err(format!("expected asc or desc, got '{direction}'")),
+        None => (value, false),
+    };
+
+    if path.is_empty() {
+        return Err("expected FIELD, FIELD:asc or FIELD:desc".to_string());
+    }
+
+    Ok(Sort {
+        path: path.to_string(),
+        descending,
+    })
+}
+
+// the value at `path`, with null as missing
+fn field<'a>(item: &'a Value, path: &str) -> Option<&'a Value> {
+    table::lookup(item, path).filter(|value| !value.is_null())
+}
+
+// numbers compare as numbers and strings as strings, values of different types are ordered
+// numbers, strings, booleans, then arrays and objects
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Number(_) => 0,
+            Value::String(_) => 1,
+            Value::Bool(_) => 2,
+            _ => 3,
+        }
+    }
+
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+impl ListArgs {
+    /// Print the requested page, or with `--all` the items of every page from `--page` on.
+    ///
+    /// `list` fetches the page with the given cursor.
+    pub async fn print<T, F, Fut>(&self, mut list: F) -> Result<(), Error>
+    where
+        T: Serialize,
+        F: FnMut(Option<String>) -> Fut,
+        Fut: Future<Output = Result<Option<T>, Error>>,
+    {
+        if !self.all {
+            match list(self.page.clone()).await? {
+                Some(page) => {
+                    let mut page = serde_json::to_value(page).context(JsonSerializationSnafu)?;
+
+                    if let Value::Object(fields) = &mut page {
+                        for field in fields.values_mut() {
+                            if let Value::Array(items) = field {
+                                self.retain_matching(items);
+                            }
+                        }
+                    }
+
+                    if self.count {
+                        return print_count(output::envelope_items(&page).map_or(0, Vec::len));
+                    }
+
+                    print_json!(&page)
+                }
+                None => panic!(),
+            }
+
+            return Ok(());
+        }
+
+        let mut items = collect_pages(self.page.clone(), self.max_items, list).await?;
+        self.retain_matching(&mut items);
+
+        if self.count {
+            return print_count(items.len());
+        }
+
+        if let Some(sort) = &self.sort {
+            sort.apply(&mut items);
+        }
+
+        print_json!(&items);
+
+        Ok(())
+    }
+
+    // keep the items whose PRN matches `--prn-glob`
+    fn retain_matching(&self, items: &mut Vec<Value>) {
+        if let Some(pattern) = &self.prn_glob {
+            items.retain(|item| {
+                item.get("prn")
+                    .and_then(Value::as_str)
+                    .is_some_and(|prn| glob::matches(pattern, prn))
+            });
+        }
+    }
+}
+
+fn print_count(count: usize) -> Result<(), Error> {
+    match output::format() {
+        None | Some(OutputFormat::Human) => output::print_rendered(&count.to_string()),
+        _ => output::print_json(&json!({ "count": count })),
+    }
+}
+
+/// The items of every page, following next_page until it is null or `max_items` were collected.
+pub async fn collect_pages<T, F, Fut>(
+    mut page: Option<String>,
+    max_items: Option<usize>,
+    mut list: F,
+) -> Result<Vec<Value>, Error>
+where
+    T: Serialize,
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Option<T>, Error>>,
+{
+    let mut items = Vec::new();
+
+    loop {
+        let Some(response) = list(page.take()).await? else {
+            break;
+        };
+
+        let response = serde_json::to_value(response).context(JsonSerializationSnafu)?;
+
+        items.extend(
+            output::envelope_items(&response)
+                .cloned()
+                .unwrap_or_default(),
+        );
+
+        if max_items.is_some_and(|max_items| items.len() >= max_items) {
+            break;
+        }
+
+        match response.get("next_page") {
+            Some(Value::String(next_page)) => page = Some(next_page.clone()),
+            _ => break,
+        }
+    }
+
+    if let Some(max_items) = max_items {
+        items.truncate(max_items);
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // three pages of two items, the cursor of a page is its number
+    async fn page(cursor: Option<String>) -> Result<Option<Value>, Error> {
+        let number: u64 = cursor.map_or(1, |cursor| cursor.parse().unwrap());
+        let next_page = (number < 3).then(|| (number + 1).to_string());
+
+        Ok(Some(json!({
+            "devices": [number * 10 + 1, number * 10 + 2],
+            "next_page": next_page,
+        })))
+    }
+
+    #[tokio::test]
+    async fn every_page_is_collected() {
+        let items = collect_pages(None, None, page).await.unwrap();
+
+        assert_eq!(items, vec![11, 12, 21, 22, 31, 32]);
+    }
+
+    #[tokio::test]
+    async fn collecting_starts_at_the_given_page() {
+        let items = collect_pages(Some("2".to_string()), None, page)
+            .await
+            .unwrap();
+
+        assert_eq!(items, vec![21, 22, 31, 32]);
+    }
+
+    fn sorted(sort: &str, mut items: Vec<Value>) -> Vec<Value> {
+        parse_sort(sort).unwrap().apply(&mut items);
+        items
+    }
+
+    #[test]
+    fn sorting_is_ascending_by_default() {
+        let items = vec![json!({ "n": 10 }), json!({ "n": 9 }), json!({ "n": 2.5 })];
+
+        assert_eq!(
+            sorted("n", items.clone()),
+            vec![json!({ "n": 2.5 }), json!({ "n": 9 }), json!({ "n": 10 })]
+        );
+        assert_eq!(sorted("n:asc", items.clone()), sorted("n", items));
+    }
+
+    #[test]
+    fn sorting_descending_is_stable() {
+        let items = vec![
+            json!({ "name": "a", "id": 1 }),
+            json!({ "name": "b", "id": 2 }),
+            json!({ "name": "a", "id": 3 }),
+        ];
+
+        assert_eq!(
+            sorted("name:desc", items),
+            vec![
+                json!({ "name": "b", "id": 2 }),
+                json!({ "name": "a", "id": 1 }),
+                json!({ "name": "a", "id": 3 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn sorting_follows_dotted_paths() {
+        let items = vec![
+            json!({ "firmware": { "version": "2.0.0" } }),
+            json!({ "firmware": { "version": "1.0.0" } }),
+        ];
+
+        assert_eq!(
+            sorted("firmware.version", items),
+            vec![
+                json!({ "firmware": { "version": "1.0.0" } }),
+                json!({ "firmware": { "version": "2.0.0" } }),
+            ]
+        );
+    }
+
+    #[test]
+    fn sorting_mixed_types_groups_them_and_puts_missing_fields_last() {
+        let items = vec![
+            json!({ "v": "b" }),
+            json!({}),
+            json!({ "v": true }),
+            json!({ "v": 2 }),
+            json!({ "v": null }),
+            json!({ "v": "a" }),
+            json!({ "v": 1 }),
+        ];
+
+        assert_eq!(
+            sorted("v", items.clone()),
+            vec![
+                json!({ "v": 1 }),
+                json!({ "v": 2 }),
+                json!({ "v": "a" }),
+                json!({ "v": "b" }),
+                json!({ "v": true }),
+                json!({}),
+                json!({ "v": null }),
+            ]
+        );
+        assert_eq!(
+            sorted("v:desc", items),
+            vec![
+                json!({ "v": true }),
+                json!({ "v": "b" }),
+                json!({ "v": "a" }),
+                json!({ "v": 2 }),
+                json!({ "v": 1 }),
+                json!({}),
+                json!({ "v": null }),
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_values_are_parsed() {
+        assert_eq!(
+            parse_sort("a.b:desc"),
+            Ok(Sort {
+                path: "a.b".to_string(),
+                descending: true
+            })
+        );
+        assert!(parse_sort("name:up").is_err());
+        assert!(parse_sort(":asc").is_err());
+    }
+
+    #[tokio::test]
+    async fn max_items_stops_fetching() {
+        let mut fetched = 0;
+
+        let items = collect_pages(None, Some(3), |cursor| {
+            fetched += 1;
+            page(cursor)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(items, vec![11, 12, 21]);
+        assert_eq!(fetched, 2);
+    }
 }
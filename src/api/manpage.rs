@@ -0,0 +1,51 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use clap::{CommandFactory, Parser};
+use snafu::ResultExt;
+
+use crate::{Error, FileSnafu};
+
+/// Write a man page for the CLI and one for each of its subcommands, for packagers.
+#[derive(Parser, Debug)]
+pub struct GenerateManpageCommand {
+    /// The directory to write the man pages to, created if missing.
+    #[arg(long, default_value = ".")]
+    out_dir: PathBuf,
+}
+
+impl GenerateManpageCommand {
+    pub fn run(self) -> Result<(), Error> {
+        fs::create_dir_all(&self.out_dir).context(FileSnafu)?;
+
+        let mut command = crate::Program::command();
+        // propagates the global options to the subcommands, so their pages list them too
+        command.build();
+
+        write_pages(&command, &self.out_dir)
+    }
+}
+
+// the page of `command`, named like `peridio-devices-list.1`, and those of its subcommands
+fn write_pages(command: &clap::Command, out_dir: &Path) -> Result<(), Error> {
+    let name = command.get_name().to_string();
+    let mut file = File::create(out_dir.join(format!("{name}.1"))).context(FileSnafu)?;
+
+    clap_mangen::Man::new(command.clone())
+        .render(&mut file)
+        .context(FileSnafu)?;
+
+    for subcommand in command.get_subcommands() {
+        if subcommand.is_hide_set() || subcommand.get_name() == "help" {
+            continue;
+        }
+
+        let subcommand = subcommand
+            .clone()
+            .name(format!("{name}-{}", subcommand.get_name()));
+
+        write_pages(&subcommand, out_dir)?;
+    }
+
+    Ok(())
+}
@@ -1,31 +1,46 @@
 mod artifact_versions;
 mod artifacts;
+mod audit_logs;
 mod binaries;
 mod binary_parts;
 mod binary_signatures;
 mod bundles;
 mod ca_certificates;
+mod cache;
+mod client;
 mod cohorts;
+mod completions;
 mod config;
 mod deployments;
 mod device_certificates;
 mod devices;
+mod error;
+mod events;
 mod firmwares;
 mod list;
+mod manpage;
 mod organization;
+mod prn;
 mod products;
 mod products_v2;
+mod relay;
 mod releases;
+mod resolve;
 mod signing_keys;
 mod tunnels;
 mod upgrade;
 mod users;
+mod version;
 mod webhooks;
+mod whoami;
 mod x509;
-use crate::utils::Style;
-use crate::utils::StyledStr;
+use crate::utils::prompt::{self, Prompter};
+use crate::utils::{Prn, StyledStr};
 use crate::GlobalOptions;
 use clap::Parser;
+pub(crate) use error::ApiError;
+pub(crate) use relay::Relay;
+use snafu::ResultExt;
 
 #[derive(Parser, Debug)]
 pub struct Command<T>
@@ -46,12 +61,24 @@ pub enum CliCommands {
     /// Upgrade the CLI
     #[command()]
     Upgrade(upgrade::UpgradeCommand),
+    /// Print a shell completion script
+    #[command()]
+    Completions(completions::CompletionsCommand),
+    /// Print the version and build metadata of the CLI
+    #[command()]
+    Version(version::VersionCommand),
     /// Manage the CLI's config
     #[command(subcommand)]
     Config(config::ConfigCommand),
     /// Create X.509 certificates and private keys
     #[command(subcommand)]
     X509(x509::X509Command),
+    /// Decode PRNs without requesting the API
+    #[command(subcommand)]
+    Prn(prn::PrnCommand),
+    /// Write man pages for the CLI
+    #[command(hide = true)]
+    GenerateManpage(manpage::GenerateManpageCommand),
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -61,6 +88,8 @@ pub enum ApiCommand {
     #[command(subcommand)]
     ArtifactVersions(artifact_versions::ArtifactVersionsCommand),
     #[command(subcommand)]
+    AuditLogs(audit_logs::AuditLogsCommand),
+    #[command(subcommand)]
     Bundles(bundles::BundlesCommand),
     #[command(subcommand)]
     Binaries(binaries::BinariesCommand),
@@ -79,6 +108,8 @@ pub enum ApiCommand {
     #[command(subcommand)]
     DeviceCertificates(device_certificates::DeviceCertificatesCommand),
     #[command(subcommand)]
+    Events(events::EventsCommand),
+    #[command(subcommand)]
     Firmwares(firmwares::FirmwaresCommand),
     #[command(subcommand)]
     Organizations(organization::OrganizationCommand),
@@ -94,79 +125,253 @@ pub enum ApiCommand {
     Tunnels(tunnels::TunnelsCommand),
     #[command(subcommand)]
     Webhooks(webhooks::WebhooksCommand),
+    /// Show the user and organization the API key resolves to
+    #[command()]
+    Whoami(whoami::WhoamiCommand),
+}
+
+impl ApiCommand {
+    // whether the command's endpoints are nested under the organization's name, which there is
+    // no documented way to look up by the organization's PRN
+    fn addresses_organization_names(&self) -> bool {
+        matches!(
+            self,
+            ApiCommand::CaCertificates(_)
+                | ApiCommand::Deployments(_)
+                | ApiCommand::Devices(_)
+                | ApiCommand::DeviceCertificates(_)
+                | ApiCommand::Firmwares(_)
+                | ApiCommand::Organizations(_)
+                | ApiCommand::Products(_)
+                | ApiCommand::Whoami(_)
+        )
+    }
+}
+
+// a command addressing the organization by name was only given its PRN
+fn organization_name_required(prn: &Prn) -> crate::Error {
+    let mut error = StyledStr::new();
+    error
+        .error("error: ")
+        .plain("The command addresses the organization by name, not by its PRN:\r\n")
+        .warning(format!("\t{prn}\r\n"))
+        .plain("\r\nPass --organization-name instead");
+    error.data_err()
 }
 
 impl CliCommands {
-    pub(crate) async fn run(self, global_options: GlobalOptions) -> Result<(), crate::Error> {
+    pub(crate) async fn run(self, mut global_options: GlobalOptions) -> Result<(), crate::Error> {
         match self {
             CliCommands::ApiCommand(api) => {
                 // require api key
                 let mut missing_arguments = Vec::new();
 
                 if global_options.api_key.is_none() {
-                    missing_arguments.push("--api-key".to_owned());
+                    missing_arguments.push(RequiredArgument::ApiKey);
+                }
+
+                // a PRN of the resource the command addresses names its organization too
+                if global_options.organization_name.is_none()
+                    && global_options.organization_prn.is_none()
+                {
+                    global_options.organization_prn = global_options
+                        .subject_prn
+                        .as_ref()
+                        .and_then(Prn::organization);
+                }
+
+                // require organization name, or a PRN to look it up by
+                if global_options.organization_name.is_none()
+                    && global_options.organization_prn.is_none()
+                {
+                    missing_arguments.push(RequiredArgument::OrganizationName);
                 }
 
-                // require organization name
-                if global_options.organization_name.is_none() {
-                    missing_arguments.push("--organization-name".to_owned());
+                if prompt::enabled(global_options.interactive) {
+                    missing_arguments =
+                        Self::prompt_missing_arguments(missing_arguments, &mut global_options)?;
                 }
 
-                Self::print_missing_arguments(missing_arguments);
-
-                match api {
-                    ApiCommand::Artifacts(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::ArtifactVersions(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Bundles(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Binaries(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::BinaryParts(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::BinarySignatures(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::CaCertificates(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Cohorts(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Deployments(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::DeviceCertificates(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Devices(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Firmwares(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Organizations(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Products(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::ProductsV2(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Releases(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::SigningKeys(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Tunnels(cmd) => cmd.run(global_options).await?,
-                    ApiCommand::Webhooks(cmd) => cmd.run(global_options).await?,
+                Self::check_missing_arguments(
+                    missing_arguments,
+                    global_options.profile.as_deref(),
+                )?;
+
+                if global_options.organization_name.is_none() && api.addresses_organization_names()
+                {
+                    if let Some(prn) = &global_options.organization_prn {
+                        return Err(organization_name_required(prn));
+                    }
+                }
+
+                // before the first request
+                if global_options.preflight {
+                    client::preflight(&global_options).await?;
+                }
+
+                let (global_options, relay) = relay::start(global_options).await?;
+
+                let result = match api {
+                    ApiCommand::Artifacts(cmd) => cmd.run(global_options).await,
+                    ApiCommand::ArtifactVersions(cmd) => cmd.run(global_options).await,
+                    ApiCommand::AuditLogs(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Bundles(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Binaries(cmd) => cmd.run(global_options).await,
+                    ApiCommand::BinaryParts(cmd) => cmd.run(global_options).await,
+                    ApiCommand::BinarySignatures(cmd) => cmd.run(global_options).await,
+                    ApiCommand::CaCertificates(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Cohorts(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Deployments(cmd) => cmd.run(global_options).await,
+                    ApiCommand::DeviceCertificates(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Devices(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Events(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Firmwares(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Organizations(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Products(cmd) => cmd.run(global_options).await,
+                    ApiCommand::ProductsV2(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Releases(cmd) => cmd.run(global_options).await,
+                    ApiCommand::SigningKeys(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Tunnels(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Webhooks(cmd) => cmd.run(global_options).await,
+                    ApiCommand::Whoami(cmd) => cmd.run(global_options).await,
+                };
+
+                // the SDK does not tell how its requests failed, the relay does
+                match result.map_err(|error| relay.failure(error)) {
+                    // the request that would change something was printed instead
+                    Err(crate::Error::DryRun) => {}
+                    result => result?,
                 }
             }
-            CliCommands::Users(cmd) => cmd.run(global_options).await?,
+            CliCommands::Users(cmd) => {
+                if global_options.preflight {
+                    client::preflight(&global_options).await?;
+                }
+
+                let (global_options, relay) = relay::start(global_options).await?;
+
+                cmd.run(global_options)
+                    .await
+                    .map_err(|error| relay.failure(error))?
+            }
             CliCommands::Upgrade(cmd) => cmd.run().await?,
+            CliCommands::Completions(cmd) => cmd.run()?,
+            CliCommands::Version(cmd) => cmd.run()?,
             CliCommands::Config(cmd) => cmd.run(global_options).await?,
             CliCommands::X509(cmd) => cmd.run(global_options).await?,
+            CliCommands::Prn(cmd) => cmd.run()?,
+            CliCommands::GenerateManpage(cmd) => cmd.run()?,
         };
 
         Ok(())
     }
 
-    pub(crate) fn print_missing_arguments(missing_arguments: Vec<String>) {
+    // ask for the missing arguments on the terminal, returning those it could not read
+    fn prompt_missing_arguments(
+        missing_arguments: Vec<RequiredArgument>,
+        global_options: &mut GlobalOptions,
+    ) -> Result<Vec<RequiredArgument>, crate::Error> {
+        let mut prompter = prompt::TerminalPrompter;
+        let mut still_missing = Vec::new();
+
+        for missing_argument in missing_arguments {
+            let value = prompter
+                .ask(
+                    missing_argument.label(),
+                    missing_argument == RequiredArgument::ApiKey,
+                )
+                .context(crate::FileSnafu)?;
+
+            if value.is_empty() {
+                still_missing.push(missing_argument);
+                continue;
+            }
+
+            match missing_argument {
+                RequiredArgument::ApiKey => global_options.api_key = Some(value),
+                RequiredArgument::OrganizationName => {
+                    global_options.organization_name = Some(value)
+                }
+            }
+        }
+
+        Ok(still_missing)
+    }
+
+    pub(crate) fn check_missing_arguments(
+        missing_arguments: Vec<RequiredArgument>,
+        profile: Option<&str>,
+    ) -> Result<(), crate::Error> {
         if !missing_arguments.is_empty() {
             let mut error = StyledStr::new();
 
-            error.push_str(Some(Style::Error), "error: ".to_string());
-            error.push_str(
-                None,
-                "The following arguments are required:\r\n".to_string(),
-            );
+            error
+                .error("error: ")
+                .plain("The following arguments are required:\r\n");
             for missing_argument in missing_arguments.iter() {
-                error.push_str(Some(Style::Success), format!("\t{missing_argument}\r\n"));
+                error
+                    .success(format!("\t{}", missing_argument.flag()))
+                    .plain(format!(
+                        " or the {} environment variable",
+                        missing_argument.env()
+                    ));
+
+                if let Some(profile) = profile {
+                    error.plain(format!(
+                        " or {} in the {profile} profile",
+                        missing_argument.profile_key()
+                    ));
+                }
+
+                error.plain("\r\n");
             }
-            error.push_str(None, "\r\nThey must be supplied either:\r\n".to_string());
-            error.push_str(
-                None,
-                "\t- via the CLI config file and referenced by profile\r\n".to_string(),
-            );
-            error.push_str(
-                None,
-                "\t- directly to the top level command (not to subcommands)".to_string(),
-            );
-            error.print_data_err();
+            error
+                .plain("\r\nThey must be supplied either:\r\n")
+                .plain("\t- via the CLI config file and referenced by profile\r\n")
+                .plain("\t- via environment variables\r\n")
+                .plain("\t- directly to the top level command (not to subcommands)");
+            return Err(error.data_err());
+        }
+
+        Ok(())
+    }
+}
+
+/// A global option commands cannot run without, see `CliCommands::check_missing_arguments`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RequiredArgument {
+    ApiKey,
+    OrganizationName,
+}
+
+impl RequiredArgument {
+    fn flag(self) -> &'static str {
+        match self {
+            Self::ApiKey => "--api-key",
+            Self::OrganizationName => "--organization-name or --organization-prn",
+        }
+    }
+
+    // what `--interactive` asks for
+    fn label(self) -> &'static str {
+        match self {
+            Self::ApiKey => "api-key",
+            Self::OrganizationName => "organization-name",
+        }
+    }
+
+    fn env(self) -> &'static str {
+        match self {
+            Self::ApiKey => "PERIDIO_API_KEY",
+            Self::OrganizationName => "PERIDIO_ORGANIZATION_NAME",
+        }
+    }
+
+    // the key of the CLI config profile that supplies the argument
+    fn profile_key(self) -> &'static str {
+        match self {
+            Self::ApiKey => "api_key",
+            Self::OrganizationName => "organization_name",
         }
     }
 }
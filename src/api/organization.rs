@@ -1,5 +1,8 @@
+use super::client::{self, Client};
 use super::Command;
 use crate::print_json;
+use crate::utils::confirm::confirm_changes;
+use crate::utils::{Style, StyledStr};
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
@@ -10,10 +13,13 @@ use peridio_sdk::api::organization_users::{
 };
 use peridio_sdk::api::Api;
 use peridio_sdk::api::ApiOptions;
+use serde_json::{json, Map, Value};
 use snafu::ResultExt;
 
 #[derive(Parser, Debug)]
 pub enum OrganizationCommand {
+    Get(Command<GetCommand>),
+    Update(Command<UpdateCommand>),
     AddUser(Command<AddUserCommand>),
     RemoveUser(Command<RemoveUserCommand>),
     GetUser(Command<GetUserCommand>),
@@ -24,6 +30,8 @@ pub enum OrganizationCommand {
 impl OrganizationCommand {
     pub async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         match self {
+            Self::Get(cmd) => cmd.run(global_options).await,
+            Self::Update(cmd) => cmd.run(global_options).await,
             Self::AddUser(cmd) => cmd.run(global_options).await,
             Self::RemoveUser(cmd) => cmd.run(global_options).await,
             Self::GetUser(cmd) => cmd.run(global_options).await,
@@ -33,6 +41,133 @@ impl OrganizationCommand {
     }
 }
 
+#[derive(Parser, Debug)]
+pub struct GetCommand {
+    /// The name of the organization to get, defaults to --organization-name.
+    #[arg(long)]
+    organization: Option<String>,
+}
+
+impl Command<GetCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let organization_name = self
+            .inner
+            .organization
+            .unwrap_or_else(|| global_options.organization_name.clone().unwrap());
+
+        let client = Client::new(&global_options)?;
+
+        let organization = client
+            .get(&client::organization_path(&organization_name))
+            .await?;
+
+        print_json!(&organization);
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct UpdateCommand {
+    /// The name of the organization to update, defaults to --organization-name.
+    ///
+    /// Updating an organization other than --organization-name also requires
+    /// --cross-organization.
+    #[arg(long)]
+    organization: Option<String>,
+
+    /// Allow updating an organization other than --organization-name.
+    #[arg(long, default_value = "false")]
+    cross_organization: bool,
+
+    /// The new name of the organization.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Apply the changes without asking for confirmation.
+    #[arg(long, short = 'y', default_value = "false")]
+    yes: bool,
+}
+
+impl Command<UpdateCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let resolved_organization_name = global_options.organization_name.clone().unwrap();
+
+        let organization_name = self
+            .inner
+            .organization
+            .unwrap_or_else(|| resolved_organization_name.clone());
+
+        if organization_name != resolved_organization_name && !self.inner.cross_organization {
+            let mut error = StyledStr::new();
+            error.push_str(Some(Style::Error), "error: ".to_string());
+            error.push_str(
+                None,
+                "Refusing to update an organization other than the current one:\r\n".to_string(),
+            );
+            error.push_str(
+                Some(Style::Warning),
+                format!("\t{organization_name} (current: {resolved_organization_name})\r\n"),
+            );
+            error.push_str(
+                None,
+                "\r\nPass --cross-organization to update it anyway".to_string(),
+            );
+            return Err(error.data_err());
+        }
+
+        let client = Client::new(&global_options)?;
+        let path = client::organization_path(&organization_name);
+
+        let name = self.inner.name;
+        let yes = self.inner.yes;
+        let mut confirmed = false;
+        let mut unconfirmed = None;
+
+        let organization = client
+            .update(&path, !global_options.no_retry_conflict, |response| {
+                let current = response.get("data").unwrap_or(response);
+                let changes = organization_changes(&name);
+
+                // a conflict re-applies the same changes, there is no need to ask again
+                if !confirmed {
+                    match confirm_changes(current, &changes, yes) {
+                        Ok(true) => confirmed = true,
+                        Ok(false) => return None,
+                        Err(error) => {
+                            unconfirmed = Some(error);
+                            return None;
+                        }
+                    }
+                }
+
+                Some(json!({ "organization": changes }))
+            })
+            .await?;
+
+        if let Some(error) = unconfirmed {
+            return Err(error);
+        }
+
+        if let Some(organization) = organization {
+            print_json!(&organization);
+        }
+
+        Ok(())
+    }
+}
+
+// only the name, the CLI knows of no other organization setting the API takes
+fn organization_changes(name: &Option<String>) -> Map<String, Value> {
+    let mut changes = Map::new();
+
+    if let Some(name) = name {
+        changes.insert("name".to_string(), Value::String(name.clone()));
+    }
+
+    changes
+}
+
 #[derive(Parser, Debug)]
 pub struct AddUserCommand {
     /// The role the user has within the organization.
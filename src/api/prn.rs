@@ -0,0 +1,115 @@
+use clap::Parser;
+use serde_json::{json, Value};
+use snafu::ResultExt;
+
+use crate::print_json;
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::{Prn, Style, StyledStr};
+use crate::Error;
+
+#[derive(Parser, Debug)]
+pub enum PrnCommand {
+    Inspect(InspectCommand),
+}
+
+impl PrnCommand {
+    pub fn run(self) -> Result<(), Error> {
+        match self {
+            Self::Inspect(cmd) => cmd.run(),
+        }
+    }
+}
+
+/// Decode a PRN without requesting the API.
+#[derive(Parser, Debug)]
+pub struct InspectCommand {
+    /// The PRN to decode, e.g. prn:1:<organization uuid>:device:<uuid>.
+    prn: Prn,
+}
+
+impl InspectCommand {
+    fn run(self) -> Result<(), Error> {
+        let fields = fields(&self.prn);
+
+        match output::format() {
+            None | Some(OutputFormat::Human) => render(&fields).print_out().unwrap(),
+            _ => print_json!(&fields),
+        }
+
+        Ok(())
+    }
+}
+
+// the parts of the PRN, null where its shape has no such segment
+fn fields(prn: &Prn) -> Value {
+    json!({
+        "prn": prn,
+        "version": prn.version,
+        "organization_id": prn.org_id.map(|org_id| org_id.to_string()),
+        "resource_type": prn.resource_type.name(),
+        "resource_id": prn.resource_id.map(|resource_id| resource_id.to_string()),
+    })
+}
+
+fn render(fields: &Value) -> StyledStr {
+    let mut rendered = StyledStr::new();
+
+    for (index, (label, key)) in [
+        ("version", "version"),
+        ("organization", "organization_id"),
+        ("type", "resource_type"),
+        ("id", "resource_id"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if index > 0 {
+            rendered.plain("\r\n");
+        }
+
+        let value = match &fields[key] {
+            Value::Null => "-".to_string(),
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        rendered.plain(format!("{label}: "));
+        rendered.push_str(Some(Style::Hint), value);
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORGANIZATION_ID: &str = "be4d30b4-de6b-47cd-85ea-a75e23fd63ef";
+    const RESOURCE_ID: &str = "3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+    #[test]
+    fn every_shape_is_decoded() {
+        let organization: Prn = format!("prn:1:{ORGANIZATION_ID}").parse().unwrap();
+        assert_eq!(
+            fields(&organization),
+            json!({
+                "prn": format!("prn:1:{ORGANIZATION_ID}"),
+                "version": 1,
+                "organization_id": ORGANIZATION_ID,
+                "resource_type": "organization",
+                "resource_id": null,
+            })
+        );
+
+        let user: Prn = format!("prn:1:user:{RESOURCE_ID}").parse().unwrap();
+        assert_eq!(fields(&user)["organization_id"], Value::Null);
+        assert_eq!(fields(&user)["resource_type"], "user");
+        assert_eq!(fields(&user)["resource_id"], RESOURCE_ID);
+
+        let device: Prn = format!("prn:1:{ORGANIZATION_ID}:device:{RESOURCE_ID}")
+            .parse()
+            .unwrap();
+        assert_eq!(fields(&device)["organization_id"], ORGANIZATION_ID);
+        assert_eq!(fields(&device)["resource_type"], "device");
+        assert_eq!(fields(&device)["resource_id"], RESOURCE_ID);
+    }
+}
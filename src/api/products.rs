@@ -1,9 +1,15 @@
 use super::Command;
+use crate::print_created;
 use crate::print_json;
+use crate::utils::output::{self, OutputFormat};
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
+use crate::JsonSerializationSnafu;
 use clap::Parser;
+use peridio_sdk::api::deployments::ListDeploymentParams;
+use peridio_sdk::api::devices::ListDeviceParams;
+use peridio_sdk::api::firmwares::ListFirmwareParams;
 use peridio_sdk::api::product_users::{
     AddProductUserParams, GetProductUserParams, ListProductUserParams, RemoveProductUserParams,
     UpdateProductUserParams,
@@ -15,7 +21,9 @@ use peridio_sdk::api::products::{
 };
 use peridio_sdk::api::Api;
 use peridio_sdk::api::ApiOptions;
+use serde_json::{json, Value};
 use snafu::ResultExt;
+use std::collections::BTreeMap;
 
 #[derive(Parser, Debug)]
 pub enum ProductsCommand {
@@ -69,7 +77,7 @@ impl Command<CreateCommand> {
         });
 
         match api.products().create(params).await.context(ApiSnafu)? {
-            Some(product) => print_json!(&product),
+            Some(product) => print_created!(&product),
             None => panic!(),
         }
 
@@ -110,14 +118,15 @@ pub struct GetCommand {
     /// The name of the resource to get.
     #[arg(long)]
     product_name: String,
+
+    /// Summarize the product's devices, firmwares, and deployments instead of returning the product itself.
+    #[arg(long, default_value = "false")]
+    stats: bool,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = GetProductParams {
-            organization_name: global_options.organization_name.unwrap(),
-            product_name: self.inner.product_name,
-        };
+        let organization_name = global_options.organization_name.unwrap();
 
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
@@ -125,6 +134,23 @@ impl Command<GetCommand> {
             ca_bundle_path: global_options.ca_path,
         });
 
+        if self.inner.stats {
+            let stats =
+                Self::product_stats(&api, organization_name, self.inner.product_name).await?;
+
+            match output::format() {
+                None | Some(OutputFormat::Human) => Self::print_stats(&stats),
+                _ => print_json!(&stats),
+            }
+
+            return Ok(());
+        }
+
+        let params = GetProductParams {
+            organization_name,
+            product_name: self.inner.product_name,
+        };
+
         match api.products().get(params).await.context(ApiSnafu)? {
             Some(product) => print_json!(&product),
             None => panic!(),
@@ -132,6 +158,113 @@ impl Command<GetCommand> {
 
         Ok(())
     }
+
+    async fn product_stats(
+        api: &Api,
+        organization_name: String,
+        product_name: String,
+    ) -> Result<Value, Error> {
+        // the v1 list endpoints are not paginated, a single request per resource returns everything
+        let (devices, firmwares, deployments) = tokio::try_join!(
+            api.devices().list(ListDeviceParams {
+                organization_name: organization_name.clone(),
+                product_name: product_name.clone(),
+            }),
+            api.firmwares().list(ListFirmwareParams {
+                organization_name: organization_name.clone(),
+                product_name: product_name.clone(),
+            }),
+            api.deployments().list(ListDeploymentParams {
+                organization_name: organization_name.clone(),
+                product_name: product_name.clone(),
+            }),
+        )
+        .context(ApiSnafu)?;
+
+        let devices = data_items(serde_json::to_value(&devices).context(JsonSerializationSnafu)?);
+        let firmwares =
+            data_items(serde_json::to_value(&firmwares).context(JsonSerializationSnafu)?);
+        let deployments =
+            data_items(serde_json::to_value(&deployments).context(JsonSerializationSnafu)?);
+
+        let mut devices_by_status: BTreeMap<String, u64> = BTreeMap::new();
+        let mut firmware_distribution: BTreeMap<String, u64> = BTreeMap::new();
+
+        for device in devices.iter() {
+            let status = match (device.get("status"), device.get("healthy")) {
+                (Some(Value::String(status)), _) => status.to_owned(),
+                (_, Some(Value::Bool(true))) => "healthy".to_owned(),
+                (_, Some(Value::Bool(false))) => "quarantined".to_owned(),
+                _ => "unknown".to_owned(),
+            };
+            *devices_by_status.entry(status).or_default() += 1;
+
+            let version = match device.pointer("/firmware_metadata/version") {
+                Some(Value::String(version)) => version.to_owned(),
+                _ => "unknown".to_owned(),
+            };
+            *firmware_distribution.entry(version).or_default() += 1;
+        }
+
+        let active_deployments = deployments
+            .iter()
+            .filter(|deployment| deployment.get("is_active") == Some(&Value::Bool(true)))
+            .count();
+
+        Ok(json!({
+            "product_name": product_name,
+            "devices": {
+                "total": devices.len(),
+                "by_status": devices_by_status,
+                "by_firmware_version": firmware_distribution,
+            },
+            "firmwares": {
+                "total": firmwares.len(),
+            },
+            "deployments": {
+                "total": deployments.len(),
+                "active": active_deployments,
+            },
+        }))
+    }
+
+    fn print_stats(stats: &Value) {
+        let count = |pointer: &str| stats.pointer(pointer).and_then(Value::as_u64).unwrap_or(0);
+
+        println!(
+            "Product: {}",
+            stats["product_name"].as_str().unwrap_or_default()
+        );
+        println!("Devices: {}", count("/devices/total"));
+        if let Some(Value::Object(by_status)) = stats.pointer("/devices/by_status") {
+            for (status, total) in by_status {
+                println!("  {status}: {total}");
+            }
+        }
+        println!("Firmware versions on devices:");
+        if let Some(Value::Object(by_version)) = stats.pointer("/devices/by_firmware_version") {
+            for (version, total) in by_version {
+                println!("  {version}: {total}");
+            }
+        }
+        println!("Firmwares: {}", count("/firmwares/total"));
+        println!(
+            "Deployments: {} ({} active)",
+            count("/deployments/total"),
+            count("/deployments/active")
+        );
+    }
+}
+
+/// Extract the items of a v1 list response, which are wrapped in a `data` array.
+fn data_items(value: Value) -> Vec<Value> {
+    match value {
+        Value::Object(mut map) => match map.remove("data") {
+            Some(Value::Array(items)) => items,
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
 }
 
 #[derive(Parser, Debug)]
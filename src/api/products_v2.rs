@@ -1,8 +1,11 @@
+use super::resolve::prn_or_name;
 use super::Command;
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
@@ -50,7 +53,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Organization)
     )]
-    organization_prn: String,
+    organization_prn: Prn,
 }
 
 impl Command<CreateCommand> {
@@ -58,7 +61,7 @@ impl Command<CreateCommand> {
         let params = CreateProductV2Params {
             archived: self.inner.archived,
             name: self.inner.name,
-            organization_prn: self.inner.organization_prn,
+            organization_prn: self.inner.organization_prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -68,7 +71,7 @@ impl Command<CreateCommand> {
         });
 
         match api.products_v2().create(params).await.context(ApiSnafu)? {
-            Some(product) => print_json!(&product),
+            Some(product) => print_created!(&product),
             None => panic!(),
         }
 
@@ -84,25 +87,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListProductsV2Params {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
-
-        match api.products_v2().list(params).await.context(ApiSnafu)? {
-            Some(product) => print_json!(&product),
-            None => panic!(),
-        }
-
-        Ok(())
+        let api = &api;
+        let list_args = &self.inner.list_args;
+
+        list_args
+            .print(|page| {
+                let params = ListProductsV2Params {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
+
+                async move { api.products_v2().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -111,16 +115,27 @@ pub struct GetCommand {
     /// The PRN of the resource to get.
     #[arg(
         long,
-        value_parser = PRNValueParser::new(PRNType::Product)
+        value_parser = PRNValueParser::new(PRNType::Product),
+        required_unless_present = "product_name"
     )]
-    prn: String,
+    prn: Option<Prn>,
+
+    /// The name of the product to get, looked up among the organization's products.
+    #[arg(long, conflicts_with = "prn")]
+    product_name: Option<String>,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = GetProductV2Params {
-            prn: self.inner.prn,
-        };
+        let prn = prn_or_name(
+            &global_options,
+            PRNType::Product,
+            self.inner.prn,
+            self.inner.product_name,
+        )
+        .await?;
+
+        let params = GetProductV2Params { prn };
 
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
@@ -142,9 +157,13 @@ pub struct UpdateCommand {
     /// The PRN of the resource to update.
     #[arg(
         long,
-        value_parser = PRNValueParser::new(PRNType::Product)
+        value_parser = PRNValueParser::new(PRNType::Product),
+        required_unless_present = "product_name"
     )]
-    prn: String,
+    prn: Option<Prn>,
+    /// The name (currently) of the product to update, looked up among the organization's products.
+    #[arg(long, conflicts_with = "prn")]
+    product_name: Option<String>,
     /// The resource's name, meant to be displayable to users.
     #[arg(long)]
     name: Option<String>,
@@ -155,8 +174,16 @@ pub struct UpdateCommand {
 
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let prn = prn_or_name(
+            &global_options,
+            PRNType::Product,
+            self.inner.prn,
+            self.inner.product_name,
+        )
+        .await?;
+
         let params = UpdateProductV2Params {
-            prn: self.inner.prn,
+            prn,
             name: self.inner.name,
             archived: self.inner.archived,
         };
@@ -175,3 +202,14 @@ impl Command<UpdateCommand> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prn_or_product_name_is_required() {
+        assert!(GetCommand::try_parse_from(["get"]).is_err());
+        assert!(GetCommand::try_parse_from(["get", "--product-name", "gateway"]).is_ok());
+    }
+}
@@ -0,0 +1,552 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use snafu::ResultExt;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+use super::client::{self, Client, ResponseBody, DEFAULT_BASE_URL};
+use super::ApiError;
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::StyledStr;
+use crate::{Error, FileSnafu, GlobalOptions};
+
+// headers of the connection to the relay rather than of the request, the relay's client sets
+// its own
+const HOP_BY_HOP: [&str; 6] = [
+    "host",
+    "connection",
+    "content-length",
+    "transfer-encoding",
+    "keep-alive",
+    "accept-encoding",
+];
+
+// the commands whose requests the relay passes on, by the token that leads their paths. Those
+// of commands that finished are dropped once another one starts, see `start`
+static ROUTES: Mutex<Vec<Weak<Route>>> = Mutex::new(Vec::new());
+
+// the address the relay listens on, from the first command that needs it until the process
+// exits, so the commands of a `--prn-file` batch share it
+static ADDRESS: OnceCell<String> = OnceCell::const_new();
+
+/// A request that `--dry-run` stopped before it was sent.
+#[derive(Debug, PartialEq)]
+pub struct PlannedRequest {
+    pub method: String,
+    pub url: String,
+    pub body: Value,
+}
+
+/// The relay of a single command, which knows how the command's requests failed, see
+/// [`Relay::failure`]. The relay stops taking the command's requests once it is dropped.
+#[derive(Clone)]
+pub struct Relay(Arc<Route>);
+
+impl fmt::Debug for Relay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Relay").finish_non_exhaustive()
+    }
+}
+
+// where the relay sends the requests of a command on to
+struct Route {
+    client: Client,
+    base_url: String,
+    token: String,
+    dry_run: bool,
+    // whether `--dry-run` printed a request of the command instead of sending it
+    planned: AtomicBool,
+    // how the last request of the command failed, see `Relay::failure`
+    failure: Mutex<Option<Error>>,
+}
+
+impl Route {
+    fn record(&self, failure: Option<Error>) {
+        if let Ok(mut last) = self.failure.lock() {
+            *last = failure;
+        }
+    }
+}
+
+// the request line and headers of a request to the relay
+struct Head {
+    method: String,
+    target: String,
+    host: Option<String>,
+    content_length: usize,
+    chunked: bool,
+    headers: HeaderMap,
+}
+
+/// Route the requests of a command through a local relay that sends them on with [`Client`].
+///
+/// Both the SDK and [`Client`] send their requests to the returned options' base URL, so every
+/// request gets the timeouts, retries, rate limit, logging, headers and cache of [`Client`],
+/// such as `--idempotency-key`. With `--dry-run` only requests that read are passed on, so
+/// commands that look something up first still work. The first request that would change
+/// something is printed instead, and the command stops there with [`Error::DryRun`].
+///
+/// The relay adds the API key to what it sends on, so it only takes requests led by a random
+/// token in the path that the base URL carries, and only for its own address as the `Host`,
+/// which other local processes and web pages pointed at it don't know. The returned options hold
+/// the relay as well, for commands that run others.
+pub async fn start(mut global_options: GlobalOptions) -> Result<(GlobalOptions, Relay), Error> {
+    let address = ADDRESS.get_or_try_init(listen).await?;
+
+    let route = Arc::new(Route {
+        client: Client::new(&global_options)?,
+        base_url: api_base_url(&global_options),
+        token: Uuid::new_v4().simple().to_string(),
+        dry_run: global_options.dry_run,
+        planned: AtomicBool::new(false),
+        failure: Mutex::new(None),
+    });
+
+    if let Ok(mut routes) = ROUTES.lock() {
+        routes.retain(|route| route.strong_count() > 0);
+        routes.push(Arc::downgrade(&route));
+    }
+
+    global_options.api_base_url = Some(route.base_url.clone());
+    global_options.base_url = Some(format!("http://{address}/{}", route.token));
+    // the relay caches already, and its clients don't retry what it sends, see `Client::execute`
+    global_options.cache = false;
+
+    let relay = Relay(route);
+    global_options.relay = Some(relay.clone());
+
+    Ok((global_options, relay))
+}
+
+impl Relay {
+    /// The error a command failed with, or how the relay saw its last request fail when the
+    /// command only got as far as the relay, such as an SDK error, which tells neither the status
+    /// nor the request ID. A command stopped by `--dry-run` failed with [`Error::DryRun`].
+    pub fn failure(&self, error: Error) -> Error {
+        // whatever the command made of the request that was not sent
+        if self.0.planned.load(Ordering::SeqCst) {
+            return Error::DryRun;
+        }
+
+        let relayed = match &error {
+            Error::Api { .. } => true,
+            Error::Request { source, .. } => {
+                matches!(source, ApiError::Network(_))
+                    || source.status() == Some(StatusCode::BAD_GATEWAY)
+            }
+            _ => false,
+        };
+
+        let failure = self
+            .0
+            .failure
+            .lock()
+            .ok()
+            .and_then(|mut failure| failure.take());
+
+        match failure {
+            Some(failure) if relayed => failure,
+            _ => error,
+        }
+    }
+}
+
+/// Like [`Relay::failure`], for the relay the requests of `global_options` go through.
+pub fn failure(global_options: &GlobalOptions, error: Error) -> Error {
+    match &global_options.relay {
+        Some(relay) => relay.failure(error),
+        None => error,
+    }
+}
+
+// listen on a port of its own, returning the address
+async fn listen() -> Result<String, Error> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.context(FileSnafu)?;
+    let address = listener.local_addr().context(FileSnafu)?.to_string();
+    let host = address.clone();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let host = host.clone();
+
+            // concurrent requests, such as those of part uploads, are passed on side by side
+            tokio::spawn(async move { answer(stream, &host).await });
+        }
+    });
+
+    Ok(address)
+}
+
+// answer a single request, the connection is closed once it was answered
+async fn answer(stream: TcpStream, host: &str) {
+    let mut stream = BufReader::new(stream);
+
+    let head = match read_head(&mut stream).await {
+        Ok(head) => head,
+        Err(error) => {
+            tracing::debug!("unable to read a request to the relay: {error}");
+            return;
+        }
+    };
+
+    let Some((route, path)) = find_route(&head, host) else {
+        tracing::debug!(
+            "refused a request to the relay without a token: {} {}",
+            head.method,
+            head.target
+        );
+
+        let (status, headers, body) =
+            refused(StatusCode::FORBIDDEN, "not a request of a running command");
+        if let Err(error) = respond(stream.get_mut(), status, &headers, body).await {
+            tracing::debug!("unable to answer a refused request: {error}");
+        }
+
+        return;
+    };
+
+    let answered = match relay(&mut stream, &route, &head, path).await {
+        Ok((status, headers, body)) => respond(stream.get_mut(), status, &headers, body).await,
+        Err(error) => {
+            let body = error.to_string().into_bytes();
+            route.record(Some(error));
+
+            respond(
+                stream.get_mut(),
+                StatusCode::BAD_GATEWAY,
+                &HeaderMap::new(),
+                ResponseBody::Complete(body),
+            )
+            .await
+        }
+    };
+
+    // such as content that stopped arriving half way
+    if let Err(error) = answered {
+        tracing::debug!("unable to answer a relayed request: {error}");
+        route.record(Some(error));
+    }
+}
+
+async fn read_head(stream: &mut BufReader<TcpStream>) -> Result<Head, Error> {
+    let mut request_line = String::new();
+    stream
+        .read_line(&mut request_line)
+        .await
+        .context(FileSnafu)?;
+
+    let mut parts = request_line.split_whitespace();
+    let mut head = Head {
+        method: parts.next().unwrap_or_default().to_string(),
+        target: parts.next().unwrap_or("/").to_string(),
+        host: None,
+        content_length: 0,
+        chunked: false,
+        headers: HeaderMap::new(),
+    };
+
+    loop {
+        let mut header = String::new();
+        stream.read_line(&mut header).await.context(FileSnafu)?;
+
+        if header.trim().is_empty() {
+            break;
+        }
+
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let name = name.trim().to_ascii_lowercase();
+
+        if name == "content-length" {
+            head.content_length = value.trim().parse().unwrap_or_default();
+        } else if name == "host" {
+            head.host = Some(value.trim().to_string());
+        } else if name == "transfer-encoding" {
+            head.chunked = !value.trim().eq_ignore_ascii_case("identity");
+        } else if !HOP_BY_HOP.contains(&name.as_str()) {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value.trim()),
+            ) {
+                head.headers.append(name, value);
+            }
+        }
+    }
+
+    Ok(head)
+}
+
+// the command a request is for and the path of the API it asks for, when it is addressed to the
+// relay's `host` and led by the token of a running command
+fn find_route<'a>(head: &'a Head, host: &str) -> Option<(Arc<Route>, &'a str)> {
+    if head.host.as_deref() != Some(host) {
+        return None;
+    }
+
+    let routes = ROUTES.lock().ok()?;
+
+    routes.iter().filter_map(Weak::upgrade).find_map(|route| {
+        let path = relayed_path(&head.target, &route.token)?;
+        Some((route, path))
+    })
+}
+
+// send a single request of a command on
+async fn relay(
+    stream: &mut BufReader<TcpStream>,
+    route: &Route,
+    head: &Head,
+    path: &str,
+) -> Result<(StatusCode, HeaderMap, ResponseBody), Error> {
+    // only the length tells where the body ends, the SDK and `Client` send it with every body
+    if head.chunked {
+        return Ok(refused(
+            StatusCode::LENGTH_REQUIRED,
+            "the relay only takes bodies with a content-length",
+        ));
+    }
+
+    let mut body = vec![0; head.content_length];
+    stream.read_exact(&mut body).await.context(FileSnafu)?;
+
+    if route.dry_run && !matches!(head.method.as_str(), "GET" | "HEAD" | "OPTIONS") {
+        // requests sent side by side, such as part uploads, are stopped all the same
+        if !route.planned.swap(true, Ordering::SeqCst) {
+            print_planned(&planned(head.method.clone(), &route.base_url, path, &body))?;
+        }
+
+        // a client error, which `Client` does not retry even with an idempotency key
+        return Ok(refused(
+            StatusCode::BAD_REQUEST,
+            "not sent, --dry-run only prints requests that change something",
+        ));
+    }
+
+    let method = Method::from_bytes(head.method.as_bytes()).unwrap_or(Method::GET);
+    let (status, headers, body) = route
+        .client
+        .forward(method, path, &head.headers, body)
+        .await?;
+
+    // unsuccessful bodies are always read whole
+    match &body {
+        ResponseBody::Complete(content) if !status.is_success() => {
+            route.record(Some(Error::Request {
+                source: ApiError::from_response(
+                    status,
+                    &headers,
+                    &String::from_utf8_lossy(content),
+                ),
+                request_id: client::request_id(&headers),
+            }))
+        }
+        _ => route.record(None),
+    }
+
+    Ok((status, headers, body))
+}
+
+// the path of the API a request to the relay is for, `None` when it is not led by `token`
+fn relayed_path<'a>(path: &'a str, token: &str) -> Option<&'a str> {
+    let path = path.strip_prefix('/')?.strip_prefix(token)?;
+
+    if path.is_empty() || path.starts_with(['/', '?']) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+// an answer of the relay itself, for requests it does not send on
+fn refused(status: StatusCode, message: &str) -> (StatusCode, HeaderMap, ResponseBody) {
+    (
+        status,
+        HeaderMap::new(),
+        ResponseBody::Complete(message.as_bytes().to_vec()),
+    )
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: ResponseBody,
+) -> Result<(), Error> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("")
+    );
+
+    for (name, value) in headers {
+        if let (false, Ok(value)) = (HOP_BY_HOP.contains(&name.as_str()), value.to_str()) {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+
+    // without a length the end of the content is where the connection closes
+    let content_length = match &body {
+        ResponseBody::Complete(content) => Some(content.len() as u64),
+        ResponseBody::Streaming(response) => response.content_length(),
+    };
+    if let Some(content_length) = content_length {
+        head.push_str(&format!("content-length: {content_length}\r\n"));
+    }
+    head.push_str("connection: close\r\n\r\n");
+
+    stream.write_all(head.as_bytes()).await.context(FileSnafu)?;
+
+    match body {
+        ResponseBody::Complete(content) => stream.write_all(&content).await.context(FileSnafu)?,
+        ResponseBody::Streaming(mut response) => {
+            // passed on as it arrives, so large content is never held in memory as a whole
+            while let Some(chunk) = response.chunk().await.map_err(client::network)? {
+                stream.write_all(&chunk).await.context(FileSnafu)?;
+            }
+        }
+    }
+
+    stream.shutdown().await.context(FileSnafu)?;
+
+    Ok(())
+}
+
+fn planned(method: String, base_url: &str, path: &str, body: &[u8]) -> PlannedRequest {
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()))
+    };
+
+    PlannedRequest {
+        method,
+        url: format!("{base_url}{path}"),
+        body,
+    }
+}
+
+/// The base URL of the API, also once the relay took the place of `--base-url`.
+pub fn api_base_url(global_options: &GlobalOptions) -> String {
+    global_options
+        .api_base_url
+        .clone()
+        .or_else(|| global_options.base_url.clone())
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Print the requests of a command that plans several under `--dry-run` at once, in the order
+/// they would be sent.
+pub fn print_plan(requests: &[PlannedRequest]) -> Result<(), Error> {
+    if !matches!(output::format(), None | Some(OutputFormat::Human)) {
+        return output::print_json(&json!({
+            "dry_run": true,
+            "requests": requests
+                .iter()
+                .map(|request| json!({
+                    "method": request.method,
+                    "url": request.url,
+                    "body": request.body,
+                }))
+                .collect::<Vec<_>>(),
+        }));
+    }
+
+    for request in requests {
+        rendered(request).print_out().context(FileSnafu)?;
+    }
+
+    Ok(())
+}
+
+fn print_planned(request: &PlannedRequest) -> Result<(), Error> {
+    if !matches!(output::format(), None | Some(OutputFormat::Human)) {
+        return output::print_json(&json!({
+            "dry_run": true,
+            "method": request.method,
+            "url": request.url,
+            "body": request.body,
+        }));
+    }
+
+    rendered(request).print_out().context(FileSnafu)?;
+
+    Ok(())
+}
+
+fn rendered(request: &PlannedRequest) -> StyledStr {
+    let mut message = StyledStr::new();
+    message
+        .warning("dry run: ")
+        .plain(format!("{} {}", request.method, request.url));
+
+    if !request.body.is_null() {
+        message.plain(format!(
+            "\r\n{}",
+            serde_json::to_string_pretty(&request.body).unwrap()
+        ));
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn planned_request_keeps_the_api_url_and_json_body() {
+        let request = planned(
+            "POST".to_string(),
+            "https://api.peridio.com",
+            "/artifacts",
+            br#"{"artifact": {"name": "firmware"}}"#,
+        );
+
+        assert_eq!(
+            request,
+            PlannedRequest {
+                method: "POST".to_string(),
+                url: "https://api.peridio.com/artifacts".to_string(),
+                body: json!({ "artifact": { "name": "firmware" } }),
+            }
+        );
+    }
+
+    #[test]
+    fn only_requests_led_by_the_token_are_relayed() {
+        let token = "0123abcd";
+
+        assert_eq!(
+            relayed_path("/0123abcd/artifacts", token),
+            Some("/artifacts")
+        );
+        assert_eq!(relayed_path("/0123abcd?limit=1", token), Some("?limit=1"));
+        assert_eq!(relayed_path("/0123abcd", token), Some(""));
+        assert_eq!(relayed_path("/artifacts", token), None);
+        assert_eq!(relayed_path("/0123abcdef/artifacts", token), None);
+        assert_eq!(relayed_path("/other/0123abcd/artifacts", token), None);
+    }
+
+    #[test]
+    fn planned_request_without_body() {
+        let request = planned(
+            "DELETE".to_string(),
+            "https://api.peridio.com",
+            "/artifacts/prn:1:a",
+            b"",
+        );
+
+        assert_eq!(request.body, Value::Null);
+    }
+}
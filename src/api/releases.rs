@@ -1,8 +1,10 @@
 use super::Command;
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
@@ -41,13 +43,13 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Bundle)
     )]
-    bundle_prn: String,
+    bundle_prn: Prn,
     /// The PRN of the cohort you wish to create a release within.
     #[arg(
         long,
         value_parser = PRNValueParser::new(PRNType::Cohort)
     )]
-    cohort_prn: String,
+    cohort_prn: Prn,
     /// An arbitrary string attached to the resource. Often useful for displaying to users.
     #[arg(long)]
     description: Option<String>,
@@ -67,13 +69,13 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Release)
     )]
-    next_release_prn: Option<String>,
+    next_release_prn: Option<Prn>,
     /// The PRN of the organization you wish to create the resource within.
     #[arg(
         long,
         value_parser = PRNValueParser::new(PRNType::Organization)
     )]
-    organization_prn: String,
+    organization_prn: Prn,
 
     /// Limits by tags the devices that are allowed to update to this release.
     /// When phase_mode is tags, this field only allows devices to update to this release if they have at least one of these tags.
@@ -110,7 +112,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Release)
     )]
-    previous_release_prn: Option<String>,
+    previous_release_prn: Option<Prn>,
     /// Whether the release is required.
     ///
     /// If true, this release must be passed through if encountered by a device.
@@ -145,19 +147,19 @@ impl Command<CreateCommand> {
         };
 
         let params = CreateReleaseParams {
-            bundle_prn: self.inner.bundle_prn,
-            cohort_prn: self.inner.cohort_prn,
+            bundle_prn: self.inner.bundle_prn.to_string(),
+            cohort_prn: self.inner.cohort_prn.to_string(),
             description: self.inner.description,
             disabled: self.inner.disabled,
             name: self.inner.name,
-            organization_prn: self.inner.organization_prn,
+            organization_prn: self.inner.organization_prn.to_string(),
             phase_mode: Some(phase_mode),
             phase_tags,
             phase_value,
             required: self.inner.required,
             schedule_date: self.inner.schedule_date,
-            next_release_prn: self.inner.next_release_prn,
-            previous_release_prn: self.inner.previous_release_prn,
+            next_release_prn: self.inner.next_release_prn.map(|prn| prn.to_string()),
+            previous_release_prn: self.inner.previous_release_prn.map(|prn| prn.to_string()),
             version: self.inner.version,
             version_requirement: self.inner.version_requirement,
         };
@@ -169,7 +171,7 @@ impl Command<CreateCommand> {
         });
 
         match api.releases().create(params).await.context(ApiSnafu)? {
-            Some(release) => print_json!(&release),
+            Some(release) => print_created!(&release),
             None => panic!(),
         }
 
@@ -185,25 +187,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListReleasesParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
-
-        match api.releases().list(params).await.context(ApiSnafu)? {
-            Some(release) => print_json!(&release),
-            None => panic!(),
-        }
-
-        Ok(())
+        let api = &api;
+        let list_args = &self.inner.list_args;
+
+        list_args
+            .print(|page| {
+                let params = ListReleasesParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
+
+                async move { api.releases().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -214,13 +217,13 @@ pub struct GetCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Release)
     )]
-    prn: String,
+    prn: Prn,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = GetReleaseParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -245,7 +248,7 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Release)
     )]
-    pub prn: String,
+    pub prn: Prn,
 
     /// An arbitrary string attached to the resource. Often useful for displaying to users.
     #[arg(long)]
@@ -268,7 +271,7 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Release)
     )]
-    pub next_release_prn: Option<String>,
+    pub next_release_prn: Option<Prn>,
 
     /// Describes if this release is using tag or numeric based phasing. tags or phase value for resolution
     ///
@@ -325,11 +328,11 @@ pub struct UpdateCommand {
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = UpdateReleaseParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
             description: self.inner.description,
             disabled: self.inner.disabled,
             name: self.inner.name,
-            next_release_prn: self.inner.next_release_prn,
+            next_release_prn: self.inner.next_release_prn.map(|prn| prn.to_string()),
             phase_mode: self.inner.phase_mode,
             phase_tags: self.inner.phase_tags,
             phase_value: self.inner.phase_value,
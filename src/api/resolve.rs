@@ -0,0 +1,128 @@
+use serde_json::Value;
+
+use super::client::{self, Client};
+use super::list::collect_pages;
+use crate::utils::{PRNType, Prn, StyledStr};
+use crate::Error;
+use crate::GlobalOptions;
+
+/// The PRN given with `--prn`, or that of the resource named `name`, see
+/// [`resolve_prn_by_name`]. One of them is required by the command's arguments.
+pub async fn prn_or_name(
+    global_options: &GlobalOptions,
+    resource_type: PRNType,
+    prn: Option<Prn>,
+    name: Option<String>,
+) -> Result<String, Error> {
+    match (prn, name) {
+        (Some(prn), _) => Ok(prn.to_string()),
+        (None, Some(name)) => resolve_prn_by_name(global_options, resource_type, &name).await,
+        (None, None) => unreachable!("clap requires --prn or a name"),
+    }
+}
+
+/// Look up the PRN of the only resource of `resource_type` named `name` in the organization.
+///
+/// Exits with an error when none or several resources have the name, listing the PRNs of the
+/// latter so one can be passed with `--prn` instead.
+pub async fn resolve_prn_by_name(
+    global_options: &GlobalOptions,
+    resource_type: PRNType,
+    name: &str,
+) -> Result<String, Error> {
+    let collection = collection(&resource_type);
+    let organization_prn = client::organization_prn(global_options).await?;
+
+    let client = Client::new(global_options)?;
+    let client = &client;
+    let path = &format!("/{collection}");
+    let search = &format!("organization_prn:'{organization_prn}' and name:'{name}'");
+
+    let resources = collect_pages(None, None, |page| async move {
+        let mut query = vec![("search", search.clone())];
+
+        if let Some(page) = page {
+            query.push(("page", page));
+        }
+
+        client.get_with_query(path, &query).await.map(Some)
+    })
+    .await?;
+
+    match named(&resources, name).as_slice() {
+        [prn] => Ok(prn.to_string()),
+        [] => {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain(format!(
+                    "No {} is named {name} in the organization:\r\n",
+                    resource_type.name()
+                ))
+                .warning(format!("\t{organization_prn}"));
+            Err(error.data_err())
+        }
+        prns => {
+            let mut error = StyledStr::new();
+            error.error("error: ").plain(format!(
+                "{} {collection} are named {name}, pass one of their PRNs with --prn:",
+                prns.len()
+            ));
+
+            for prn in prns {
+                error.plain("\r\n").warning(format!("\t{prn}"));
+            }
+
+            Err(error.data_err())
+        }
+    }
+}
+
+// the list endpoint of the resources that can be looked up by name
+fn collection(resource_type: &PRNType) -> &'static str {
+    match resource_type {
+        PRNType::Artifact => "artifacts",
+        PRNType::Cohort => "cohorts",
+        PRNType::Product => "products",
+        PRNType::Release => "releases",
+        _ => unreachable!("{} can't be looked up by name", resource_type.name()),
+    }
+}
+
+// the PRNs of the resources named exactly `name`, the search may also match similar names
+fn named<'a>(resources: &'a [Value], name: &str) -> Vec<&'a str> {
+    resources
+        .iter()
+        .filter(|resource| resource.get("name").and_then(Value::as_str) == Some(name))
+        .filter_map(|resource| resource.get("prn").and_then(Value::as_str))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_exact_names_match() {
+        let products = [
+            json!({ "name": "gateway", "prn": "prn:1:org:product:a" }),
+            json!({ "name": "gateway-lab", "prn": "prn:1:org:product:b" }),
+            json!({ "name": "gateway", "prn": "prn:1:org:product:c" }),
+        ];
+
+        assert_eq!(
+            named(&products, "gateway"),
+            ["prn:1:org:product:a", "prn:1:org:product:c"]
+        );
+        assert_eq!(named(&products, "gateway-lab"), ["prn:1:org:product:b"]);
+        assert!(named(&products, "Gateway").is_empty());
+    }
+
+    #[test]
+    fn resources_without_a_prn_are_skipped() {
+        let cohorts = [json!({ "name": "canary" })];
+
+        assert!(named(&cohorts, "canary").is_empty());
+    }
+}
@@ -1,19 +1,27 @@
 use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use super::Command;
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
+use crate::utils::temp_file::TempFile;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
+use crate::utils::StyledStr;
 use crate::ApiSnafu;
 use crate::Error;
+use crate::FileSnafu;
 use crate::GlobalOptions;
 use crate::NonExistingPathSnafu;
 use base64::engine::general_purpose;
 use base64::Engine;
 use clap::Parser;
-use ed25519_dalek::pkcs8::DecodePublicKey;
-use ed25519_dalek::VerifyingKey;
+use ed25519_dalek::pkcs8::LineEnding;
+use ed25519_dalek::pkcs8::{DecodePublicKey, EncodePrivateKey};
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use peridio_sdk::api::signing_keys::CreateSigningKeyParams;
 use peridio_sdk::api::signing_keys::DeleteSigningKeyParams;
 use peridio_sdk::api::signing_keys::GetSigningKeyParams;
@@ -26,6 +34,7 @@ use snafu::ResultExt;
 pub enum SigningKeysCommand {
     Create(Command<CreateCommand>),
     Delete(Command<DeleteCommand>),
+    Generate(Command<GenerateCommand>),
     Get(Command<GetCommand>),
     List(Command<ListCommand>),
 }
@@ -35,6 +44,7 @@ impl SigningKeysCommand {
         match self {
             Self::Create(cmd) => cmd.run(global_options).await,
             Self::Delete(cmd) => cmd.run(global_options).await,
+            Self::Generate(cmd) => cmd.run(global_options).await,
             Self::Get(cmd) => cmd.run(global_options).await,
             Self::List(cmd) => cmd.run(global_options).await,
         }
@@ -62,7 +72,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Organization)
     )]
-    organization_prn: String,
+    organization_prn: Prn,
     /// The path to the public key raw file.
     #[arg(
         long,
@@ -106,7 +116,7 @@ impl Command<CreateCommand> {
         let params = CreateSigningKeyParams {
             value,
             name: self.inner.name,
-            organization_prn: self.inner.organization_prn,
+            organization_prn: self.inner.organization_prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -116,7 +126,70 @@ impl Command<CreateCommand> {
         });
 
         match api.signing_keys().create(params).await.context(ApiSnafu)? {
-            Some(key) => print_json!(&key),
+            Some(key) => print_created!(&key),
+            None => panic!(),
+        }
+
+        Ok(())
+    }
+}
+
+/// Generate an Ed25519 key pair locally and register its public key as a signing key.
+///
+/// The private key is written as a PKCS#8 PEM file that only the current user may read, the
+/// format --signing-key-private expects.
+#[derive(Parser, Debug)]
+pub struct GenerateCommand {
+    /// The resource's name, meant to be displayable to users.
+    #[arg(long)]
+    name: String,
+    /// The PRN of the organization you wish to create the resource within.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Organization)
+    )]
+    organization_prn: Prn,
+    /// The path to write the private key PEM file to.
+    #[arg(long)]
+    private_key_path: PathBuf,
+    /// Overwrite an existing file at --private-key-path.
+    #[arg(long, default_value = "false")]
+    force: bool,
+}
+
+impl Command<GenerateCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let path = self.inner.private_key_path;
+
+        if path.exists() && !self.inner.force {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("Refusing to overwrite an existing private key:\r\n")
+                .warning(format!("\t{}\r\n", path.display()))
+                .plain("\r\nPass --force to replace it");
+            return Err(error.data_err());
+        }
+
+        let signing_key = generate_signing_key();
+
+        // saved before registering, a registered key without its private key is of no use
+        write_private_key(&signing_key, &path)?;
+
+        let params = CreateSigningKeyParams {
+            value: general_purpose::STANDARD.encode(signing_key.verifying_key().as_bytes()),
+            name: self.inner.name,
+            organization_prn: self.inner.organization_prn.to_string(),
+        };
+
+        let api = Api::new(ApiOptions {
+            api_key: global_options.api_key.unwrap(),
+            endpoint: global_options.base_url,
+            ca_bundle_path: global_options.ca_path,
+        });
+
+        match api.signing_keys().create(params).await.context(ApiSnafu)? {
+            Some(key) => print_created!(&key),
             None => panic!(),
         }
 
@@ -124,6 +197,29 @@ impl Command<CreateCommand> {
     }
 }
 
+fn generate_signing_key() -> SigningKey {
+    let mut secret = [0; ed25519_dalek::SECRET_KEY_LENGTH];
+    aws_lc_rs::rand::fill(&mut secret).expect("the system random number generator failed");
+
+    SigningKey::from_bytes(&secret)
+}
+
+// the key as PKCS#8 PEM, moved into place only once it is complete
+fn write_private_key(signing_key: &SigningKey, path: &Path) -> Result<(), Error> {
+    let pem = signing_key
+        .to_pkcs8_pem(LineEnding::LF)
+        .expect("an Ed25519 key always encodes as PKCS#8");
+
+    let mut temp_file = TempFile::new(path).context(FileSnafu)?;
+    temp_file
+        .file()
+        .write_all(pem.as_bytes())
+        .context(FileSnafu)?;
+    temp_file.persist().context(FileSnafu)?;
+
+    Ok(())
+}
+
 #[derive(Parser, Debug)]
 pub struct GetCommand {
     /// The PRN of the resource to get.
@@ -131,13 +227,13 @@ pub struct GetCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::SigningKey)
     )]
-    prn: String,
+    prn: Prn,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = GetSigningKeyParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -163,25 +259,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListSigningKeysParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
+        let api = &api;
+        let list_args = &self.inner.list_args;
 
-        match api.signing_keys().list(params).await.context(ApiSnafu)? {
-            Some(signing_key) => print_json!(&signing_key),
-            None => panic!(),
-        }
+        list_args
+            .print(|page| {
+                let params = ListSigningKeysParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
 
-        Ok(())
+                async move { api.signing_keys().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -192,13 +289,13 @@ pub struct DeleteCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::SigningKey)
     )]
-    signing_key_prn: String,
+    signing_key_prn: Prn,
 }
 
 impl Command<DeleteCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = DeleteSigningKeyParams {
-            signing_key_prn: self.inner.signing_key_prn,
+            signing_key_prn: self.inner.signing_key_prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -214,3 +311,46 @@ impl Command<DeleteCommand> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::pkcs8::DecodePrivateKey;
+
+    #[test]
+    fn generated_keys_are_random() {
+        assert_ne!(
+            generate_signing_key().to_bytes(),
+            generate_signing_key().to_bytes()
+        );
+    }
+
+    #[test]
+    fn private_key_is_written_as_pkcs8_pem() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("signing-key.pem");
+        let signing_key = generate_signing_key();
+
+        write_private_key(&signing_key, &path).unwrap();
+
+        let pem = fs::read_to_string(&path).unwrap();
+        let read = SigningKey::from_pkcs8_pem(&pem).unwrap();
+        assert_eq!(read.verifying_key(), signing_key.verifying_key());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_key_is_only_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("signing-key.pem");
+        fs::write(&path, "previous key").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private_key(&generate_signing_key(), &path).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
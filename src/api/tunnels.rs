@@ -3,8 +3,9 @@ use std::time::{Duration, Instant};
 
 use super::Command;
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
-use crate::utils::{PRNType, PRNValueParser};
+use crate::utils::{PRNType, PRNValueParser, Prn};
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
@@ -19,6 +20,7 @@ use snafu::ResultExt;
 
 #[derive(Parser, Debug)]
 pub enum TunnelsCommand {
+    Close(Command<CloseCommand>),
     Create(Command<CreateCommand>),
     Get(Command<GetCommand>),
     List(Command<ListCommand>),
@@ -28,6 +30,7 @@ pub enum TunnelsCommand {
 impl TunnelsCommand {
     pub async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         match self {
+            Self::Close(cmd) => cmd.run(global_options).await,
             Self::Create(cmd) => cmd.run(global_options).await,
             Self::Get(cmd) => cmd.run(global_options).await,
             Self::List(cmd) => cmd.run(global_options).await,
@@ -47,7 +50,7 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Device)
     )]
-    device_prn: String,
+    device_prn: Prn,
 
     /// The port of the device that being used for the service.
     #[arg(long)]
@@ -66,7 +69,7 @@ impl Command<CreateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = CreateTunnelParams {
             cidr_block_allowlist: self.inner.cidr_block_allowlist,
-            device_prn: self.inner.device_prn,
+            device_prn: self.inner.device_prn.to_string(),
             device_tunnel_port: self.inner.device_tunnel_port,
             ttl: self.inner.ttl,
         };
@@ -96,14 +99,14 @@ impl Command<CreateCommand> {
                         match api.tunnels().get(params).await.context(ApiSnafu)? {
                             Some(response) => {
                                 if response.tunnel.state != "requested" {
-                                    print_json!(&response);
+                                    print_created!(&response);
                                     break;
                                 }
 
                                 let next_sleep_secs = (attempts + 2).pow(2);
 
                                 if now.elapsed().as_secs() + next_sleep_secs > max_time {
-                                    print_json!(&response);
+                                    print_created!(&response);
                                     break;
                                 } else {
                                     sleep(Duration::from_secs(sleep_secs))
@@ -113,7 +116,7 @@ impl Command<CreateCommand> {
                         }
                     }
                 } else {
-                    print_json!(&response);
+                    print_created!(&response);
                 }
             }
             None => panic!(),
@@ -130,13 +133,13 @@ pub struct GetCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Tunnel)
     )]
-    prn: String,
+    prn: Prn,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = GetTunnelParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -162,25 +165,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListTunnelsParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
-
-        match api.tunnels().list(params).await.context(ApiSnafu)? {
-            Some(tunnel) => print_json!(&tunnel),
-            None => panic!(),
-        }
-
-        Ok(())
+        let api = &api;
+        let list_args = &self.inner.list_args;
+
+        list_args
+            .print(|page| {
+                let params = ListTunnelsParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
+
+                async move { api.tunnels().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -191,7 +195,7 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Tunnel)
     )]
-    prn: String,
+    prn: Prn,
 
     /// The resource's state, currently only supports "closed".
     #[arg(long, value_parser=only_closed)]
@@ -202,6 +206,39 @@ pub struct UpdateCommand {
     pub ttl: Option<u16>,
 }
 
+#[derive(Parser, Debug)]
+pub struct CloseCommand {
+    /// The PRN of the resource to close.
+    #[arg(
+        long,
+        value_parser = PRNValueParser::new(PRNType::Tunnel)
+    )]
+    prn: Prn,
+}
+
+impl Command<CloseCommand> {
+    async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let params = UpdateTunnelParams {
+            prn: self.inner.prn.to_string(),
+            state: Some("closed".to_string()),
+            ttl: None,
+        };
+
+        let api = Api::new(ApiOptions {
+            api_key: global_options.api_key.unwrap(),
+            endpoint: global_options.base_url,
+            ca_bundle_path: global_options.ca_path,
+        });
+
+        match api.tunnels().update(params).await.context(ApiSnafu)? {
+            Some(tunnel) => print_json!(&tunnel),
+            None => panic!(),
+        }
+
+        Ok(())
+    }
+}
+
 fn less_than_3600(s: &str) -> Result<u16, String> {
     number_range(s, 1, 3600)
 }
@@ -217,7 +254,7 @@ fn only_closed(s: &str) -> Result<String, String> {
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = UpdateTunnelParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
             state: self.inner.state,
             ttl: self.inner.ttl,
         };
@@ -1,8 +1,8 @@
 use std::{
-    cmp::min,
-    env,
-    fs::{create_dir_all, rename},
-    io::{Cursor, ErrorKind, Seek, Write},
+    cmp::{min, Ordering},
+    env, fmt,
+    fs::{self, create_dir_all, File},
+    io::{self, Cursor, ErrorKind, Seek, Write},
     path::Path,
 };
 
@@ -13,8 +13,12 @@ use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
 use reqwest::ClientBuilder;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use tar::Archive;
 
+use crate::utils::output;
+use crate::utils::temp_file::TempFile;
+use crate::utils::StyledStr;
 use crate::Error;
 
 #[derive(Deserialize, Debug)]
@@ -27,15 +31,249 @@ struct GithubAssetResponse {
 struct GithubResponse {
     tag_name: String,
     assets: Vec<GithubAssetResponse>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+}
+
+/// Which releases to upgrade to.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    /// Only releases, the default.
+    Stable,
+    /// Releases and pre-releases, such as 1.2.0-beta.1.
+    Beta,
 }
 
 #[derive(Parser, Debug)]
 pub struct UpgradeCommand {
     /// Controls what version to upgrade to.
     ///
-    /// If not specified, the latest version will be used.
+    /// If not specified, the latest version of the --channel will be used. A pinned version is
+    /// installed even when it is older than the current one.
     #[arg(long)]
     version: Option<String>,
+
+    /// Which releases to consider when no --version is given.
+    #[arg(long, value_enum, default_value_t = Channel::Stable, conflicts_with = "version")]
+    channel: Channel,
+
+    /// Only report whether an upgrade is available, without installing it.
+    #[arg(long, default_value = "false")]
+    check_only: bool,
+
+    /// Install releases that publish no checksum, such as those from before checksums were
+    /// published, without verifying the download.
+    #[arg(long, default_value = "false")]
+    allow_unverified: bool,
+}
+
+/// A release version such as `0.28.1` or `0.29.0-beta.1`, ordered by semantic versioning.
+#[derive(Debug, PartialEq, Eq)]
+struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Option<String>,
+}
+
+impl Version {
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.strip_prefix('v').unwrap_or(value);
+        let (release, pre) = match value.split_once('-') {
+            Some((release, pre)) => (release, Some(pre.to_string())),
+            None => (value, None),
+        };
+
+        let mut numbers = release.split('.').map(|number| number.parse().ok());
+        let version = Self {
+            major: numbers.next()??,
+            minor: numbers.next()??,
+            patch: numbers.next()??,
+            pre,
+        };
+
+        numbers.next().is_none().then_some(version)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                // a pre-release comes before its release
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(pre), Some(other)) => compare_pre(pre, other),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(pre) = &self.pre {
+            write!(f, "-{pre}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// dot separated identifiers, numeric ones compare as numbers and before alphanumeric ones
+fn compare_pre(pre: &str, other: &str) -> Ordering {
+    let mut identifiers = pre.split('.');
+    let mut others = other.split('.');
+
+    loop {
+        return match (identifiers.next(), others.next()) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(identifier), Some(other)) => {
+                let ordering = match (identifier.parse::<u64>(), other.parse::<u64>()) {
+                    (Ok(identifier), Ok(other)) => identifier.cmp(&other),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => identifier.cmp(other),
+                };
+
+                if ordering == Ordering::Equal {
+                    continue;
+                }
+
+                ordering
+            }
+        };
+    }
+}
+
+/// Whether `release` should be installed over `current`, a pinned version even when it is older.
+fn should_install(current: &Version, release: &Version, pinned: bool) -> bool {
+    if pinned {
+        release != current
+    } else {
+        release > current
+    }
+}
+
+// what --check-only reports
+fn check_message(current: &Version, release: &Version, pinned: bool) -> String {
+    if should_install(current, release, pinned) {
+        format!("Upgrade available: {current} -> {release}")
+    } else {
+        format!("CLI already up to date ({current})")
+    }
+}
+
+/// The newest release of the `channel` among `releases`, drafts are never considered.
+fn newest_release(releases: Vec<GithubResponse>, channel: Channel) -> Option<GithubResponse> {
+    releases
+        .into_iter()
+        .filter(|release| !release.draft && (channel == Channel::Beta || !release.prerelease))
+        .filter_map(|release| Version::parse(&release.tag_name).map(|version| (version, release)))
+        .max_by(|(version, _), (other, _)| version.cmp(other))
+        .map(|(_, release)| release)
+}
+
+// the name of the executable in release archives
+const EXECUTABLE_NAME: &str = if cfg!(windows) {
+    "peridio.exe"
+} else {
+    "peridio"
+};
+
+/// Replace `executable` with the content of `update`, keeping the mode of `executable`.
+///
+/// The update is staged next to `executable`, as the cache directory may live on another
+/// filesystem, and renamed over it, so a crash leaves either the old or the new executable in
+/// place but never a partial one.
+fn replace_executable(executable: &Path, update: &Path) -> io::Result<()> {
+    let mut temp_file = TempFile::new(executable)?;
+
+    io::copy(&mut File::open(update)?, temp_file.file())?;
+
+    let permissions = fs::metadata(executable)?.permissions();
+    fs::set_permissions(temp_file.path(), permissions)?;
+
+    #[cfg(not(windows))]
+    temp_file.persist()?;
+
+    // a running executable cannot be overwritten on Windows, but it can be renamed
+    #[cfg(windows)]
+    {
+        let replaced = executable.with_extension("exe.old");
+        let _ = fs::remove_file(&replaced);
+        fs::rename(executable, &replaced)?;
+
+        if let Err(error) = temp_file.persist() {
+            fs::rename(&replaced, executable)?;
+            return Err(error);
+        }
+
+        remove_after_exit(&replaced);
+    }
+
+    Ok(())
+}
+
+// the replaced executable is still running, so removing it is left to a process that waits
+// for this one to exit, a leftover is removed by the next upgrade otherwise
+#[cfg(windows)]
+fn remove_after_exit(replaced: &Path) {
+    use std::os::windows::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+
+    let _ = Command::new("cmd")
+        .arg("/C")
+        .arg(format!(
+            "ping -n 3 127.0.0.1 > nul & del /F /Q \"{}\"",
+            replaced.display()
+        ))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(DETACHED_PROCESS)
+        .spawn();
+}
+
+/// Check that `archive` has the published SHA-256 `checksum`.
+fn verify_checksum(archive: &[u8], checksum: &str) -> Result<(), String> {
+    let actual = format!("{:x}", Sha256::digest(archive));
+
+    if actual == checksum {
+        Ok(())
+    } else {
+        Err(format!(
+            "checksum mismatch, expected {checksum}, got {actual}"
+        ))
+    }
+}
+
+/// The SHA-256 of a `.sha256` asset, which holds the hex digest optionally followed by a name.
+fn parse_checksum(asset: &str) -> Option<String> {
+    let checksum = asset.split_whitespace().next()?.to_lowercase();
+
+    (checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit())).then_some(checksum)
+}
+
+/// `message` as the error of a failed upgrade, see `StyledStr::data_err`.
+fn upgrade_error(message: impl Into<String>) -> StyledStr {
+    let mut error = StyledStr::new();
+    error.error("error: ").plain(message);
+    error
 }
 
 impl UpgradeCommand {
@@ -45,64 +283,110 @@ impl UpgradeCommand {
 
             create_dir_all(cache_dir).unwrap();
 
-            if let Ok(resp) = Self::get_release_info(self.version).await {
-                let current_version = env!("CARGO_PKG_VERSION");
+            let pinned = self.version.is_some();
 
-                // no need to update
-                if resp.tag_name == current_version {
-                    println!("CLI already up to date");
-                    return Ok(());
-                }
+            let resp = Self::get_release_info(self.version, self.channel).await?;
 
-                let name = format!("peridio-{}_{}.tar.gz", resp.tag_name, env!("TARGET"));
-
-                let github_asset_info = match resp.assets.iter().find(|&x| x.name == name) {
-                    Some(x) => x,
-                    None => {
-                        println!(
-                            "version {} does not include a pre-built binary for target {}",
-                            resp.tag_name,
-                            env!("TARGET")
-                        );
-                        return Ok(());
-                    }
-                };
+            let current_version = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
 
-                if let Err(message) = Self::download_update(cache_dir, github_asset_info).await {
-                    println!("{message}");
-                    return Ok(());
-                }
+            let Some(release_version) = Version::parse(&resp.tag_name) else {
+                return Err(
+                    upgrade_error(format!("release {} is not a version", resp.tag_name)).data_err(),
+                );
+            };
 
-                if let Err(message) = Self::apply_update(cache_dir, &resp) {
-                    println!("{message}");
-                    return Ok(());
-                }
+            if self.check_only {
+                println!(
+                    "{}",
+                    check_message(&current_version, &release_version, pinned)
+                );
+                return Ok(());
+            }
+
+            // no need to update
+            if !should_install(&current_version, &release_version, pinned) {
+                println!("CLI already up to date");
+                return Ok(());
             }
+
+            let name = format!("peridio-{}_{}.tar.gz", resp.tag_name, env!("TARGET"));
+
+            let Some(github_asset_info) = resp.assets.iter().find(|&x| x.name == name) else {
+                return Err(upgrade_error(format!(
+                    "version {} does not include a pre-built binary for target {}",
+                    resp.tag_name,
+                    env!("TARGET")
+                ))
+                .data_err());
+            };
+
+            let checksum_name = format!("{name}.sha256");
+
+            let checksum = match resp.assets.iter().find(|&x| x.name == checksum_name) {
+                Some(checksum_info) => Some(Self::get_checksum(checksum_info).await?),
+                None if self.allow_unverified => {
+                    output::warn_urgent(format!(
+                        "version {} does not publish a checksum for {name}, installing it UNVERIFIED",
+                        resp.tag_name
+                    ));
+                    None
+                }
+                None => {
+                    return Err(upgrade_error(format!(
+                        "version {} does not publish a checksum for {name}, refusing to install it, pass --allow-unverified to install it anyway",
+                        resp.tag_name
+                    ))
+                    .data_err());
+                }
+            };
+
+            Self::download_update(cache_dir, github_asset_info, checksum.as_deref()).await?;
+
+            Self::apply_update(cache_dir, &resp, checksum.as_deref())?;
         }
 
         Ok(())
     }
 
-    fn apply_update(path: &Path, github_response: &GithubResponse) -> Result<(), String> {
-        let update_file = path.join("peridio");
+    fn apply_update(
+        path: &Path,
+        github_response: &GithubResponse,
+        checksum: Option<&str>,
+    ) -> Result<(), Error> {
+        let update_file = path.join(EXECUTABLE_NAME);
 
-        let current_cli_executable =
-            env::current_exe().map_err(|_| "Can't retrieve the current cli directory")?;
+        let current_cli_executable = env::current_exe()
+            .map_err(|_| upgrade_error("Can't retrieve the current cli directory").exit_err(1))?;
 
-        if let Err(err) = rename(update_file, &current_cli_executable) {
+        let replace_error = |err: io::Error| {
             if err.kind() == ErrorKind::PermissionDenied {
-                return Err(format!(
+                return upgrade_error(format!(
                     "CLI failed to upgrade: permission denied writing to {}",
                     &current_cli_executable.display()
-                ));
+                ))
+                .exit_err(77);
             }
-            return Err(format!(
+            upgrade_error(format!(
                 "CLI failed to upgrade: unknown error writing to {}",
                 &current_cli_executable.display()
-            ));
-        }
+            ))
+            .exit_err(1)
+        };
+
+        replace_executable(&current_cli_executable, &update_file).map_err(replace_error)?;
 
-        println!("CLI upgraded successfully ({})", github_response.tag_name);
+        let _ = fs::remove_file(update_file);
+
+        match checksum {
+            Some(checksum) => println!(
+                "CLI upgraded successfully ({}, sha256 {checksum})",
+                github_response.tag_name
+            ),
+            None => println!(
+                "CLI upgraded successfully ({}, unverified)",
+                github_response.tag_name
+            ),
+        }
 
         Ok(())
     }
@@ -110,20 +394,22 @@ impl UpgradeCommand {
     async fn download_update(
         download_path: &Path,
         github_asset_info: &GithubAssetResponse,
-    ) -> Result<(), String> {
+        checksum: Option<&str>,
+    ) -> Result<(), Error> {
         let client = ClientBuilder::new().use_rustls_tls().build().unwrap();
         let url = &github_asset_info.browser_download_url;
+        let failed = |message: String| upgrade_error(message).temp_fail_err();
 
         // Reqwest setup
         let res = client
             .get(url)
             .send()
             .await
-            .map_err(|_| format!("Failed to GET from '{url}'"))?;
+            .map_err(|_| failed(format!("Failed to GET from '{url}'")))?;
 
         let total_size = res
             .content_length()
-            .ok_or(format!("Failed to get content length from '{url}'"))?;
+            .ok_or_else(|| failed(format!("Failed to get content length from '{url}'")))?;
 
         // Indicatif setup
         let pb = ProgressBar::new(total_size);
@@ -140,9 +426,9 @@ impl UpgradeCommand {
         let mut buff = Cursor::new(mem);
 
         while let Some(item) = stream.next().await {
-            let chunk = item.map_err(|_| "Error while downloading file".to_string())?;
+            let chunk = item.map_err(|_| failed("Error while downloading file".to_string()))?;
             buff.write_all(&chunk)
-                .map_err(|_| "Error while writing to buffer".to_string())?;
+                .map_err(|_| failed("Error while writing to buffer".to_string()))?;
             let new = min(downloaded + (chunk.len() as u64), total_size);
             downloaded = new;
             pb.set_position(new);
@@ -150,6 +436,16 @@ impl UpgradeCommand {
 
         pb.finish_and_clear();
 
+        // nothing is unpacked, let alone installed, unless the archive is the published one
+        if let Some(checksum) = checksum {
+            verify_checksum(buff.get_ref(), checksum).map_err(|message| {
+                upgrade_error(format!("Refusing to install '{url}': {message}")).data_err()
+            })?;
+        }
+
+        // an executable left behind by an earlier attempt must not be installed instead
+        let _ = fs::remove_file(download_path.join(EXECUTABLE_NAME));
+
         buff.rewind().unwrap();
 
         let gz = GzDecoder::new(&mut buff);
@@ -158,26 +454,263 @@ impl UpgradeCommand {
 
         archive
             .unpack(download_path)
-            .map_err(|_| "Error while saving the updated file".to_string())?;
+            .map_err(|_| upgrade_error("Error while saving the updated file").exit_err(1))?;
 
         Ok(())
     }
 
-    async fn get_release_info(version: Option<String>) -> Result<GithubResponse, reqwest::Error> {
-        let client = ClientBuilder::new().use_rustls_tls().build()?;
-        let url = if let Some(version) = version {
-            format!("https://api.github.com/repos/peridio/peridio-cli/releases/tags/{version}")
-        } else {
-            "https://api.github.com/repos/peridio/peridio-cli/releases/latest".to_owned()
-        };
+    async fn get_checksum(checksum_info: &GithubAssetResponse) -> Result<String, Error> {
+        let client = ClientBuilder::new().use_rustls_tls().build().unwrap();
+        let url = &checksum_info.browser_download_url;
+        let failed = |message: String| upgrade_error(message).temp_fail_err();
 
-        client
+        let asset = client
             .get(url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|_| failed(format!("Failed to GET from '{url}'")))?
+            .text()
+            .await
+            .map_err(|_| failed(format!("Failed to read the checksum from '{url}'")))?;
+
+        parse_checksum(&asset)
+            .ok_or_else(|| upgrade_error(format!("Invalid checksum in '{url}'")).data_err())
+    }
+
+    async fn get_release_info(
+        version: Option<String>,
+        channel: Channel,
+    ) -> Result<GithubResponse, Error> {
+        let client = ClientBuilder::new().use_rustls_tls().build().unwrap();
+        let url = match (&version, channel) {
+            (Some(version), _) => {
+                format!("https://api.github.com/repos/peridio/peridio-cli/releases/tags/{version}")
+            }
+            (None, Channel::Stable) => {
+                "https://api.github.com/repos/peridio/peridio-cli/releases/latest".to_owned()
+            }
+            // the latest endpoint skips pre-releases
+            (None, Channel::Beta) => {
+                "https://api.github.com/repos/peridio/peridio-cli/releases?per_page=100".to_owned()
+            }
+        };
+
+        let request = client
+            .get(&url)
             .header("Accept", "application/vnd.github+json")
-            .header("User-Agent", "peridio/peridio-cli")
+            .header("User-Agent", "peridio/peridio-cli");
+        let failed =
+            |_| upgrade_error(format!("Failed to get release info from '{url}'")).temp_fail_err();
+
+        if version.is_none() && channel == Channel::Beta {
+            let releases = request
+                .send()
+                .await
+                .map_err(failed)?
+                .json::<Vec<GithubResponse>>()
+                .await
+                .map_err(failed)?;
+
+            return newest_release(releases, channel)
+                .ok_or_else(|| upgrade_error(format!("No release found at '{url}'")).data_err());
+        }
+
+        request
             .send()
-            .await?
+            .await
+            .map_err(failed)?
             .json::<GithubResponse>()
             .await
+            .map_err(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(value: &str) -> Version {
+        Version::parse(value).unwrap()
+    }
+
+    fn release(tag_name: &str, prerelease: bool) -> GithubResponse {
+        GithubResponse {
+            tag_name: tag_name.to_string(),
+            assets: Vec::new(),
+            draft: false,
+            prerelease,
+        }
+    }
+
+    #[test]
+    fn versions_are_parsed() {
+        assert_eq!(
+            version("v1.2.3-beta.1"),
+            Version {
+                major: 1,
+                minor: 2,
+                patch: 3,
+                pre: Some("beta.1".to_string()),
+            }
+        );
+        assert_eq!(version("0.28.1").to_string(), "0.28.1");
+
+        for invalid in ["", "1.2", "1.2.3.4", "1.x.3", "latest"] {
+            assert_eq!(Version::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn versions_are_ordered_semantically() {
+        let ordered = [
+            "0.9.0",
+            "0.10.0-alpha",
+            "0.10.0-alpha.1",
+            "0.10.0-alpha.beta",
+            "0.10.0-beta.2",
+            "0.10.0-beta.11",
+            "0.10.0-rc.1",
+            "0.10.0",
+            "0.10.1",
+            "1.0.0",
+        ];
+
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{pair:?}");
+        }
+    }
+
+    #[test]
+    fn only_newer_versions_are_installed_unless_pinned() {
+        let current = version("0.28.1");
+
+        assert!(should_install(&current, &version("0.29.0"), false));
+        assert!(!should_install(&current, &version("0.28.1"), false));
+        assert!(!should_install(&current, &version("0.27.0"), false));
+        assert!(!should_install(&current, &version("0.28.1-beta.1"), false));
+
+        assert!(should_install(&current, &version("0.27.0"), true));
+        assert!(!should_install(&current, &version("0.28.1"), true));
+    }
+
+    #[test]
+    fn check_only_reports_without_installing() {
+        let current = version("0.28.1");
+
+        assert_eq!(
+            check_message(&current, &version("0.29.0"), false),
+            "Upgrade available: 0.28.1 -> 0.29.0"
+        );
+        assert_eq!(
+            check_message(&current, &version("0.28.1"), false),
+            "CLI already up to date (0.28.1)"
+        );
+    }
+
+    #[test]
+    fn beta_channel_includes_pre_releases() {
+        let releases = || {
+            vec![
+                release("0.28.1", false),
+                release("0.29.0-beta.1", true),
+                release("0.27.0", false),
+                GithubResponse {
+                    draft: true,
+                    ..release("0.30.0", false)
+                },
+            ]
+        };
+
+        assert_eq!(
+            newest_release(releases(), Channel::Beta).unwrap().tag_name,
+            "0.29.0-beta.1"
+        );
+        assert_eq!(
+            newest_release(releases(), Channel::Stable)
+                .unwrap()
+                .tag_name,
+            "0.28.1"
+        );
+    }
+
+    #[test]
+    fn checksums_are_parsed_from_the_asset() {
+        let checksum = TEST_CHECKSUM;
+
+        assert_eq!(
+            parse_checksum(&format!("{checksum}  peridio-0.29.0_x86_64.tar.gz\n")),
+            Some(checksum.to_string())
+        );
+        assert_eq!(
+            parse_checksum(&checksum.to_uppercase()),
+            Some(checksum.to_string())
+        );
+        assert_eq!(parse_checksum("not a checksum"), None);
+        assert_eq!(parse_checksum(""), None);
+    }
+
+    // the SHA-256 of "test"
+    const TEST_CHECKSUM: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn matching_checksum_is_verified() {
+        assert_eq!(verify_checksum(b"test", TEST_CHECKSUM), Ok(()));
+    }
+
+    #[test]
+    fn mismatching_checksum_is_refused() {
+        let error = verify_checksum(b"tampered", TEST_CHECKSUM).unwrap_err();
+
+        assert!(
+            error.starts_with(&format!(
+                "checksum mismatch, expected {TEST_CHECKSUM}, got "
+            )),
+            "{error}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_is_replaced_through_a_temp_file_keeping_its_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let executable = directory.path().join("peridio");
+        fs::write(&executable, "old").unwrap();
+        fs::set_permissions(&executable, fs::Permissions::from_mode(0o750)).unwrap();
+
+        let cache = tempfile::tempdir().unwrap();
+        let update = cache.path().join("peridio");
+        fs::write(&update, "new").unwrap();
+        fs::set_permissions(&update, fs::Permissions::from_mode(0o777)).unwrap();
+
+        replace_executable(&executable, &update).unwrap();
+
+        assert_eq!(fs::read_to_string(&executable).unwrap(), "new");
+        assert_eq!(
+            fs::metadata(&executable).unwrap().permissions().mode() & 0o777,
+            0o750
+        );
+
+        // the staged copy was renamed into place rather than left behind
+        let entries: Vec<_> = fs::read_dir(directory.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, ["peridio"]);
+    }
+
+    #[test]
+    fn failed_replacement_leaves_the_executable_and_no_temp_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let executable = directory.path().join("peridio");
+        fs::write(&executable, "old").unwrap();
+
+        let missing_update = directory.path().join("missing");
+
+        assert!(replace_executable(&executable, &missing_update).is_err());
+        assert_eq!(fs::read_to_string(&executable).unwrap(), "old");
+        assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
     }
 }
@@ -1,5 +1,5 @@
 use super::Command;
-use crate::api::CliCommands;
+use crate::api::{CliCommands, RequiredArgument};
 use crate::print_json;
 use crate::ApiSnafu;
 use crate::Error;
@@ -31,10 +31,10 @@ impl Command<MeCommand> {
         let mut missing_arguments = Vec::new();
 
         if global_options.api_key.is_none() {
-            missing_arguments.push("--api-key".to_owned());
+            missing_arguments.push(RequiredArgument::ApiKey);
         }
 
-        CliCommands::print_missing_arguments(missing_arguments);
+        CliCommands::check_missing_arguments(missing_arguments, global_options.profile.as_deref())?;
 
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
@@ -0,0 +1,35 @@
+use clap::Parser;
+use serde_json::json;
+use snafu::ResultExt;
+
+use crate::print_json;
+use crate::Error;
+
+#[derive(Parser, Debug)]
+pub struct VersionCommand {
+    /// Print the build metadata as JSON.
+    #[arg(long, default_value = "false")]
+    json: bool,
+}
+
+impl VersionCommand {
+    pub fn run(self) -> Result<(), Error> {
+        let version = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "git_hash": env!("PERIDIO_CLI_GIT_HASH"),
+            "target": env!("TARGET"),
+            "rustc_version": env!("PERIDIO_CLI_RUSTC_VERSION"),
+        });
+
+        if self.json {
+            print_json!(&version);
+        } else {
+            println!("peridio {}", env!("CARGO_PKG_VERSION"));
+            println!("commit: {}", env!("PERIDIO_CLI_GIT_HASH"));
+            println!("target: {}", env!("TARGET"));
+            println!("rustc: {}", env!("PERIDIO_CLI_RUSTC_VERSION"));
+        }
+
+        Ok(())
+    }
+}
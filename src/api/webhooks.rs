@@ -1,8 +1,10 @@
 use super::Command;
 use crate::api::list::ListArgs;
+use crate::print_created;
 use crate::print_json;
 use crate::utils::PRNType;
 use crate::utils::PRNValueParser;
+use crate::utils::Prn;
 use crate::ApiSnafu;
 use crate::Error;
 use crate::GlobalOptions;
@@ -62,14 +64,14 @@ pub struct CreateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Organization)
     )]
-    organization_prn: String,
+    organization_prn: Prn,
 }
 
 impl Command<CreateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = CreateWebhookParams {
             description: self.inner.description,
-            organization_prn: self.inner.organization_prn,
+            organization_prn: self.inner.organization_prn.to_string(),
             enabled_events: self.inner.enabled_events,
             url: self.inner.url,
         };
@@ -81,7 +83,7 @@ impl Command<CreateCommand> {
         });
 
         match api.webhooks().create(params).await.context(ApiSnafu)? {
-            Some(webhook) => print_json!(&webhook),
+            Some(webhook) => print_created!(&webhook),
             None => panic!(),
         }
 
@@ -97,25 +99,26 @@ pub struct ListCommand {
 
 impl Command<ListCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
-        let params = ListWebhooksParams {
-            limit: self.inner.list_args.limit,
-            order: self.inner.list_args.order,
-            search: self.inner.list_args.search,
-            page: self.inner.list_args.page,
-        };
-
         let api = Api::new(ApiOptions {
             api_key: global_options.api_key.unwrap(),
             endpoint: global_options.base_url,
             ca_bundle_path: global_options.ca_path,
         });
-
-        match api.webhooks().list(params).await.context(ApiSnafu)? {
-            Some(webhook) => print_json!(&webhook),
-            None => panic!(),
-        }
-
-        Ok(())
+        let api = &api;
+        let list_args = &self.inner.list_args;
+
+        list_args
+            .print(|page| {
+                let params = ListWebhooksParams {
+                    limit: list_args.limit,
+                    order: list_args.order.clone(),
+                    search: list_args.search.clone(),
+                    page,
+                };
+
+                async move { api.webhooks().list(params).await.context(ApiSnafu) }
+            })
+            .await
     }
 }
 
@@ -126,13 +129,13 @@ pub struct GetCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Webhook)
     )]
-    prn: String,
+    prn: Prn,
 }
 
 impl Command<GetCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = GetWebhookParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
@@ -213,7 +216,7 @@ pub struct UpdateCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Webhook)
     )]
-    prn: String,
+    prn: Prn,
     /// An arbitrary string attached to the resource. Often useful for displaying to users.
     #[arg(long)]
     pub description: Option<String>,
@@ -233,7 +236,7 @@ pub struct UpdateCommand {
 impl Command<UpdateCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = UpdateWebhookParams {
-            prn: self.inner.prn,
+            prn: self.inner.prn.to_string(),
             description: self.inner.description,
             enabled_events: self.inner.enabled_events,
             state: self.inner.state,
@@ -262,13 +265,13 @@ pub struct DeleteCommand {
         long,
         value_parser = PRNValueParser::new(PRNType::Webhook)
     )]
-    webhook_prn: String,
+    webhook_prn: Prn,
 }
 
 impl Command<DeleteCommand> {
     async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
         let params = DeleteWebhookParams {
-            webhook_prn: self.inner.webhook_prn,
+            webhook_prn: self.inner.webhook_prn.to_string(),
         };
 
         let api = Api::new(ApiOptions {
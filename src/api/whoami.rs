@@ -0,0 +1,142 @@
+use clap::Parser;
+use serde_json::{json, Value};
+use snafu::ResultExt;
+
+use super::client::{self, Client};
+use super::ApiError;
+use crate::print_json;
+use crate::utils::output::{self, OutputFormat};
+use crate::utils::{Style, StyledStr};
+use crate::Error;
+use crate::GlobalOptions;
+
+#[derive(Parser, Debug)]
+pub struct WhoamiCommand {}
+
+impl WhoamiCommand {
+    pub async fn run(self, global_options: GlobalOptions) -> Result<(), Error> {
+        let organization_name = global_options.organization_name.clone().unwrap();
+        let client = Client::new(&global_options)?;
+
+        let user = authorized(client.get("/users/me").await, &organization_name)?;
+        let organization = authorized(
+            client
+                .get(&client::organization_path(&organization_name))
+                .await,
+            &organization_name,
+        )?;
+
+        let identity = identity(&user, &organization, &organization_name);
+
+        match output::format() {
+            None | Some(OutputFormat::Human) => render(&identity).print_out().unwrap(),
+            _ => print_json!(&identity),
+        }
+
+        Ok(())
+    }
+}
+
+// a rejected API key fails with a message naming the organization instead of the raw response
+fn authorized(response: Result<Value, Error>, organization_name: &str) -> Result<Value, Error> {
+    match response {
+        Err(Error::Request {
+            source: ApiError::Unauthorized { status },
+            ..
+        }) => {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain(format!(
+                    "The API key is not authorized for the organization ({status}):\r\n"
+                ))
+                .warning(format!("\t{organization_name}"));
+
+            // NOPERM, like other rejected requests
+            Err(error.exit_err(77))
+        }
+        response => response,
+    }
+}
+
+// responses either wrap the resource in `data` or a key named after it
+fn resource<'a>(response: &'a Value, key: &str) -> &'a Value {
+    response
+        .get(key)
+        .or_else(|| response.get("data"))
+        .unwrap_or(response)
+}
+
+fn identity(user: &Value, organization: &Value, organization_name: &str) -> Value {
+    let user = resource(user, "user");
+    let organization = resource(organization, "organization");
+
+    json!({
+        "user": user.get("email").or_else(|| user.get("username")),
+        "organization_name": organization
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or(organization_name),
+        "organization_prn": organization.get("prn"),
+    })
+}
+
+fn render(identity: &Value) -> StyledStr {
+    let mut rendered = StyledStr::new();
+
+    for (index, (label, key)) in [
+        ("user", "user"),
+        ("organization", "organization_name"),
+        ("prn", "organization_prn"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if index > 0 {
+            rendered.plain("\r\n");
+        }
+
+        let value = identity[key].as_str().unwrap_or("-");
+        rendered.plain(format!("{label}: "));
+        rendered.push_str(Some(Style::Hint), value.to_string());
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_is_read_from_wrapped_responses() {
+        let identity = identity(
+            &json!({ "data": { "email": "user@example.com", "username": "user" } }),
+            &json!({ "organization": { "name": "example", "prn": "prn:1:org" } }),
+            "example",
+        );
+
+        assert_eq!(
+            identity,
+            json!({
+                "user": "user@example.com",
+                "organization_name": "example",
+                "organization_prn": "prn:1:org",
+            })
+        );
+    }
+
+    #[test]
+    fn missing_fields_are_null() {
+        let identity = identity(&json!({ "username": "user" }), &json!({}), "example");
+
+        assert_eq!(
+            identity,
+            json!({
+                "user": "user",
+                "organization_name": "example",
+                "organization_prn": null,
+            })
+        );
+    }
+}
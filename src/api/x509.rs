@@ -152,7 +152,7 @@ impl Command<CreateCommand> {
                         None,
                         " is unset or null, but is required by the --signer option.".to_string(),
                     );
-                    error.print_data_err();
+                    return Err(error.data_err());
                 }
             } else {
                 let mut error = StyledStr::new();
@@ -165,7 +165,7 @@ impl Command<CreateCommand> {
                     None,
                     " is unset or null, but is required by the --signer option.".to_string(),
                 );
-                error.print_data_err();
+                return Err(error.data_err());
             }
         } else if let (Some(signer_key_path), Some(signer_cert_path)) =
             (self.inner.signer_key, self.inner.signer_cert)
@@ -5,8 +5,13 @@ use crate::config::config_v2::ConfigV2;
 use crate::config::config_v2::ProfileV2;
 use crate::utils::{Style, StyledStr};
 use directories::ProjectDirs;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use self::config_v1::ConfigV1;
 
@@ -33,61 +38,58 @@ impl Config {
                 error.push_str(Some(Style::Warning), profile_name.to_string());
                 error.push_str(None, "'".to_string());
                 error.push_str(None, " not found.".to_string());
-                error.print_data_err()
+                Err(error.data_err())
             }
         }
     }
 
-    pub fn parse(config_directory: &Option<String>) -> Option<ConfigV2> {
-        // get directory
-        let mut config_dir_path = if let Some(config_dir) = config_directory {
-            let config_dir_path = PathBuf::from(config_dir);
+    /// Parse the config and credentials, from `config_file` when given and otherwise from
+    /// `config.json` in the config directory.
+    ///
+    /// Only a missing default config is `None`, an explicit `config_file` must exist.
+    pub fn parse(
+        config_directory: &Option<String>,
+        config_file: Option<&Path>,
+    ) -> Result<Option<ConfigV2>, crate::Error> {
+        let config_path = match config_file {
+            Some(config_file) => {
+                if !config_file.is_file() {
+                    let mut error = StyledStr::new();
+                    error
+                        .error("error: ")
+                        .plain("Config file does not exist:\r\n")
+                        .warning(format!("\t{}", config_file.display()));
+                    return Err(error.data_err());
+                }
 
-            if config_dir_path.exists() {
-                // use this config
-                config_dir_path
-            } else {
-                panic!("The provided config directory is invalid");
+                config_file.to_path_buf()
             }
-        } else if let Some(proj_dirs) = ProjectDirs::from("", "", "peridio") {
-            let cache_dir = proj_dirs.config_dir();
-
-            fs::create_dir_all(cache_dir).unwrap();
-
-            cache_dir.to_path_buf()
-        } else {
-            panic!("We can't determine your config path")
+            None => Self::directory(config_directory).join("config.json"),
         };
 
-        // get credentials
-        config_dir_path.push("credentials.json");
+        // credentials are kept next to the config
+        let credentials_path = config_path.with_file_name("credentials.json");
 
-        let credentials: HashMap<String, Credential> = if config_dir_path.exists() {
+        let credentials: HashMap<String, Credential> = if credentials_path.exists() {
             let credentials_file =
-                fs::read_to_string(&config_dir_path).expect("Cannot read credentials file");
-            serde_json::from_str(&credentials_file).expect("Cannot read credential file")
+                fs::read_to_string(&credentials_path).expect("Cannot read credentials file");
+            parse_file(&credentials_path, &credentials_file)?
         } else {
             HashMap::new()
         };
 
-        // get config
-        config_dir_path.pop();
-        config_dir_path.push("config.json");
-
-        if config_dir_path.exists() {
-            let config_file =
-                fs::read_to_string(&config_dir_path).expect("Cannot read config file");
+        if config_path.exists() {
+            let config_file = fs::read_to_string(&config_path).expect("Cannot read config file");
 
             if serde_json::from_str::<ConfigV1>(&config_file).is_ok() {
                 let mut error = StyledStr::new();
                 error.push_str(Some(Style::Error), "error: ".to_string());
                 error.push_str(None, "Your current config file is deprecated. Please upgrade your config by running:\r\n".to_string());
                 error.push_str(Some(Style::Success), "\tperidio config upgrade".to_string());
-                error.print_data_err();
+                return Err(error.data_err());
             }
 
-            let mut config: ConfigV2 =
-                serde_json::from_str(&config_file).expect("Cannot read config file");
+            let mut config: ConfigV2 = parse_file(&config_path, &config_file)?;
 
             for (profile_name, profile) in config.profiles.iter_mut() {
                 if let Some(credential) = credentials.get(profile_name) {
@@ -95,9 +97,45 @@ impl Config {
                 }
             }
 
-            Some(config)
+            Ok(Some(config))
         } else {
-            None
+            Ok(None)
+        }
+    }
+
+    fn directory(config_directory: &Option<String>) -> PathBuf {
+        if let Some(config_dir) = config_directory {
+            let config_dir_path = PathBuf::from(config_dir);
+
+            if config_dir_path.exists() {
+                // use this config
+                config_dir_path
+            } else {
+                panic!("The provided config directory is invalid");
+            }
+        } else if let Some(proj_dirs) = ProjectDirs::from("", "", "peridio") {
+            let cache_dir = proj_dirs.config_dir();
+
+            fs::create_dir_all(cache_dir).unwrap();
+
+            cache_dir.to_path_buf()
+        } else {
+            panic!("We can't determine your config path")
+        }
+    }
+}
+
+// fails with the path and the parse error rather than panicking on a malformed file
+fn parse_file<T: DeserializeOwned>(path: &Path, content: &str) -> Result<T, crate::Error> {
+    match serde_json::from_str(content) {
+        Ok(parsed) => Ok(parsed),
+        Err(source) => {
+            let mut error = StyledStr::new();
+            error
+                .error("error: ")
+                .plain("Config file is not valid:\r\n")
+                .warning(format!("\t{}: {source}", path.display()));
+            Err(error.data_err())
         }
     }
 }
@@ -4,26 +4,42 @@ mod utils;
 
 use std::process::ExitCode;
 use std::{
-    fmt,
-    io::{self, ErrorKind},
+    env,
+    ffi::OsString,
+    fmt, fs,
+    io::{self, ErrorKind, IsTerminal, Read},
     path::{self, PathBuf},
 };
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser};
 use config::Config;
-use snafu::Snafu;
+use reqwest::StatusCode;
+use snafu::{ResultExt, Snafu};
 
+use crate::api::ApiError;
 use crate::config::config_v2::{CertificateAuthoritiesV2, SigningKeyPairsV2};
-use crate::utils::{Style, StyledStr};
+use crate::utils::output::OutputFormat;
+use crate::utils::table::Columns;
+use crate::utils::{ColorMode, PRNType, PRNValueParser, Prn, Style, StyledStr};
 
 #[macro_export]
 #[allow(clippy::crate_in_macro_def)]
 macro_rules! print_json {
     ($v:expr) => {
-        println!(
-            "{}",
-            serde_json::to_string($v).context(crate::JsonSerializationSnafu)?
-        )
+        crate::utils::output::print_json(
+            &serde_json::to_value($v).context(crate::JsonSerializationSnafu)?,
+        )?
+    };
+}
+
+#[macro_export]
+#[allow(clippy::crate_in_macro_def)]
+macro_rules! print_created {
+    ($v:expr) => {
+        crate::utils::output::print_created(
+            &serde_json::to_value($v).context(crate::JsonSerializationSnafu)?,
+        )?
     };
 }
 
@@ -36,6 +52,40 @@ pub enum Error {
     #[snafu(display("Unable to serialize to JSON {}", source))]
     JsonSerialization { source: serde_json::Error },
 
+    #[snafu(display("Unable to serialize to YAML {}", source))]
+    YamlSerialization { source: serde_yaml::Error },
+
+    #[snafu(display("Request failed: {}", source))]
+    Http { source: reqwest::Error },
+
+    #[snafu(display("{}", source))]
+    Request {
+        source: ApiError,
+        request_id: Option<String>,
+    },
+
+    #[snafu(display(
+        "{} was changed by someone else during the update, fetch it again and retry",
+        path
+    ))]
+    UpdateConflict { path: String },
+
+    #[snafu(display(
+        "Gave up waiting after {}s, the state is still {}",
+        timeout.as_secs(),
+        state
+    ))]
+    WaitTimeout {
+        state: String,
+        timeout: std::time::Duration,
+    },
+
+    #[snafu(display("Invalid JSON in {}: {}", input, source))]
+    JsonInput {
+        input: String,
+        source: serde_json::Error,
+    },
+
     #[snafu(display("Unable to open file {}", source))]
     File { source: io::Error },
 
@@ -53,6 +103,13 @@ pub enum Error {
 
     #[snafu(display("Failed to parse date: {}", source))]
     DateParse { source: time::error::Parse },
+
+    #[snafu(display("--dry-run stopped the command before its first change"))]
+    DryRun,
+
+    // a failure explained to the user already, see `StyledStr::data_err`
+    #[snafu(display("{}", message.text()))]
+    Exit { message: StyledStr, code: u8 },
 }
 
 impl fmt::Debug for Error {
@@ -61,8 +118,44 @@ impl fmt::Debug for Error {
     }
 }
 
+/// The exit codes of failed commands, shown at the end of `--help`.
+const EXIT_CODES: &str = "\
+Exit codes:
+  1   Any other failure
+  2   Invalid arguments
+  65  Invalid input, or the API rejected the request (4xx) (DATAERR)
+  69  The resource was not found (404) (UNAVAILABLE)
+  75  Temporary failure worth retrying: rate limited (429), a server error (5xx), a timeout, a conflicting update or giving up --wait (TEMPFAIL)
+  77  The API key is missing permissions or invalid (401, 403) (NOPERM)";
+
+impl Error {
+    /// The sysexits style code the process exits with, so scripts can branch on the kind of
+    /// failure, see `EXIT_CODES`.
+    pub(crate) fn exit_code(&self) -> u8 {
+        match self {
+            Error::Request { source, .. } => source.exit_code(),
+            Error::Exit { code, .. } => *code,
+            Error::UpdateConflict { .. } | Error::WaitTimeout { .. } => 75,
+            Error::JsonInput { .. } | Error::NonExistingPath { .. } => 65,
+            _ => 1,
+        }
+    }
+
+    /// The status of the response the request failed with, if it got one.
+    pub(crate) fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Request { source, .. } => source.status(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Parser)]
-#[command(name = "peridio", version = env!("PERIDIO_CLI_VERSION"))]
+#[command(
+    name = "peridio",
+    version = env!("PERIDIO_CLI_VERSION"),
+    after_long_help = EXIT_CODES
+)]
 struct Program {
     #[command(flatten)]
     global_options: GlobalOptions,
@@ -76,7 +169,24 @@ pub struct GlobalOptions {
     #[arg(long, env = "PERIDIO_API_KEY", hide_env_values = true, short = 'a')]
     api_key: Option<String>,
 
-    #[arg(long, env = "PERIDIO_BASE_URL", short = 'b')]
+    /// Read the API key from a file, keeping it out of the process arguments and shell history.
+    #[arg(long, conflicts_with = "api_key")]
+    api_key_file: Option<PathBuf>,
+
+    /// Read the API key from stdin.
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["api_key", "api_key_file"]
+    )]
+    api_key_stdin: bool,
+
+    #[arg(
+        long,
+        env = "PERIDIO_BASE_URL",
+        short = 'b',
+        value_parser = utils::parse_base_url
+    )]
     base_url: Option<String>,
 
     #[arg(long, env = "PERIDIO_CA_PATH", short = 'c')]
@@ -85,6 +195,16 @@ pub struct GlobalOptions {
     #[arg(long, env = "PERIDIO_ORGANIZATION_NAME", short = 'o')]
     organization_name: Option<String>,
 
+    /// The PRN of the organization, for commands that address resources by PRN. Commands that
+    /// address the organization by name, such as devices or products, still require
+    /// --organization-name. Without either, the organization of the command's --prn is used.
+    #[arg(
+        long,
+        conflicts_with = "organization_name",
+        value_parser = PRNValueParser::new(PRNType::Organization)
+    )]
+    organization_prn: Option<Prn>,
+
     #[arg(long, env = "PERIDIO_PROFILE", short = 'p')]
     profile: Option<String>,
 
@@ -96,6 +216,167 @@ pub struct GlobalOptions {
     )]
     config_directory: Option<String>,
 
+    /// The config file to resolve --profile from, instead of config.json in the config directory.
+    ///
+    /// Credentials are read from credentials.json next to it.
+    #[arg(long, env = "PERIDIO_CONFIG", conflicts_with = "config_directory")]
+    config: Option<PathBuf>,
+
+    /// Format of the command output, inferred from the --output-file extension when omitted.
+    #[arg(long, env = "PERIDIO_OUTPUT", value_enum)]
+    output: Option<OutputFormat>,
+
+    /// Check that the backend is reachable and healthy before running the command.
+    #[arg(long, default_value = "false")]
+    preflight: bool,
+
+    /// Print only the items of list results, without the surrounding pagination metadata.
+    #[arg(long, default_value = "false")]
+    no_envelope: bool,
+
+    /// How long a request may take in total, such as 30s or 2m.
+    #[arg(long, value_parser = utils::parse_duration)]
+    timeout: Option<std::time::Duration>,
+
+    /// How long connecting to the API may take, such as 10s.
+    #[arg(long, value_parser = utils::parse_duration)]
+    connect_timeout: Option<std::time::Duration>,
+
+    /// How many times requests that only read are retried after a network error, a 429 or a 5xx response.
+    #[arg(long, default_value = "3")]
+    max_retries: u32,
+
+    /// The delay in milliseconds before the first retry, doubling with every further retry. A Retry-After header takes precedence.
+    #[arg(long, default_value = "500")]
+    retry_base_delay: u64,
+
+    /// Send at most this many requests per second, shared by every request of the invocation including --prn-file batches and --all pages. Bursts of up to this many requests are sent right away.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    rate_limit: Option<u32>,
+
+    /// Keep successful GET responses on disk and serve them again for --cache-ttl, revalidating older ones with their ETag.
+    #[arg(long, default_value = "false")]
+    cache: bool,
+
+    /// How long a cached response is served without asking the API, such as 30s or 5m.
+    #[arg(long, default_value = "60s", value_parser = utils::parse_duration)]
+    cache_ttl: std::time::Duration,
+
+    /// With --cache, ask the API again instead of serving cached responses, and cache the new ones.
+    #[arg(long, default_value = "false")]
+    no_cache: bool,
+
+    /// The directory cached responses are kept in, the user's cache directory by default.
+    #[arg(long, env = "PERIDIO_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Print the request a command that creates, changes or deletes something would send, without sending it. Requests that only read are still sent.
+    #[arg(long, default_value = "false")]
+    dry_run: bool,
+
+    /// Send this Idempotency-Key header with create requests, so retrying them cannot create duplicates. Pass auto to generate a random key, which retries of this invocation reuse. Meant for commands that create a single resource.
+    #[arg(long, value_parser = utils::parse_idempotency_key)]
+    idempotency_key: Option<String>,
+
+    /// Abort an update when someone else changed the resource first, instead of re-applying it. Only updates that read the resource to change it are guarded: organizations update, devices bulk, and devices update with --add-tag, --remove-tag or --metadata. Other updates only send the fields they were given, which a concurrent change can't make stale.
+    #[arg(long, default_value = "false")]
+    no_retry_conflict: bool,
+
+    /// Wrap JSON output in a versioned envelope with the result, pagination and warnings, see docs/output.md.
+    #[arg(long, default_value = "false", conflicts_with = "no_envelope")]
+    output_envelope: bool,
+
+    /// Indent JSON output even when stdout is not a terminal.
+    #[arg(long, default_value = "false", conflicts_with = "compact")]
+    pretty: bool,
+
+    /// Print JSON and YAML output on a single line even when stdout is a terminal, YAML in flow style.
+    #[arg(long, default_value = "false")]
+    compact: bool,
+
+    /// Print errors on a single line of key=value fields. Enabled by default with --output json or when stderr is not a terminal.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    compact_errors: Option<bool>,
+
+    /// Ask for missing required arguments, masking secrets such as the API key, instead of failing. Only applies when stdin is a terminal.
+    #[arg(long, default_value = "false")]
+    interactive: bool,
+
+    /// Suppress progress, status, warning and success messages, errors are still printed. Create commands print only the PRN of the created resource.
+    #[arg(long, short = 'q', default_value = "false")]
+    quiet: bool,
+
+    /// The columns of table and CSV output as a comma separated list of HEADER:FIELD or FIELD, where FIELD is a dotted path such as firmware_metadata.version.
+    #[arg(long, value_parser = utils::table::parse_columns)]
+    columns: Option<Columns>,
+
+    /// Only print these fields of results, as a comma separated list of dotted paths such as firmware.version.
+    #[arg(long, value_delimiter = ',')]
+    fields: Option<Vec<String>>,
+
+    /// Write the command output to a file instead of stdout.
+    #[arg(long)]
+    output_file: Option<PathBuf>,
+
+    /// Run the command once for each PRN in a newline-delimited file, passing each one as --prn.
+    #[arg(long)]
+    prn_file: Option<PathBuf>,
+
+    /// How many PRNs from --prn-file to process at the same time.
+    #[arg(long, default_value = "4")]
+    batch_concurrency: usize,
+
+    /// How many failed PRNs from --prn-file may be retried, shared across the whole batch.
+    #[arg(long, default_value = "0")]
+    batch_retry_budget: usize,
+
+    /// The W3C trace ID to send with requests, a random one is generated when omitted.
+    #[arg(
+        long,
+        env = "PERIDIO_TRACE_ID",
+        value_parser = utils::trace::parse_trace_id,
+        conflicts_with = "trace_parent"
+    )]
+    trace_id: Option<String>,
+
+    /// A full W3C traceparent header to send with requests.
+    #[arg(
+        long,
+        env = "PERIDIO_TRACE_PARENT",
+        value_parser = utils::trace::parse_traceparent
+    )]
+    trace_parent: Option<String>,
+
+    /// When to color messages on stderr.
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// Never color messages, the same as --color never.
+    #[arg(long, default_value = "false")]
+    no_color: bool,
+
+    /// Print diagnostic details to stderr: the traceparent of this invocation and the method, URL
+    /// and status of each request. Repeat as -vv to also print headers and truncated bodies, with
+    /// the Authorization header redacted.
+    #[arg(long, short = 'v', action = ArgAction::Count)]
+    verbose: u8,
+
+    // options only set through their environment variable, which a profile overrides
+    #[clap(skip)]
+    from_env: Vec<&'static str>,
+
+    // where the relay sends requests on to once it took the place of `base_url`, see `relay::start`
+    #[clap(skip)]
+    api_base_url: Option<String>,
+
+    // the relay the requests of the command go through, see `relay::start`
+    #[clap(skip)]
+    relay: Option<api::Relay>,
+
+    // the `--prn` the command addresses, whose organization stands in for a missing one
+    #[clap(skip)]
+    subject_prn: Option<Prn>,
+
     #[clap(skip)]
     signing_key_pairs: Option<SigningKeyPairsV2>,
 
@@ -103,7 +384,49 @@ pub struct GlobalOptions {
     certificate_authorities: Option<CertificateAuthoritiesV2>,
 }
 
+// the global options a profile can set
+const PROFILE_OPTIONS: [&str; 4] = ["api_key", "base_url", "ca_path", "organization_name"];
+
+impl GlobalOptions {
+    // flags win over profiles, which win over environment variables
+    fn profile_overrides(&self, id: &str, value: &Option<impl Sized>) -> bool {
+        value.is_none() || self.from_env.contains(&id)
+    }
+}
+
+// the `--prn` of the innermost subcommand, when it parses as a PRN
+fn subject_prn(matches: &ArgMatches) -> Option<Prn> {
+    let mut matches = matches;
+
+    while let Some((_, subcommand)) = matches.subcommand() {
+        matches = subcommand;
+    }
+
+    // commands without a --prn, or taking it as a plain string, fail the lookup
+    matches.try_get_one::<Prn>("prn").ok().flatten().cloned()
+}
+
 impl Program {
+    /// Parse the arguments like `try_parse_from`, remembering which options came from the
+    /// environment so a profile can take precedence over them.
+    fn try_parse_with_sources<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().try_get_matches_from(args)?;
+        let mut program =
+            Self::from_arg_matches(&matches).map_err(|error| error.format(&mut Self::command()))?;
+
+        program.global_options.from_env = PROFILE_OPTIONS
+            .into_iter()
+            .filter(|id| matches.value_source(id) == Some(ValueSource::EnvVariable))
+            .collect();
+        program.global_options.subject_prn = subject_prn(&matches);
+
+        Ok(program)
+    }
+
     async fn run(mut self) -> Result<(), Error> {
         if let Some(path) = &self.global_options.ca_path {
             if !path.exists() {
@@ -114,38 +437,105 @@ impl Program {
             }
         }
 
+        let color = utils::color_choice(self.global_options.color, self.global_options.no_color);
+        utils::set_color_choice(color);
+        utils::set_stdout_color_choice(utils::stdout_color_choice(
+            self.global_options.color,
+            self.global_options.no_color,
+        ));
+
+        // events are styled on stdout, which follows its own terminal unless colors were chosen
+        if self.global_options.no_color || self.global_options.color != ColorMode::Auto {
+            console::set_colors_enabled(color != termcolor::ColorChoice::Never);
+        }
+
+        utils::set_compact_errors(self.global_options.compact_errors.unwrap_or_else(|| {
+            self.global_options.output == Some(OutputFormat::Json) || !io::stderr().is_terminal()
+        }));
+
+        utils::output::init(&self.global_options)?;
+
+        let traceparent = utils::trace::init(
+            self.global_options.trace_id.clone(),
+            self.global_options.trace_parent.clone(),
+        );
+
+        utils::logging::init(self.global_options.verbose);
+        utils::rate_limit::init(self.global_options.rate_limit);
+
+        if self.global_options.verbose > 0 {
+            let mut message = StyledStr::new();
+            message.push_str(Some(Style::Warning), "traceparent: ".to_string());
+            message.push_str(None, traceparent.to_string());
+            message.print_err().unwrap();
+        }
+
+        if let Some(path) = &self.global_options.api_key_file {
+            let api_key = fs::read_to_string(path).context(NonExistingPathSnafu { path })?;
+            self.global_options.api_key = Some(api_key.trim().to_string());
+        }
+
+        if self.global_options.api_key_stdin {
+            let mut api_key = String::new();
+            io::stdin()
+                .read_to_string(&mut api_key)
+                .context(FileSnafu)?;
+            self.global_options.api_key = Some(api_key.trim().to_string());
+        }
+
         // parse config files if profile config is provided
 
         match self.command {
-            Command::CliCommand(api::CliCommands::Config(_)) => (),
+            Command::CliCommand(
+                api::CliCommands::Config(_)
+                | api::CliCommands::Completions(_)
+                | api::CliCommands::Version(_)
+                | api::CliCommands::Prn(_)
+                | api::CliCommands::GenerateManpage(_),
+            ) => {}
             _ => {
-                if let Some(config) = Config::parse(&self.global_options.config_directory) {
+                if let Some(config) = Config::parse(
+                    &self.global_options.config_directory,
+                    self.global_options.config.as_deref(),
+                )? {
                     if let Some(profile_name) = &self.global_options.profile {
-                        if let Ok(profile) = Config::get_profile(&config, profile_name) {
-                            // profile was provided
-                            if self.global_options.api_key.is_none() {
-                                if let Some(api_key) = profile.api_key {
-                                    self.global_options.api_key = Some(api_key);
-                                };
-                            }
-
-                            if self.global_options.base_url.is_none() {
-                                if let Some(base_url) = profile.base_url {
-                                    self.global_options.base_url = Some(base_url);
-                                };
+                        let profile = Config::get_profile(&config, profile_name)?;
+
+                        // profile was provided
+                        let options = &self.global_options;
+
+                        if options.profile_overrides("api_key", &options.api_key) {
+                            if let Some(api_key) = profile.api_key {
+                                self.global_options.api_key = Some(api_key);
+                            };
+                        }
+
+                        let options = &self.global_options;
+
+                        if options.profile_overrides("base_url", &options.base_url) {
+                            if let Some(base_url) = profile.base_url {
+                                self.global_options.base_url = Some(base_url);
                             };
+                        };
+
+                        let options = &self.global_options;
 
-                            if self.global_options.ca_path.is_none() {
-                                if let Some(ca_path) = profile.ca_path {
-                                    self.global_options.ca_path = Some(ca_path.into());
-                                };
+                        if options.profile_overrides("ca_path", &options.ca_path) {
+                            if let Some(ca_path) = profile.ca_path {
+                                self.global_options.ca_path = Some(ca_path.into());
                             };
+                        };
+
+                        let options = &self.global_options;
 
-                            if self.global_options.organization_name.is_none() {
-                                if let Some(organization_name) = profile.organization_name {
-                                    self.global_options.organization_name = Some(organization_name);
-                                };
-                            }
+                        // an explicit --organization-prn names the organization instead
+                        if options.organization_prn.is_none()
+                            && options
+                                .profile_overrides("organization_name", &options.organization_name)
+                        {
+                            if let Some(organization_name) = profile.organization_name {
+                                self.global_options.organization_name = Some(organization_name);
+                            };
                         }
                     }
 
@@ -172,11 +562,76 @@ enum Command {
 
 #[tokio::main]
 async fn main() -> ExitCode {
-    match Program::parse().run().await {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            utils::temp_file::remove_pending();
+
+            // 128 + SIGINT
+            std::process::exit(130);
+        }
+    });
+
+    let args: Vec<OsString> = env::args_os().collect();
+
+    if let Some(prn_file) = utils::batch::prn_file_arg(&args) {
+        return utils::batch::run(args, prn_file).await;
+    }
+
+    let interactive = utils::prompt::enabled(utils::prompt::interactive_arg(&args));
+    let program = utils::prompt::parse(
+        args,
+        interactive,
+        &mut utils::prompt::TerminalPrompter,
+        Program::try_parse_with_sources,
+    )
+    .unwrap_or_else(|error| error.exit());
+
+    match program.run().await.and_then(|()| utils::output::finish()) {
         Err(error) => {
-            match error {
+            utils::temp_file::remove_pending();
+
+            let code = error.exit_code();
+
+            let message = match error {
+                // rendered compact by `print_err_with_code` when compact errors are on
+                Error::Exit { message, .. } => message,
+
+                Error::JsonInput { input, source } => {
+                    let mut error = StyledStr::new();
+                    error.push_str(Some(Style::Error), "error: ".to_string());
+                    error.push_str(None, format!("Invalid JSON in {input}:\r\n"));
+                    error.push_str(Some(Style::Warning), format!("\t{source}"));
+                    error
+                }
+
+                error if utils::compact_errors() => {
+                    let mut fields =
+                        vec![("level", "error".to_string()), ("code", code.to_string())];
+
+                    if let Some(status) = error.status() {
+                        fields.push(("status", status.as_u16().to_string()));
+                    }
+
+                    if let Error::Request {
+                        request_id: Some(request_id),
+                        ..
+                    } = &error
+                    {
+                        fields.push(("request_id", request_id.clone()));
+                    }
+
+                    fields.push(("message", error.to_string().replace(['\r', '\n'], " ")));
+
+                    eprintln!("{}", utils::compact_line(&fields));
+
+                    return ExitCode::from(code);
+                }
+
+                // the SDK failed without the relay seeing a failed request, see `Relay::failure`
                 Error::Api { source } => {
-                    eprintln!("{source}")
+                    let mut error = StyledStr::new();
+                    error.error("error: ").plain(source.to_string());
+                    error
                 }
 
                 Error::NonExistingPath { path, source: _ } => {
@@ -184,13 +639,34 @@ async fn main() -> ExitCode {
                     error.push_str(Some(Style::Error), "error: ".to_string());
                     error.push_str(None, "Path does not exist:\r\n".to_string());
                     error.push_str(Some(Style::Warning), format!("\t{}", path.display()));
-                    error.print_data_err();
+                    error
                 }
 
-                error => eprintln!("Error: {error}"),
-            }
+                Error::Request { source, request_id } => {
+                    let mut error = source.styled();
+
+                    // support can look the request up by its ID
+                    if let Some(request_id) = request_id {
+                        error.plain("\r\nrequest id: ").warning(request_id);
+                    }
+
+                    error.plain("\r\n").push_str(
+                        Some(Style::Hint),
+                        format!("exit code {code}, see the exit codes in --help"),
+                    );
+                    error
+                }
+
+                error => {
+                    eprintln!("Error: {error}");
+
+                    return ExitCode::from(code);
+                }
+            };
+
+            message.print_err_with_code(Some(code)).unwrap();
 
-            ExitCode::FAILURE
+            ExitCode::from(code)
         }
         Ok(()) => ExitCode::SUCCESS,
     }
@@ -0,0 +1,214 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clap::Parser;
+use futures_util::{stream, StreamExt};
+
+use crate::utils::{output, temp_file, Style, StyledStr};
+use crate::{Error, Program};
+
+const PRN_FILE_ARG: &str = "--prn-file";
+
+// the exit codes of failures a PRN is retried after, see `EXIT_CODES`
+const RETRIED_EXIT_CODES: [u8; 2] = [69, 75];
+
+/// The value of `--prn-file` if it was passed to the top level command.
+pub fn prn_file_arg(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+
+        if arg == "--" {
+            break;
+        }
+
+        if arg == PRN_FILE_ARG {
+            return args.next().map(PathBuf::from);
+        }
+
+        if let Some(value) = arg.strip_prefix(&format!("{PRN_FILE_ARG}=")) {
+            return Some(PathBuf::from(value));
+        }
+    }
+
+    None
+}
+
+/// Run the command once per PRN listed in `prn_file`.
+///
+/// Every PRN is passed to the subcommand as `--prn`, so each one goes through the same parsing
+/// and validation (including the subcommand's `PRNValueParser`) as a single invocation would.
+pub async fn run(args: Vec<OsString>, prn_file: PathBuf) -> ExitCode {
+    let contents = match fs::read_to_string(&prn_file) {
+        Ok(contents) => contents,
+        Err(_) => {
+            let mut error = StyledStr::new();
+            error.push_str(Some(Style::Error), "error: ".to_string());
+            error.push_str(None, "Unable to read the PRN file:\r\n".to_string());
+            error.push_str(Some(Style::Warning), format!("\t{}", prn_file.display()));
+            error.print_err_with_code(Some(65)).unwrap();
+
+            // DATAERR
+            return ExitCode::from(65);
+        }
+    };
+
+    let prns: Vec<String> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect();
+
+    let base_args = without_prn_file(&args);
+    let total = prns.len();
+
+    let mut failures: Vec<(String, String)> = Vec::new();
+    let mut runnable: Vec<(String, Vec<OsString>)> = Vec::new();
+    let mut settings = None;
+
+    for prn in prns {
+        let mut prn_args = base_args.clone();
+        prn_args.push("--prn".into());
+        prn_args.push(prn.clone().into());
+
+        match Program::try_parse_with_sources(&prn_args) {
+            Ok(program) => {
+                settings.get_or_insert((
+                    program.global_options.batch_concurrency,
+                    program.global_options.batch_retry_budget,
+                ));
+                runnable.push((prn, prn_args));
+            }
+            Err(error) => failures.push((prn, first_line(&error.to_string()))),
+        }
+    }
+
+    let (concurrency, retry_budget) = settings.unwrap_or((1, 0));
+    let retry_budget = AtomicUsize::new(retry_budget);
+
+    let results = stream::iter(runnable)
+        .map(|(prn, prn_args)| {
+            let retry_budget = &retry_budget;
+
+            async move {
+                loop {
+                    let Some((code, message)) = run_prn(&prn_args).await else {
+                        return (prn, None);
+                    };
+
+                    // failures that would fail again are not retried, the budget is shared
+                    // across the whole batch
+                    if !RETRIED_EXIT_CODES.contains(&code)
+                        || retry_budget
+                            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |budget| {
+                                budget.checked_sub(1)
+                            })
+                            .is_err()
+                    {
+                        return (prn, Some(message));
+                    }
+                }
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    for (prn, failure) in results {
+        if let Some(message) = failure {
+            failures.push((prn, message));
+        }
+    }
+
+    // a shared --output-file is kept when at least one PRN produced output
+    if failures.len() < total {
+        if let Err(error) = output::finish() {
+            failures.push((prn_file.display().to_string(), error.to_string()));
+        }
+    } else {
+        temp_file::remove_pending();
+    }
+
+    let mut summary = StyledStr::new();
+
+    summary.push_str(Some(Style::Success), "success: ".to_string());
+    summary.push_str(
+        None,
+        format!("{} of {total} PRNs succeeded", total - failures.len()),
+    );
+
+    if !failures.is_empty() {
+        summary.push_str(None, "\r\n".to_string());
+        summary.push_str(Some(Style::Error), "error: ".to_string());
+        summary.push_str(None, format!("{} of {total} PRNs failed:", failures.len()));
+
+        for (prn, message) in failures.iter() {
+            summary.push_str(None, "\r\n\t".to_string());
+            summary.push_str(Some(Style::Warning), prn.to_string());
+            summary.push_str(None, format!(": {message}"));
+        }
+    }
+
+    summary.print_err().unwrap();
+
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// run the command for one PRN, returning the exit code and message it failed with
+async fn run_prn(prn_args: &[OsString]) -> Option<(u8, String)> {
+    // running a program consumes it, the arguments were already validated
+    let program = Program::try_parse_with_sources(prn_args).unwrap();
+
+    match program.run().await {
+        Ok(()) => None,
+        Err(Error::Exit { message, code }) => Some((code, one_line(&message.text()))),
+        Err(error) => Some((error.exit_code(), first_line(&error.to_string()))),
+    }
+}
+
+fn without_prn_file(args: &[OsString]) -> Vec<OsString> {
+    let mut result = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == PRN_FILE_ARG {
+            // drop the value as well
+            args.next();
+        } else if !arg
+            .to_string_lossy()
+            .starts_with(&format!("{PRN_FILE_ARG}="))
+        {
+            result.push(arg.to_owned());
+        }
+    }
+
+    result
+}
+
+fn first_line(message: &str) -> String {
+    message
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("error: ")
+        .to_string()
+}
+
+// a multi-line message on one line, without its level
+fn one_line(message: &str) -> String {
+    message
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_start_matches("error: ")
+        .to_string()
+}
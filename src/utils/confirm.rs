@@ -0,0 +1,142 @@
+use std::io::{self, IsTerminal, Write};
+
+use serde_json::{Map, Value};
+
+use crate::utils::{output, Style, StyledStr};
+
+/// Show the fields `changes` would modify on `current` and ask before applying them.
+///
+/// Returns `false` when nothing would change or the user declines. Without a terminal to ask
+/// on, `assume_yes` must be set, otherwise it fails instead of guessing.
+pub fn confirm_changes(
+    current: &Value,
+    changes: &Map<String, Value>,
+    assume_yes: bool,
+) -> Result<bool, crate::Error> {
+    let changed: Vec<(&String, &Value, &Value)> = changes
+        .iter()
+        .map(|(key, new)| (key, current.get(key).unwrap_or(&Value::Null), new))
+        .filter(|(_, old, new)| old != new)
+        .collect();
+
+    let mut diff = StyledStr::new();
+
+    if changed.is_empty() {
+        diff.push_str(Some(Style::Success), "success: ".to_string());
+        diff.push_str(None, "Nothing to change".to_string());
+        diff.print_err().unwrap();
+        return Ok(false);
+    }
+
+    diff.push_str(None, "The following changes will be applied:".to_string());
+
+    for (key, old, new) in changed {
+        diff.push_str(None, format!("\r\n\t{key}: "));
+        diff.push_str(Some(Style::Error), old.to_string());
+        diff.push_str(None, " -> ".to_string());
+        diff.push_str(Some(Style::Success), new.to_string());
+    }
+
+    diff.print_err().unwrap();
+
+    if assume_yes {
+        return Ok(true);
+    }
+
+    if !io::stdin().is_terminal() {
+        let mut error = StyledStr::new();
+        error.push_str(Some(Style::Error), "error: ".to_string());
+        error.push_str(
+            None,
+            "Refusing to apply changes without confirmation, pass --yes to skip the prompt"
+                .to_string(),
+        );
+        return Err(error.data_err());
+    }
+
+    Ok(ask("Apply these changes? [y/N] "))
+}
+
+/// Ask before deleting `resource`, such as `artifact prn:1:...`.
+///
+/// Returns `false` when the user declines. Without a terminal on both stdin and stdout, so when
+/// scripted or piped, `assume_yes` must be set, otherwise it fails instead of guessing.
+pub fn confirm_deletion(resource: &str, assume_yes: bool) -> Result<bool, crate::Error> {
+    if assume_yes {
+        return Ok(true);
+    }
+
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        let mut error = StyledStr::new();
+        error
+            .error("error: ")
+            .plain("Refusing to delete without confirmation, pass --yes to skip the prompt:\r\n")
+            .warning(format!("\t{resource}"));
+        return Err(error.data_err());
+    }
+
+    Ok(ask(&format!("Delete {resource}? [y/N] ")))
+}
+
+/// Show the `details` of the `resource` about to be uploaded, such as `firmware`, and ask first.
+///
+/// Returns `false` when the user declines. Without a terminal on both stdin and stdout there is
+/// no one to ask, and as uploading destroys nothing the upload goes ahead.
+pub fn confirm_upload(resource: &str, details: &[(&str, &str)], assume_yes: bool) -> bool {
+    let mut summary = StyledStr::new();
+    summary.plain(format!("The following {resource} will be uploaded:"));
+
+    for (name, value) in details {
+        summary
+            .plain(format!("\r\n\t{name}: "))
+            .push_str(Some(Style::Hint), value.to_string());
+    }
+
+    if !output::quiet() {
+        summary.print_err().unwrap();
+    }
+
+    if assume_yes || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return true;
+    }
+
+    ask(&format!("Upload this {resource}? [y/N] "))
+}
+
+// prompt on stderr and read the answer, anything but yes declines
+fn ask(question: &str) -> bool {
+    eprint!("{question}");
+    io::stderr().flush().unwrap();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    is_yes(&answer)
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_yes_confirms() {
+        for answer in ["y\n", "Y\n", "yes\n", " YES \n"] {
+            assert!(is_yes(answer), "{answer:?}");
+        }
+
+        for answer in ["\n", "n\n", "no\n", "yess\n", ""] {
+            assert!(!is_yes(answer), "{answer:?}");
+        }
+    }
+
+    #[test]
+    fn assume_yes_skips_the_prompt() {
+        assert!(confirm_deletion("artifact prn:1:test", true).unwrap());
+    }
+}
@@ -0,0 +1,117 @@
+use serde_json::Value;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::utils::output;
+
+/// How far a certificate is from its `not_after`.
+#[derive(Debug, PartialEq)]
+enum Expiry {
+    Valid,
+    ExpiresIn(i64),
+    Expired,
+}
+
+/// Warn on stderr when the certificate in a get response expires within `warn_days`, and more
+/// loudly when it has already expired.
+///
+/// Responses without a parsable `not_after` are left alone.
+pub fn warn_if_expiring(response: &Value, warn_days: u32) {
+    let Some(not_after) = not_after(response) else {
+        return;
+    };
+
+    let date = not_after.format(&Rfc3339).unwrap();
+
+    match expiry(not_after, OffsetDateTime::now_utc(), warn_days) {
+        Expiry::Valid => (),
+        Expiry::ExpiresIn(days) => {
+            output::warn(format!("The certificate expires in {days} days, at {date}"))
+        }
+        Expiry::Expired => output::warn_urgent(format!("The certificate expired at {date}")),
+    }
+}
+
+// the certificate is either the response itself or wrapped in it, as in `ca_certificate`
+fn not_after(response: &Value) -> Option<OffsetDateTime> {
+    let not_after = response.get("not_after").or_else(|| {
+        response
+            .as_object()?
+            .values()
+            .find_map(|value| value.get("not_after"))
+    })?;
+
+    OffsetDateTime::parse(not_after.as_str()?, &Rfc3339).ok()
+}
+
+fn expiry(not_after: OffsetDateTime, now: OffsetDateTime, warn_days: u32) -> Expiry {
+    if not_after <= now {
+        Expiry::Expired
+    } else if not_after - now <= Duration::days(warn_days.into()) {
+        Expiry::ExpiresIn((not_after - now).whole_days())
+    } else {
+        Expiry::Valid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    const NOW: OffsetDateTime = datetime!(2024-06-01 12:00 UTC);
+
+    #[test]
+    fn healthy_certificates_do_not_warn() {
+        assert_eq!(
+            expiry(datetime!(2025-06-01 0:00 UTC), NOW, 30),
+            Expiry::Valid
+        );
+        assert_eq!(
+            expiry(datetime!(2024-07-02 0:00 UTC), NOW, 30),
+            Expiry::Valid
+        );
+    }
+
+    #[test]
+    fn certificates_near_expiry_warn() {
+        assert_eq!(
+            expiry(datetime!(2024-06-21 12:00 UTC), NOW, 30),
+            Expiry::ExpiresIn(20)
+        );
+        assert_eq!(
+            expiry(datetime!(2024-07-01 12:00 UTC), NOW, 30),
+            Expiry::ExpiresIn(30)
+        );
+        assert_eq!(
+            expiry(datetime!(2024-06-21 12:00 UTC), NOW, 7),
+            Expiry::Valid
+        );
+    }
+
+    #[test]
+    fn expired_certificates_warn_urgently() {
+        assert_eq!(expiry(NOW, NOW, 30), Expiry::Expired);
+        assert_eq!(
+            expiry(datetime!(2020-01-01 0:00 UTC), NOW, 0),
+            Expiry::Expired
+        );
+    }
+
+    #[test]
+    fn not_after_is_found_in_wrapped_responses() {
+        let expected = Some(datetime!(2030-01-01 0:00 UTC));
+
+        assert_eq!(
+            not_after(&json!({ "not_after": "2030-01-01T00:00:00Z" })),
+            expected
+        );
+        assert_eq!(
+            not_after(&json!({ "ca_certificate": { "not_after": "2030-01-01T00:00:00Z" } })),
+            expected
+        );
+        assert_eq!(not_after(&json!({ "not_after": "soon" })), None);
+        assert_eq!(not_after(&json!({})), None);
+    }
+}
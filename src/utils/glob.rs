@@ -0,0 +1,69 @@
+/// Whether `text` matches the shell-style `pattern`, where `*` matches any run of characters,
+/// `?` any single character and every other character itself.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // the last `*` and where in `text` its run ends for now, to backtrack to on a mismatch
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRN: &str = "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef:device:3a1ad4e6";
+
+    #[test]
+    fn prefixes_match() {
+        assert!(matches("prn:1:be4d30b4-*", PRN));
+        assert!(!matches("prn:1:00000000-*", PRN));
+    }
+
+    #[test]
+    fn suffixes_match() {
+        assert!(matches("*:device:3a1ad4e6", PRN));
+        assert!(!matches("*:binary:3a1ad4e6", PRN));
+    }
+
+    #[test]
+    fn stars_match_any_run_and_question_marks_one_character() {
+        assert!(matches("*", PRN));
+        assert!(matches("*", ""));
+        assert!(matches("prn:1:*:device:*", PRN));
+        assert!(matches("prn:1:*:device:3a1ad4e?", PRN));
+        assert!(matches("prn:?:*", PRN));
+        assert!(!matches("prn:??:*", PRN));
+        assert!(!matches("prn:1:*:device:3a1ad4e", PRN));
+    }
+
+    #[test]
+    fn without_wildcards_the_whole_text_has_to_match() {
+        assert!(matches(PRN, PRN));
+        assert!(!matches("prn:1", PRN));
+    }
+}
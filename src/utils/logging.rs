@@ -0,0 +1,89 @@
+use reqwest::header::{HeaderMap, AUTHORIZATION};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+// bodies logged at -vv are cut off after this many bytes
+const MAX_BODY_LENGTH: usize = 1024;
+
+/// Log requests to stderr for `-v` and `-vv`, stdout stays reserved for the command's output.
+///
+/// `-v` logs the method, URL and status of each request, `-vv` also logs headers and bodies.
+/// Only the CLI's own events are logged, not those of its dependencies. Only the first call has
+/// an effect, so every PRN of a `--prn-file` batch logs the same way.
+pub fn init(verbose: u8) {
+    let level = match verbose {
+        0 => return,
+        1 => LevelFilter::INFO,
+        _ => LevelFilter::DEBUG,
+    };
+
+    // the global subscriber can only be set once
+    let _ = tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_ansi(false)
+                .with_target(false)
+                .without_time(),
+        )
+        .with(Targets::new().with_target(env!("CARGO_CRATE_NAME"), level))
+        .try_init();
+}
+
+/// The headers one per line, with the value of `Authorization` redacted.
+pub fn headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if name == AUTHORIZATION {
+                "[redacted]"
+            } else {
+                value.to_str().unwrap_or("[binary]")
+            };
+
+            format!("{name}: {value}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The body as text, truncated to a length that keeps the log readable.
+pub fn body(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&body[..body.len().min(MAX_BODY_LENGTH)]);
+
+    if body.len() > MAX_BODY_LENGTH {
+        format!("{text}… ({} bytes)", body.len())
+    } else {
+        text.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorization_is_redacted() {
+        let mut map = HeaderMap::new();
+        map.insert(AUTHORIZATION, "Bearer secret-key".parse().unwrap());
+        map.insert("traceparent", "00-abc".parse().unwrap());
+
+        let logged = headers(&map);
+
+        assert!(!logged.contains("secret-key"), "{logged}");
+        assert!(logged.contains("authorization: [redacted]"), "{logged}");
+        assert!(logged.contains("traceparent: 00-abc"), "{logged}");
+    }
+
+    #[test]
+    fn long_bodies_are_truncated() {
+        assert_eq!(body(b"{}"), "{}");
+
+        let logged = body(&[b'a'; 2000]);
+
+        assert!(logged.starts_with(&"a".repeat(MAX_BODY_LENGTH)));
+        assert!(logged.ends_with("… (2000 bytes)"));
+    }
+}
@@ -1,11 +1,121 @@
+pub mod batch;
+pub mod confirm;
+pub mod expiry;
+pub mod glob;
+pub mod logging;
+pub mod output;
+pub mod prompt;
+pub mod rate_limit;
 pub mod serde_introspection;
+pub mod table;
+pub mod temp_file;
+pub mod trace;
+pub mod wait;
 
 use clap::error::{ContextKind, ContextValue, ErrorKind};
 use serde_json::{Map, Value};
-use std::io::Write;
-use termcolor::WriteColor;
+use snafu::ResultExt;
+use std::io::{IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use termcolor::{ColorChoice, WriteColor};
 use uuid::Uuid;
 
+// color messages on stderr, see `color_choice`
+static COLORS: AtomicBool = AtomicBool::new(true);
+
+// color messages on stdout, see `stdout_color_choice`
+static STDOUT_COLORS: AtomicBool = AtomicBool::new(true);
+
+/// When to color messages, see `color_choice`.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum ColorMode {
+    /// Color messages on a terminal, unless NO_COLOR is set or TERM is dumb.
+    Auto,
+    /// Always color messages.
+    Always,
+    /// Never color messages.
+    Never,
+}
+
+/// Resolve `--color` and `--no-color`, the latter always wins.
+pub fn color_choice(mode: ColorMode, no_color: bool) -> ColorChoice {
+    match mode {
+        _ if no_color => ColorChoice::Never,
+        ColorMode::Always => ColorChoice::Always,
+        ColorMode::Never => ColorChoice::Never,
+        ColorMode::Auto => auto_color_choice(std::io::stderr().is_terminal()),
+    }
+}
+
+/// Like `color_choice`, but `auto` follows whether stdout is a terminal, so results redirected to
+/// a file stay plain while messages on the terminal are colored.
+pub fn stdout_color_choice(mode: ColorMode, no_color: bool) -> ColorChoice {
+    match mode {
+        ColorMode::Auto if !no_color => auto_color_choice(std::io::stdout().is_terminal()),
+        mode => color_choice(mode, no_color),
+    }
+}
+
+fn auto_color_choice(terminal: bool) -> ColorChoice {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+
+    if no_color || dumb || !terminal {
+        ColorChoice::Never
+    } else {
+        ColorChoice::Always
+    }
+}
+
+pub fn set_color_choice(choice: ColorChoice) {
+    COLORS.store(choice != ColorChoice::Never, Ordering::Relaxed);
+}
+
+pub fn set_stdout_color_choice(choice: ColorChoice) {
+    STDOUT_COLORS.store(choice != ColorChoice::Never, Ordering::Relaxed);
+}
+
+/// Whether results on stdout are colored, see `stdout_color_choice`.
+pub fn stdout_colors() -> bool {
+    STDOUT_COLORS.load(Ordering::Relaxed)
+}
+
+fn color(colors: &AtomicBool) -> ColorChoice {
+    if colors.load(Ordering::Relaxed) {
+        ColorChoice::Always
+    } else {
+        ColorChoice::Never
+    }
+}
+
+// render errors on a single line, see `set_compact_errors`
+static COMPACT_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Render every error and warning as a single `key=value` line, for log aggregators that group
+/// messages by line.
+pub fn set_compact_errors(compact: bool) {
+    COMPACT_ERRORS.store(compact, Ordering::Relaxed);
+}
+
+pub fn compact_errors() -> bool {
+    COMPACT_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Join `key=value` pairs into one line, quoting values with spaces or quotes.
+pub fn compact_line(fields: &[(&str, String)]) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| {
+            if value.is_empty() || value.contains([' ', '"', '=']) {
+                format!("{key}={value:?}")
+            } else {
+                format!("{key}={value}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 pub struct StyledStr {
     messages: Vec<(Option<Style>, String)>,
 }
@@ -23,48 +133,138 @@ impl StyledStr {
         }
     }
 
+    /// Append `msg` styled as an error.
+    pub fn error(&mut self, msg: impl Into<String>) -> &mut Self {
+        self.push_str(Some(Style::Error), msg.into());
+        self
+    }
+
+    /// Append `msg` styled as a warning.
+    pub fn warning(&mut self, msg: impl Into<String>) -> &mut Self {
+        self.push_str(Some(Style::Warning), msg.into());
+        self
+    }
+
+    /// Append `msg` styled as a success.
+    pub fn success(&mut self, msg: impl Into<String>) -> &mut Self {
+        self.push_str(Some(Style::Success), msg.into());
+        self
+    }
+
+    /// Append `msg` without styling.
+    pub fn plain(&mut self, msg: impl Into<String>) -> &mut Self {
+        self.push_str(None, msg.into());
+        self
+    }
+
+    /// Append the fragments of `other`, keeping their styles.
+    pub fn append(&mut self, other: StyledStr) -> &mut Self {
+        self.messages.extend(other.messages);
+        self
+    }
+
     pub fn print_err(&self) -> std::io::Result<()> {
-        let bufwtr = termcolor::BufferWriter::stderr(termcolor::ColorChoice::Always);
+        self.print_err_with_code(None)
+    }
+
+    /// Print the message as an error, with the `code` the command exits with in compact errors.
+    pub fn print_err_with_code(&self, code: Option<u8>) -> std::io::Result<()> {
+        if compact_errors() {
+            return writeln!(std::io::stderr(), "{}", self.compact(code));
+        }
+
+        let bufwtr = termcolor::BufferWriter::stderr(color(&COLORS));
         let mut buffer = bufwtr.buffer();
 
-        for (style, message) in &self.messages {
-            let mut color = termcolor::ColorSpec::new();
-            match style {
-                Some(Style::Success) => {
-                    color.set_fg(Some(termcolor::Color::Green));
-                }
-                Some(Style::Warning) => {
-                    color.set_fg(Some(termcolor::Color::Yellow));
-                }
-                Some(Style::Error) => {
-                    color.set_fg(Some(termcolor::Color::Red));
-                    color.set_bold(true);
-                }
-                None => {}
-            }
+        self.write_styled(&mut buffer)?;
+        bufwtr.print(&buffer)?;
 
-            buffer.set_color(&color)?;
-            write!(buffer, "{message}")?;
-        }
+        Ok(())
+    }
+
+    /// Print the message to stdout, for results as opposed to diagnostics.
+    pub fn print_out(&self) -> std::io::Result<()> {
+        let bufwtr = termcolor::BufferWriter::stdout(color(&STDOUT_COLORS));
+        let mut buffer = bufwtr.buffer();
 
-        write!(buffer, "\r\n")?;
+        self.write_styled(&mut buffer)?;
         bufwtr.print(&buffer)?;
 
         Ok(())
     }
 
-    pub fn print_data_err(&self) -> ! {
-        self.print_err().unwrap();
+    fn write_styled(&self, buffer: &mut termcolor::Buffer) -> std::io::Result<()> {
+        for (style, message) in &self.messages {
+            buffer.set_color(&color_spec(style.as_ref()))?;
+            write!(buffer, "{message}")?;
+        }
+
+        buffer.reset()?;
+        write!(buffer, "\r\n")
+    }
+
+    /// The message as the error of a command that exits with DATAERR, printed once it reaches
+    /// `main` or the batch, see `Error::exit_code`.
+    pub fn data_err(self) -> crate::Error {
+        self.exit_err(65)
+    }
+
+    /// The message as the error of a command that exits with TEMPFAIL, see `data_err`.
+    pub fn temp_fail_err(self) -> crate::Error {
+        self.exit_err(75)
+    }
 
-        // DATAERR
-        std::process::exit(65)
+    /// The message as the error of a command that exits with `code`, see `data_err`.
+    pub fn exit_err(self, code: u8) -> crate::Error {
+        crate::Error::Exit {
+            message: self,
+            code,
+        }
     }
 
-    pub fn print_success(&self) -> ! {
-        self.print_err().unwrap();
+    /// Print the message to stdout unless `--quiet` was passed.
+    pub fn print_success(&self) -> std::io::Result<()> {
+        if output::quiet() {
+            return Ok(());
+        }
 
-        // SUCCESS
-        std::process::exit(0)
+        self.print_out()
+    }
+}
+
+impl StyledStr {
+    /// The message without styling.
+    pub fn text(&self) -> String {
+        self.messages
+            .iter()
+            .map(|(_, message)| message.as_str())
+            .collect()
+    }
+
+    // the message without styling or line breaks, led by its level
+    fn compact(&self, code: Option<u8>) -> String {
+        let text = self.text();
+
+        let (level, message) = ["error", "warning", "success"]
+            .iter()
+            .find_map(|level| {
+                text.strip_prefix(&format!("{level}: "))
+                    .map(|message| (*level, message))
+            })
+            .unwrap_or(("info", text.as_str()));
+
+        let mut fields = vec![("level", level.to_string())];
+
+        if let Some(code) = code {
+            fields.push(("code", code.to_string()));
+        }
+
+        fields.push((
+            "message",
+            message.split_whitespace().collect::<Vec<_>>().join(" "),
+        ));
+
+        compact_line(&fields)
     }
 }
 
@@ -72,16 +272,198 @@ pub enum Style {
     Success,
     Warning,
     Error,
+    /// Secondary text such as field labels, dimmed.
+    Hint,
+    /// URLs and paths, underlined.
+    Link,
+    /// Keys of highlighted JSON, blue.
+    Key,
 }
 
-pub fn maybe_json(data: Option<String>) -> Option<Map<String, Value>> {
-    if let Some(json) = data {
-        match serde_json::from_str(json.as_str()) {
-            Ok(json_data) => Some(json_data),
-            Err(_e) => None,
+// the terminal attributes of a style, buffers without color ignore them
+fn color_spec(style: Option<&Style>) -> termcolor::ColorSpec {
+    let mut color = termcolor::ColorSpec::new();
+    match style {
+        Some(Style::Success) => {
+            color.set_fg(Some(termcolor::Color::Green));
+        }
+        Some(Style::Warning) => {
+            color.set_fg(Some(termcolor::Color::Yellow));
+        }
+        Some(Style::Error) => {
+            color.set_fg(Some(termcolor::Color::Red));
+            color.set_bold(true);
+        }
+        Some(Style::Hint) => {
+            color.set_dimmed(true);
         }
+        Some(Style::Link) => {
+            color.set_underline(true);
+        }
+        Some(Style::Key) => {
+            color.set_fg(Some(termcolor::Color::Blue));
+        }
+        None => {}
+    }
+    color
+}
+
+/// Parse an `--idempotency-key`, where `auto` generates a random key for this invocation.
+pub fn parse_idempotency_key(value: &str) -> Result<String, String> {
+    match value.trim() {
+        "" => Err("expected a key or auto".to_string()),
+        "auto" => Ok(Uuid::new_v4().to_string()),
+        key => Ok(key.to_string()),
+    }
+}
+
+/// Parse a duration such as `90s`, `15m`, `2h` or `1d`, a bare number is taken as seconds.
+pub fn parse_duration(value: &str) -> Result<std::time::Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("expected a duration such as 30s, 15m, 2h or 1d, got '{value}'"))?;
+
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "unknown duration unit '{unit}', expected s, m, h or d"
+            ))
+        }
+    };
+
+    amount
+        .checked_mul(seconds)
+        .map(std::time::Duration::from_secs)
+        .ok_or_else(|| format!("the duration '{value}' is too long"))
+}
+
+/// Parse a point in time given as RFC 3339, such as `2024-06-01T12:00:00Z`, or as a duration
+/// before now, such as `24h`.
+pub fn parse_time(value: &str) -> Result<time::OffsetDateTime, String> {
+    parse_time_at(value, time::OffsetDateTime::now_utc())
+}
+
+fn parse_time_at(value: &str, now: time::OffsetDateTime) -> Result<time::OffsetDateTime, String> {
+    if let Ok(time) =
+        time::OffsetDateTime::parse(value.trim(), &time::format_description::well_known::Rfc3339)
+    {
+        return Ok(time);
+    }
+
+    let ago = parse_duration(value).map_err(|_| {
+        format!("expected an RFC 3339 time or a duration such as 30m or 24h, got '{value}'")
+    })?;
+
+    now.checked_sub(time::Duration::try_from(ago).map_err(|error| error.to_string())?)
+        .ok_or_else(|| format!("'{value}' is too far in the past"))
+}
+
+/// Check that a `--base-url` is an absolute HTTP or HTTPS URL.
+pub fn parse_base_url(value: &str) -> Result<String, String> {
+    let url = reqwest::Url::parse(value).map_err(|error| format!("invalid URL: {error}"))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "expected an http or https URL, got '{}'",
+            url.scheme()
+        ));
+    }
+
+    Ok(value.to_string())
+}
+
+/// Deep-merge `patch` into `target`, see `--merge-data`.
+///
+/// Objects are merged key by key and a null in `patch` removes the key. Any other value replaces
+/// the one in `target`, except that arrays are appended to when `concat_arrays` is set.
+pub fn merge_json(target: &mut Value, patch: &Value, concat_arrays: bool) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                if value.is_null() {
+                    target.remove(key);
+                } else {
+                    // new objects are merged into an empty one, so their nulls are dropped too
+                    let empty = if value.is_object() {
+                        Value::Object(Map::new())
+                    } else {
+                        Value::Null
+                    };
+
+                    merge_json(
+                        target.entry(key.clone()).or_insert(empty),
+                        value,
+                        concat_arrays,
+                    );
+                }
+            }
+        }
+        (Value::Array(target), Value::Array(patch)) if concat_arrays => {
+            target.extend(patch.iter().cloned());
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+/// Parse a JSON object given inline, or read from a file when prefixed with `@` such as
+/// `@payload.json`. `@-` reads it from stdin until EOF.
+///
+/// Unlike silently dropping input that doesn't parse, errors name the file or a snippet of the
+/// inline value so the mistake can be found.
+pub fn maybe_json_from_source(
+    data: Option<String>,
+) -> Result<Option<Map<String, Value>>, crate::Error> {
+    json_from_source(data, &mut std::io::stdin())
+}
+
+fn json_from_source(
+    data: Option<String>,
+    stdin: &mut impl Read,
+) -> Result<Option<Map<String, Value>>, crate::Error> {
+    let Some(data) = data else {
+        return Ok(None);
+    };
+
+    let (json, input) = match data.strip_prefix('@') {
+        Some("-") => {
+            let mut json = String::new();
+            stdin.read_to_string(&mut json).context(crate::FileSnafu)?;
+
+            (json, "stdin".to_string())
+        }
+        Some(path) => (
+            std::fs::read_to_string(path).context(crate::NonExistingPathSnafu { path })?,
+            path.to_string(),
+        ),
+        None => (data.clone(), snippet(&data)),
+    };
+
+    serde_json::from_str(&json)
+        .map(Some)
+        .context(crate::JsonInputSnafu { input })
+}
+
+// enough of an inline value to recognize it in an error message
+fn snippet(value: &str) -> String {
+    const SNIPPET_LENGTH: usize = 40;
+
+    if value.chars().count() > SNIPPET_LENGTH {
+        format!(
+            "'{}...'",
+            value.chars().take(SNIPPET_LENGTH).collect::<String>()
+        )
     } else {
-        None
+        format!("'{value}'")
     }
 }
 
@@ -168,124 +550,221 @@ impl TryFrom<String> for PRNType {
     }
 }
 
-#[derive(Clone, PartialEq)]
-pub struct PRNValueParser(PRNType);
+impl PRNType {
+    /// The name of the type within a PRN.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::APIKey => "api_key",
+            Self::Artifact => "artifact",
+            Self::ArtifactVersion => "artifact_version",
+            Self::AuditLog => "audit_log",
+            Self::Binary => "binary",
+            Self::BinaryPart => "binary_part",
+            Self::BinarySignature => "binary_signature",
+            Self::Bundle => "bundle",
+            Self::BundleOverride => "bundle_override",
+            Self::CACertificate => "ca_certificate",
+            Self::Cohort => "cohort",
+            Self::Deployment => "deployment",
+            Self::Device => "device",
+            Self::DeviceCertificate => "device_certificate",
+            Self::Event => "event",
+            Self::Firmware => "firmware",
+            Self::OrgUser => "org_user",
+            Self::Organization => "organization",
+            Self::Product => "product",
+            Self::Release => "release",
+            Self::ReleaseClaim => "release_claim",
+            Self::SigningKey => "signing_key",
+            Self::Tunnel => "tunnel",
+            Self::User => "user",
+            Self::WebConsoleShell => "web_console_shell",
+            Self::Webhook => "webhook",
+            Self::UserToken => "user_token",
+        }
+    }
 
-impl PRNValueParser {
-    pub fn new(prn_type: PRNType) -> Self {
-        Self(prn_type)
+    // users and their tokens exist outside of any organization
+    fn is_user_level(&self) -> bool {
+        matches!(self, Self::User | Self::UserToken)
     }
 }
 
-impl clap::builder::TypedValueParser for PRNValueParser {
-    type Value = String;
-
-    fn parse_ref(
-        &self,
-        cmd: &clap::Command,
-        arg: Option<&clap::Arg>,
-        value: &std::ffi::OsStr,
-    ) -> Result<Self::Value, clap::Error> {
-        let value: String = value.to_str().unwrap().to_owned();
+/// A parsed PRN, its `Display` is the canonical string.
+///
+/// The accepted shapes are
+///
+/// - `prn:1:<organization uuid>` for organizations,
+/// - `prn:1:<type>:<uuid>` for users and user tokens,
+/// - `prn:1:<organization uuid>:<type>:<uuid>` for every other resource.
+///
+/// An organization PRN has an `org_id` but no `resource_id`, user level PRNs have a
+/// `resource_id` but no `org_id`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Prn {
+    pub version: u8,
+    pub org_id: Option<Uuid>,
+    pub resource_type: PRNType,
+    pub resource_id: Option<Uuid>,
+}
 
-        let mut split = value.split(':').fuse();
+impl Prn {
+    /// The PRN of the organization the resource belongs to.
+    pub fn organization(&self) -> Option<Prn> {
+        self.org_id.map(|org_id| Prn {
+            version: self.version,
+            org_id: Some(org_id),
+            resource_type: PRNType::Organization,
+            resource_id: None,
+        })
+    }
+}
 
-        let prn_length = split.clone().count();
+impl std::fmt::Display for Prn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "prn:{}", self.version)?;
 
-        if !(3..=5).contains(&prn_length) {
-            return Err(prn_error(cmd, arg, "Invalid PRN"));
+        if let Some(org_id) = self.org_id {
+            write!(f, ":{org_id}")?;
         }
 
-        if split.next().is_some_and(|x| x != "prn") {
-            return Err(prn_error(cmd, arg, "Invalid PRN"));
+        if let Some(resource_id) = self.resource_id {
+            write!(f, ":{}:{resource_id}", self.resource_type.name())?;
         }
 
-        if split.next().is_some_and(|x| x != "1") {
-            return Err(prn_error(cmd, arg, "Invalid PRN"));
-        }
+        Ok(())
+    }
+}
 
-        match prn_length {
-            3 => {
-                // organization prn only
-                if self.0 != PRNType::Organization {
-                    return Err(prn_error(cmd, arg, "Invalid PRN type"));
-                }
-                // the uuid has to be valid
-                if Uuid::try_parse(split.next().unwrap()).is_err() {
-                    return Err(prn_error(
-                        cmd,
-                        arg,
-                        "Invalid PRN UUID, expected 'organization' UUID in PRN",
-                    ));
-                }
+impl std::str::FromStr for Prn {
+    type Err = String;
 
-                0
-            }
-            4 => {
-                // user or user token
-                if self.0 != PRNType::User || self.0 != PRNType::UserToken {
-                    return Err(prn_error(cmd, arg, "Invalid PRN type"));
-                }
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let segments: Vec<&str> = value.split(':').collect();
 
-                let prn_type = PRNType::try_from(split.next().unwrap().to_string());
+        let ["prn", "1", segments @ ..] = segments.as_slice() else {
+            return Err("Invalid PRN".to_string());
+        };
 
-                if prn_type.is_err() {
-                    return Err(prn_error(cmd, arg, "Invalid PRN type"));
-                }
+        let uuid =
+            |segment: &str, error: &str| Uuid::try_parse(segment).map_err(|_| error.to_string());
 
-                let prn_type = prn_type.unwrap();
+        let prn_type = |segment: &str| {
+            PRNType::try_from(segment.to_string()).map_err(|error| error.to_string())
+        };
 
-                if prn_type != PRNType::User || prn_type != PRNType::UserToken {
-                    return Err(prn_error(
-                        cmd,
-                        arg,
-                        "Invalid PRN type, expected 'user' or 'user_token' PRN",
-                    ));
+        let (org_id, resource_type, resource_id) = match segments {
+            [organization] => (
+                Some(uuid(
+                    organization,
+                    "Invalid PRN UUID, expected 'organization' UUID in PRN",
+                )?),
+                PRNType::Organization,
+                None,
+            ),
+            [resource_type, resource] => {
+                let resource_type = prn_type(resource_type)?;
+
+                if !resource_type.is_user_level() {
+                    return Err("Invalid PRN, expected organization UUID in PRN".to_string());
                 }
 
-                0
-            }
-            5 => {
-                // the org uuid has to be valid
-                if Uuid::try_parse(split.next().unwrap()).is_err() {
-                    return Err(prn_error(
-                        cmd,
-                        arg,
+                (
+                    None,
+                    resource_type,
+                    Some(uuid(
+                        resource,
                         "Invalid PRN UUID, expected valid UUID in PRN",
+                    )?),
+                )
+            }
+            [organization, resource_type, resource] => {
+                let org_id = uuid(organization, "Invalid PRN UUID, expected valid UUID in PRN")?;
+                let resource_type = prn_type(resource_type)?;
+
+                if resource_type.is_user_level() || resource_type == PRNType::Organization {
+                    return Err(format!(
+                        "Invalid PRN, '{}' PRNs have no organization UUID",
+                        resource_type.name()
                     ));
                 }
 
-                let prn_type = PRNType::try_from(split.next().unwrap().to_string());
+                (
+                    Some(org_id),
+                    resource_type,
+                    Some(uuid(
+                        resource,
+                        "Invalid PRN UUID, expected valid UUID in PRN",
+                    )?),
+                )
+            }
+            _ => return Err("Invalid PRN".to_string()),
+        };
 
-                if prn_type.is_err() {
-                    return Err(prn_error(cmd, arg, "Invalid PRN type"));
-                }
+        Ok(Prn {
+            version: 1,
+            org_id,
+            resource_type,
+            resource_id,
+        })
+    }
+}
 
-                let prn_type = prn_type.unwrap();
+impl serde::Serialize for Prn {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
 
-                if self.0 != prn_type {
-                    return Err(prn_error(
-                        cmd,
-                        arg,
-                        format!("Invalid PRN type, expected '{:#?}' PRN", self.0).as_str(),
-                    ));
-                }
+/// Parse `value` as a PRN of one of the `expected` types, see [`Prn`] for the accepted shapes.
+fn validate_prn(expected: &[PRNType], value: &str) -> Result<Prn, String> {
+    let prn: Prn = value.parse()?;
 
-                // the uuid has to be valid
-                if Uuid::try_parse(split.next().unwrap()).is_err() {
-                    return Err(prn_error(
-                        cmd,
-                        arg,
-                        "Invalid PRN UUID, expected valid UUID in PRN",
-                    ));
-                }
+    if !expected.contains(&prn.resource_type) {
+        let expected = match expected {
+            [prn_type] => format!("'{prn_type:#?}'"),
+            prn_types => {
+                let prn_types: Vec<String> = prn_types
+                    .iter()
+                    .map(|prn_type| format!("'{prn_type:#?}'"))
+                    .collect();
 
-                0
+                format!("one of {}", prn_types.join(", "))
             }
-            _ => return Err(prn_error(cmd, arg, "Invalid PRN")),
         };
 
-        Ok(value)
+        return Err(format!("Invalid PRN type, expected {expected} PRN"));
+    }
+
+    Ok(prn)
+}
+
+#[derive(Clone, PartialEq)]
+pub struct PRNValueParser(Vec<PRNType>);
+
+impl PRNValueParser {
+    pub fn new(prn_type: PRNType) -> Self {
+        Self(vec![prn_type])
+    }
+
+    /// Accept a PRN of any of the `prn_types`, for flags that take more than one kind of resource.
+    pub fn any_of(prn_types: Vec<PRNType>) -> Self {
+        Self(prn_types)
+    }
+}
+
+impl clap::builder::TypedValueParser for PRNValueParser {
+    type Value = Prn;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        let value: String = value.to_str().unwrap().to_owned();
+
+        validate_prn(&self.0, &value).map_err(|error| prn_error(cmd, arg, &error))
     }
 }
 
@@ -361,3 +840,483 @@ impl clap::builder::TypedValueParser for PRNValueParser {
 //         Ok(result)
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::builder::TypedValueParser;
+    use serde_json::json;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn objects_are_merged_deeply() {
+        let mut target = json!({ "site": { "city": "berlin", "floor": 2 }, "rack": "a" });
+
+        merge_json(&mut target, &json!({ "site": { "floor": 3 } }), false);
+
+        assert_eq!(
+            target,
+            json!({ "site": { "city": "berlin", "floor": 3 }, "rack": "a" })
+        );
+    }
+
+    #[test]
+    fn arrays_are_replaced_unless_concatenated() {
+        let patch = json!({ "sensors": ["humidity"] });
+
+        let mut replaced = json!({ "sensors": ["temperature"] });
+        merge_json(&mut replaced, &patch, false);
+        assert_eq!(replaced, json!({ "sensors": ["humidity"] }));
+
+        let mut concatenated = json!({ "sensors": ["temperature"] });
+        merge_json(&mut concatenated, &patch, true);
+        assert_eq!(
+            concatenated,
+            json!({ "sensors": ["temperature", "humidity"] })
+        );
+    }
+
+    #[test]
+    fn nulls_remove_keys() {
+        let mut target = json!({ "site": { "city": "berlin", "floor": 2 }, "rack": "a" });
+
+        merge_json(
+            &mut target,
+            &json!({ "site": { "floor": null }, "rack": null, "new": { "a": 1, "b": null } }),
+            false,
+        );
+
+        assert_eq!(
+            target,
+            json!({ "site": { "city": "berlin" }, "new": { "a": 1 } })
+        );
+    }
+
+    fn json_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{name}", Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    fn parse(prn_type: PRNType, value: &str) -> Result<Prn, clap::Error> {
+        PRNValueParser::new(prn_type).parse_ref(
+            &clap::Command::new("test"),
+            None,
+            OsStr::new(value),
+        )
+    }
+
+    #[test]
+    fn user_prn_is_valid() {
+        let prn = "prn:1:user:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+        assert_eq!(parse(PRNType::User, prn).unwrap().to_string(), prn);
+    }
+
+    #[test]
+    fn user_token_prn_is_valid() {
+        let prn = "prn:1:user_token:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+        assert_eq!(parse(PRNType::UserToken, prn).unwrap().to_string(), prn);
+    }
+
+    #[test]
+    fn device_prn_is_rejected_for_user() {
+        let prn = "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef:device:3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+        assert!(parse(PRNType::User, prn).is_err());
+    }
+
+    const ORGANIZATION: &str = "be4d30b4-de6b-47cd-85ea-a75e23fd63ef";
+    const RESOURCE: &str = "3a1ad4e6-6a4f-4a6c-9f0e-84c1b8a7e2f1";
+
+    #[test]
+    fn every_prn_type_is_validated() {
+        let prn_types = [
+            (PRNType::APIKey, "api_key"),
+            (PRNType::Artifact, "artifact"),
+            (PRNType::ArtifactVersion, "artifact_version"),
+            (PRNType::AuditLog, "audit_log"),
+            (PRNType::Binary, "binary"),
+            (PRNType::BinaryPart, "binary_part"),
+            (PRNType::BinarySignature, "binary_signature"),
+            (PRNType::Bundle, "bundle"),
+            (PRNType::BundleOverride, "bundle_override"),
+            (PRNType::CACertificate, "ca_certificate"),
+            (PRNType::Cohort, "cohort"),
+            (PRNType::Deployment, "deployment"),
+            (PRNType::Device, "device"),
+            (PRNType::DeviceCertificate, "device_certificate"),
+            (PRNType::Event, "event"),
+            (PRNType::Firmware, "firmware"),
+            (PRNType::OrgUser, "org_user"),
+            (PRNType::Organization, "organization"),
+            (PRNType::Product, "product"),
+            (PRNType::Release, "release"),
+            (PRNType::ReleaseClaim, "release_claim"),
+            (PRNType::SigningKey, "signing_key"),
+            (PRNType::Tunnel, "tunnel"),
+            (PRNType::User, "user"),
+            (PRNType::WebConsoleShell, "web_console_shell"),
+            (PRNType::Webhook, "webhook"),
+            (PRNType::UserToken, "user_token"),
+        ];
+
+        for (prn_type, name) in prn_types {
+            let (valid, invalid) = match prn_type {
+                PRNType::Organization => (
+                    format!("prn:1:{ORGANIZATION}"),
+                    vec![
+                        "prn:1:not-a-uuid".to_string(),
+                        format!("prn:1:{ORGANIZATION}:device:{RESOURCE}"),
+                    ],
+                ),
+                PRNType::User | PRNType::UserToken => (
+                    format!("prn:1:{name}:{RESOURCE}"),
+                    vec![
+                        format!("prn:1:{name}:not-a-uuid"),
+                        format!("prn:1:{ORGANIZATION}:{name}:{RESOURCE}"),
+                    ],
+                ),
+                _ => (
+                    format!("prn:1:{ORGANIZATION}:{name}:{RESOURCE}"),
+                    vec![
+                        format!("prn:1:{ORGANIZATION}:{name}:not-a-uuid"),
+                        format!("prn:1:not-a-uuid:{name}:{RESOURCE}"),
+                        format!("prn:1:{ORGANIZATION}:unknown:{RESOURCE}"),
+                        format!("prn:1:{ORGANIZATION}"),
+                        format!("prn:2:{ORGANIZATION}:{name}:{RESOURCE}"),
+                    ],
+                ),
+            };
+
+            assert!(
+                validate_prn(&[prn_type.clone()], &valid).is_ok(),
+                "{prn_type:?} {valid}"
+            );
+
+            for invalid in invalid {
+                assert!(
+                    validate_prn(&[prn_type.clone()], &invalid).is_err(),
+                    "{prn_type:?} {invalid}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn prn_of_another_type_is_rejected() {
+        let error = validate_prn(
+            &[PRNType::Bundle],
+            &format!("prn:1:{ORGANIZATION}:device:{RESOURCE}"),
+        )
+        .unwrap_err();
+
+        assert_eq!(error, "Invalid PRN type, expected 'Bundle' PRN");
+    }
+
+    #[test]
+    fn prn_matching_one_of_several_types_is_valid() {
+        let parser = PRNValueParser::any_of(vec![PRNType::Artifact, PRNType::Binary]);
+        let prn = format!("prn:1:{ORGANIZATION}:binary:{RESOURCE}");
+
+        let parsed = parser
+            .parse_ref(&clap::Command::new("test"), None, OsStr::new(&prn))
+            .unwrap();
+
+        assert_eq!(parsed.resource_type, PRNType::Binary);
+    }
+
+    #[test]
+    fn prn_matching_none_of_several_types_is_rejected() {
+        let error = validate_prn(
+            &[PRNType::Artifact, PRNType::Binary],
+            &format!("prn:1:{ORGANIZATION}:device:{RESOURCE}"),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error,
+            "Invalid PRN type, expected one of 'Artifact', 'Binary' PRN"
+        );
+    }
+
+    #[test]
+    fn prns_round_trip() {
+        for prn in [
+            format!("prn:1:{ORGANIZATION}"),
+            format!("prn:1:user:{RESOURCE}"),
+            format!("prn:1:user_token:{RESOURCE}"),
+            format!("prn:1:{ORGANIZATION}:device:{RESOURCE}"),
+            format!("prn:1:{ORGANIZATION}:artifact_version:{RESOURCE}"),
+        ] {
+            assert_eq!(prn.parse::<Prn>().unwrap().to_string(), prn);
+        }
+    }
+
+    #[test]
+    fn prn_fields_are_extracted() {
+        let organization = Uuid::parse_str(ORGANIZATION).unwrap();
+        let resource = Uuid::parse_str(RESOURCE).unwrap();
+
+        let prn: Prn = format!("prn:1:{ORGANIZATION}").parse().unwrap();
+        assert_eq!(prn.version, 1);
+        assert_eq!(prn.org_id, Some(organization));
+        assert_eq!(prn.resource_type, PRNType::Organization);
+        assert_eq!(prn.resource_id, None);
+
+        let prn: Prn = format!("prn:1:user_token:{RESOURCE}").parse().unwrap();
+        assert_eq!(prn.org_id, None);
+        assert_eq!(prn.resource_type, PRNType::UserToken);
+        assert_eq!(prn.resource_id, Some(resource));
+
+        let prn: Prn = format!("prn:1:{ORGANIZATION}:binary:{RESOURCE}")
+            .parse()
+            .unwrap();
+        assert_eq!(prn.org_id, Some(organization));
+        assert_eq!(prn.resource_type, PRNType::Binary);
+        assert_eq!(prn.resource_id, Some(resource));
+        assert_eq!(
+            prn.organization().unwrap().to_string(),
+            format!("prn:1:{ORGANIZATION}")
+        );
+    }
+
+    fn styled(buffer: &mut termcolor::Buffer) -> String {
+        let mut message = StyledStr::new();
+        message.push_str(Some(Style::Error), "error: ".to_string());
+        message.push_str(Some(Style::Warning), "colored".to_string());
+        message.write_styled(buffer).unwrap();
+
+        String::from_utf8(buffer.as_slice().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn without_color_no_ansi_codes_are_written() {
+        assert!(styled(&mut termcolor::Buffer::ansi()).contains('\x1b'));
+        assert_eq!(
+            styled(&mut termcolor::Buffer::no_color()),
+            "error: colored\r\n"
+        );
+    }
+
+    #[test]
+    fn styles_set_their_attributes() {
+        let success = color_spec(Some(&Style::Success));
+        assert_eq!(success.fg(), Some(&termcolor::Color::Green));
+
+        let warning = color_spec(Some(&Style::Warning));
+        assert_eq!(warning.fg(), Some(&termcolor::Color::Yellow));
+
+        let error = color_spec(Some(&Style::Error));
+        assert_eq!(error.fg(), Some(&termcolor::Color::Red));
+        assert!(error.bold());
+
+        let hint = color_spec(Some(&Style::Hint));
+        assert!(hint.dimmed() && hint.fg().is_none());
+
+        let link = color_spec(Some(&Style::Link));
+        assert!(link.underline() && link.fg().is_none());
+
+        let key = color_spec(Some(&Style::Key));
+        assert_eq!(key.fg(), Some(&termcolor::Color::Blue));
+
+        assert!(color_spec(None).is_none());
+    }
+
+    #[test]
+    fn hints_and_links_are_plain_without_color() {
+        let mut message = StyledStr::new();
+        message.push_str(Some(Style::Hint), "docs: ".to_string());
+        message.push_str(Some(Style::Link), "https://docs.peridio.com".to_string());
+
+        let mut buffer = termcolor::Buffer::no_color();
+        message.write_styled(&mut buffer).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.into_inner()).unwrap(),
+            "docs: https://docs.peridio.com\r\n"
+        );
+    }
+
+    #[test]
+    fn helpers_chain() {
+        let mut message = StyledStr::new();
+        message
+            .error("error: ")
+            .plain("missing ")
+            .success("--api-key");
+
+        let mut buffer = termcolor::Buffer::no_color();
+        message.write_styled(&mut buffer).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.into_inner()).unwrap(),
+            "error: missing --api-key\r\n"
+        );
+        assert!(matches!(message.messages[0], (Some(Style::Error), _)));
+        assert!(matches!(message.messages[1], (None, _)));
+        assert!(matches!(message.messages[2], (Some(Style::Success), _)));
+    }
+
+    #[test]
+    fn append_keeps_styles() {
+        let mut details = StyledStr::new();
+        details.warning("\tdetails");
+
+        let mut message = StyledStr::new();
+        message
+            .error("error: ")
+            .plain("failed:\r\n")
+            .append(details);
+
+        assert_eq!(message.messages.len(), 3);
+        assert!(
+            matches!(&message.messages[2], (Some(Style::Warning), text) if text == "\tdetails")
+        );
+    }
+
+    #[test]
+    fn no_color_flag_wins() {
+        assert_eq!(color_choice(ColorMode::Always, true), ColorChoice::Never);
+        assert_eq!(color_choice(ColorMode::Auto, true), ColorChoice::Never);
+        assert_eq!(color_choice(ColorMode::Never, false), ColorChoice::Never);
+        assert_eq!(color_choice(ColorMode::Always, false), ColorChoice::Always);
+    }
+
+    #[test]
+    fn stdout_colors_follow_explicit_choices() {
+        assert_eq!(
+            stdout_color_choice(ColorMode::Auto, true),
+            ColorChoice::Never
+        );
+        assert_eq!(
+            stdout_color_choice(ColorMode::Never, false),
+            ColorChoice::Never
+        );
+        assert_eq!(
+            stdout_color_choice(ColorMode::Always, false),
+            ColorChoice::Always
+        );
+    }
+
+    #[test]
+    fn absent_json_is_none() {
+        assert!(maybe_json_from_source(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn inline_json_is_parsed() {
+        let json = maybe_json_from_source(Some(r#"{"version": "1.0.0"}"#.to_string()))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(json["version"], "1.0.0");
+    }
+
+    #[test]
+    fn json_file_is_read() {
+        let path = json_file("payload.json", r#"{"version": "1.0.0"}"#);
+
+        let json = maybe_json_from_source(Some(format!("@{path}")))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(json["version"], "1.0.0");
+    }
+
+    #[test]
+    fn json_is_read_from_stdin() {
+        let mut stdin = r#"{"version": "1.0.0"}"#.as_bytes();
+
+        let json = json_from_source(Some("@-".to_string()), &mut stdin)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(json["version"], "1.0.0");
+    }
+
+    #[test]
+    fn malformed_stdin_is_named() {
+        let mut stdin = "[1, 2".as_bytes();
+
+        let error = json_from_source(Some("@-".to_string()), &mut stdin).unwrap_err();
+
+        assert!(error.to_string().contains("stdin"), "{error}");
+    }
+
+    #[test]
+    fn missing_json_file_names_the_path() {
+        let error = maybe_json_from_source(Some("@does-not-exist.json".to_string())).unwrap_err();
+
+        assert!(error.to_string().contains("does-not-exist.json"));
+    }
+
+    #[test]
+    fn malformed_json_names_the_input() {
+        let path = json_file("malformed.json", "{\"version\": ");
+
+        let error = maybe_json_from_source(Some(format!("@{path}"))).unwrap_err();
+        assert!(error.to_string().contains(&path));
+
+        let error = maybe_json_from_source(Some("{version: 1}".to_string())).unwrap_err();
+        assert!(error.to_string().contains("'{version: 1}'"));
+    }
+
+    #[test]
+    fn auto_idempotency_key_is_random() {
+        let key = parse_idempotency_key("auto").unwrap();
+
+        assert!(Uuid::parse_str(&key).is_ok(), "{key}");
+        assert_ne!(key, parse_idempotency_key("auto").unwrap());
+        assert_eq!(parse_idempotency_key("given").unwrap(), "given");
+        assert!(parse_idempotency_key(" ").is_err());
+    }
+
+    #[test]
+    fn absolute_times_are_parsed() {
+        let now = time::macros::datetime!(2024-06-01 12:00 UTC);
+
+        assert_eq!(
+            parse_time_at("2024-05-01T08:30:00Z", now).unwrap(),
+            time::macros::datetime!(2024-05-01 8:30 UTC)
+        );
+        assert_eq!(
+            parse_time_at("2024-05-01T08:30:00+02:00", now).unwrap(),
+            time::macros::datetime!(2024-05-01 6:30 UTC)
+        );
+    }
+
+    #[test]
+    fn relative_times_are_resolved_against_now() {
+        let now = time::macros::datetime!(2024-06-01 12:00 UTC);
+
+        assert_eq!(
+            parse_time_at("24h", now).unwrap(),
+            time::macros::datetime!(2024-05-31 12:00 UTC)
+        );
+        assert_eq!(
+            parse_time_at("15m", now).unwrap(),
+            time::macros::datetime!(2024-06-01 11:45 UTC)
+        );
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        let now = time::macros::datetime!(2024-06-01 12:00 UTC);
+
+        for value in ["yesterday", "2024-06-01", "24x", "", "999999999999d"] {
+            assert!(parse_time_at(value, now).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn durations_that_overflow_are_rejected() {
+        assert_eq!(
+            parse_duration("2h"),
+            Ok(std::time::Duration::from_secs(2 * 60 * 60))
+        );
+        assert!(parse_duration("999999999999999999d").is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+    }
+}
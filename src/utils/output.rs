@@ -0,0 +1,759 @@
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use indicatif::ProgressBar;
+
+use serde_json::{json, Value};
+use snafu::ResultExt;
+
+use crate::utils::table::{self, Column};
+use crate::utils::temp_file::TempFile;
+use crate::utils::{self, Style, StyledStr};
+use crate::{Error, FileSnafu, GlobalOptions, JsonSerializationSnafu, YamlSerializationSnafu};
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum OutputFormat {
+    /// Render results for people reading a terminal.
+    Human,
+    /// Render results as JSON.
+    Json,
+    /// Render results as indented JSON.
+    PrettyJson,
+    /// Render list results as one JSON document per line.
+    Ndjson,
+    /// Render list results as comma separated values with a header row.
+    Csv,
+    /// Render results as YAML.
+    Yaml,
+    /// Render list results as an aligned table, see --columns.
+    Table,
+}
+
+impl OutputFormat {
+    fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "json" => Some(Self::Json),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            "csv" => Some(Self::Csv),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+struct Settings {
+    format: Option<OutputFormat>,
+    quiet: bool,
+    columns: Option<Vec<Column>>,
+    fields: Option<Vec<String>>,
+    no_envelope: bool,
+    output_envelope: bool,
+    // `--pretty` or `--compact`, `None` to decide by the format and terminal
+    pretty: Option<bool>,
+    output_file: Option<Mutex<Option<TempFile>>>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// The version of the `--output-envelope` structure, see `docs/output.md`.
+///
+/// Fields are only ever added within a version, removing or changing one bumps it.
+pub const OUTPUT_VERSION: u64 = 1;
+
+// draw progress bars, see `progress_bar`
+static PROGRESS: AtomicBool = AtomicBool::new(true);
+
+// warnings of this invocation, repeated in the envelope for consumers that don't read stderr
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Resolve the output settings for this process.
+///
+/// An explicit `--output` always wins, otherwise the format is inferred from the extension of
+/// `--output-file`. Only the first call has an effect.
+pub fn init(global_options: &GlobalOptions) -> Result<(), Error> {
+    if SETTINGS.get().is_some() {
+        return Ok(());
+    }
+
+    let output_file = global_options.output_file.as_deref();
+
+    let format = match (global_options.output, output_file) {
+        (Some(format), _) => Some(format),
+        (None, Some(path)) => {
+            let inferred = OutputFormat::from_extension(path);
+
+            if inferred.is_none() {
+                warn(format!(
+                    "Unrecognized --output-file extension, pass --output to choose a format: {}",
+                    path.display()
+                ));
+            }
+
+            inferred
+        }
+        (None, None) => None,
+    };
+
+    if global_options.quiet
+        || global_options.no_color
+        || matches!(
+            format,
+            Some(OutputFormat::Json | OutputFormat::PrettyJson | OutputFormat::Ndjson)
+        )
+    {
+        disable_progress();
+    }
+
+    let output_file = match output_file {
+        Some(path) => Some(Mutex::new(Some(TempFile::new(path).context(FileSnafu)?))),
+        None => None,
+    };
+
+    let _ = SETTINGS.set(Settings {
+        format,
+        quiet: global_options.quiet,
+        columns: global_options.columns.clone().map(|columns| columns.0),
+        fields: global_options.fields.clone(),
+        no_envelope: global_options.no_envelope,
+        output_envelope: global_options.output_envelope,
+        pretty: match (global_options.pretty, global_options.compact) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        output_file,
+    });
+
+    Ok(())
+}
+
+/// The resolved output format, `None` when the default should be used.
+pub fn format() -> Option<OutputFormat> {
+    SETTINGS.get().and_then(|settings| settings.format)
+}
+
+/// Print a warning to stderr unless `--quiet` was passed, and keep it for the
+/// `--output-envelope`.
+pub fn warn(message: String) {
+    warn_with(Style::Warning, message)
+}
+
+/// Like `warn`, but styled as an error for problems that need attention now.
+pub fn warn_urgent(message: String) {
+    warn_with(Style::Error, message)
+}
+
+fn warn_with(style: Style, message: String) {
+    if !quiet() {
+        let mut warning = StyledStr::new();
+        warning.push_str(Some(style), "warning: ".to_string());
+        warning.push_str(None, message.clone());
+        warning.print_err().unwrap();
+    }
+
+    WARNINGS.lock().unwrap().push(message);
+}
+
+pub fn quiet() -> bool {
+    SETTINGS.get().is_some_and(|settings| settings.quiet)
+}
+
+/// Stop drawing progress bars for the rest of the process.
+pub fn disable_progress() {
+    PROGRESS.store(false, Ordering::Relaxed);
+}
+
+/// A progress bar of `length` on stderr, hidden when stderr is not a terminal or progress was
+/// disabled by `--quiet`, `--no-color` or JSON output.
+pub fn progress_bar(length: u64) -> ProgressBar {
+    if progress_shown() {
+        ProgressBar::new(length)
+    } else {
+        ProgressBar::hidden()
+    }
+}
+
+fn progress_shown() -> bool {
+    PROGRESS.load(Ordering::Relaxed) && std::io::stderr().is_terminal()
+}
+
+/// A spinner on stderr for waits of unknown length, hidden like `progress_bar`.
+pub fn spinner() -> ProgressBar {
+    if progress_shown() {
+        let spinner = ProgressBar::new_spinner();
+        spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+        spinner
+    } else {
+        ProgressBar::hidden()
+    }
+}
+
+/// Print a progress or status message to stderr unless `--quiet` was passed.
+pub fn status(message: &str) {
+    if !quiet() {
+        eprintln!("{message}");
+    }
+}
+
+/// Move the `--output-file` into place once the command has succeeded.
+pub fn finish() -> Result<(), Error> {
+    if let Some(output_file) = SETTINGS.get().and_then(|s| s.output_file.as_ref()) {
+        if let Some(temp_file) = output_file.lock().unwrap().take() {
+            temp_file.persist().context(FileSnafu)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Render `value` in the resolved format to stdout or `--output-file`.
+pub fn print_json(value: &Value) -> Result<(), Error> {
+    let unwrapped;
+    let value = match envelope_items(value) {
+        Some(items) if SETTINGS.get().is_some_and(|settings| settings.no_envelope) => {
+            unwrapped = Value::Array(items.clone());
+            &unwrapped
+        }
+        _ => value,
+    };
+
+    let projected;
+    let value = match SETTINGS.get().and_then(|settings| settings.fields.as_ref()) {
+        Some(fields) => {
+            let (value, unknown) = project_response(value, fields);
+
+            for field in unknown {
+                warn(format!("Unknown field: {field}"));
+            }
+
+            projected = value;
+            &projected
+        }
+        None => value,
+    };
+
+    let rendered = match format() {
+        format @ (None
+        | Some(OutputFormat::Human)
+        | Some(OutputFormat::Json)
+        | Some(OutputFormat::PrettyJson)) => {
+            let enveloped;
+            let value = if SETTINGS
+                .get()
+                .is_some_and(|settings| settings.output_envelope)
+            {
+                enveloped = envelope(value);
+                &enveloped
+            } else {
+                value
+            };
+
+            let pretty = pretty();
+
+            if format == Some(OutputFormat::Json) && pretty != Some(false) && highlight() {
+                return highlighted(value).print_out().context(FileSnafu);
+            }
+
+            if pretty.unwrap_or(format == Some(OutputFormat::PrettyJson)) {
+                serde_json::to_string_pretty(value).context(JsonSerializationSnafu)?
+            } else {
+                serde_json::to_string(value).context(JsonSerializationSnafu)?
+            }
+        }
+        Some(OutputFormat::Ndjson) => {
+            let mut lines = Vec::new();
+            for item in items(value) {
+                lines.push(serde_json::to_string(item).context(JsonSerializationSnafu)?);
+            }
+            lines.join("\n")
+        }
+        Some(OutputFormat::Csv) => {
+            let items = items(value);
+            csv(&items, &columns(&items, true))
+        }
+        Some(OutputFormat::Table) => {
+            let items = items(value);
+            table::render(&items, &columns(&items, false))
+        }
+        // JSON is YAML in flow style
+        Some(OutputFormat::Yaml) if pretty() == Some(false) => {
+            serde_json::to_string(&sorted(value)).context(JsonSerializationSnafu)?
+        }
+        Some(OutputFormat::Yaml) => yaml(value)?,
+    };
+
+    print_rendered(&rendered)
+}
+
+/// Render a list like `print_json`, but as a table of `default_columns` when no format was
+/// chosen. `--columns` and `--fields` still take precedence over the default columns.
+pub fn print_list(value: &Value, default_columns: &[Column]) -> Result<(), Error> {
+    if format().is_some() {
+        return print_json(value);
+    }
+
+    let items = items(value);
+    let columns = if SETTINGS
+        .get()
+        .is_some_and(|settings| settings.columns.is_some() || settings.fields.is_some())
+    {
+        columns(&items, false)
+    } else {
+        default_columns.to_vec()
+    };
+
+    print_rendered(&table::render(&items, &columns))
+}
+
+/// Render a newly created resource, or only its PRN under `--quiet` so scripts can capture it.
+pub fn print_created(value: &Value) -> Result<(), Error> {
+    if quiet() {
+        if let Some(prn) = find_prn(value) {
+            return print_rendered(prn);
+        }
+    }
+
+    print_json(value)
+}
+
+/// Write already rendered output to stdout or `--output-file`.
+pub fn print_rendered(rendered: &str) -> Result<(), Error> {
+    match SETTINGS.get().and_then(|s| s.output_file.as_ref()) {
+        Some(output_file) => {
+            let mut output_file = output_file.lock().unwrap();
+
+            if let Some(temp_file) = output_file.as_mut() {
+                writeln!(temp_file.file(), "{rendered}").context(FileSnafu)?;
+            }
+        }
+        None => println!("{rendered}"),
+    }
+
+    Ok(())
+}
+
+// whether `--pretty` or `--compact` was passed
+fn pretty() -> Option<bool> {
+    SETTINGS.get().and_then(|settings| settings.pretty)
+}
+
+// `--output json` is read by a person when it goes to a terminal with colors, pipes and
+// `--output-file` keep it compact and plain
+fn highlight() -> bool {
+    std::io::stdout().is_terminal()
+        && utils::stdout_colors()
+        && SETTINGS
+            .get()
+            .is_some_and(|settings| settings.output_file.is_none())
+}
+
+// `value` indented like `serde_json::to_string_pretty`, with keys and values styled
+fn highlighted(value: &Value) -> StyledStr {
+    let mut highlighted = StyledStr::new();
+    highlight_into(&mut highlighted, value, 0);
+    highlighted
+}
+
+fn highlight_into(out: &mut StyledStr, value: &Value, depth: usize) {
+    let indent = |depth: usize| "  ".repeat(depth);
+
+    match value {
+        Value::Object(object) if !object.is_empty() => {
+            out.plain("{\n");
+
+            for (i, (key, value)) in object.iter().enumerate() {
+                out.plain(indent(depth + 1))
+                    .push_str(Some(Style::Key), Value::from(key.as_str()).to_string());
+                out.plain(": ");
+                highlight_into(out, value, depth + 1);
+                out.plain(if i + 1 < object.len() { ",\n" } else { "\n" });
+            }
+
+            out.plain(indent(depth)).plain("}");
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.plain("[\n");
+
+            for (i, item) in items.iter().enumerate() {
+                out.plain(indent(depth + 1));
+                highlight_into(out, item, depth + 1);
+                out.plain(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+
+            out.plain(indent(depth)).plain("]");
+        }
+        Value::String(_) => {
+            out.success(value.to_string());
+        }
+        Value::Number(_) | Value::Bool(_) => {
+            out.warning(value.to_string());
+        }
+        Value::Null => out.push_str(Some(Style::Hint), value.to_string()),
+        // empty objects and arrays
+        _ => {
+            out.plain(value.to_string());
+        }
+    }
+}
+
+// the versioned structure consumers of `--output-envelope` can rely on
+fn envelope(value: &Value) -> Value {
+    let (data, pagination) = match (envelope_items(value), value) {
+        (Some(items), Value::Object(object)) => {
+            let pagination: serde_json::Map<String, Value> = object
+                .iter()
+                .filter(|(key, _)| ENVELOPE_METADATA.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+
+            (Value::Array(items.clone()), Value::Object(pagination))
+        }
+        _ => (value.clone(), Value::Null),
+    };
+
+    json!({
+        "output_version": OUTPUT_VERSION,
+        "status": "ok",
+        "data": data,
+        "pagination": pagination,
+        "warnings": WARNINGS.lock().unwrap().clone(),
+    })
+}
+
+// pagination metadata that may sit next to the items of a list response
+const ENVELOPE_METADATA: [&str; 3] = ["next_page", "pagination", "previous_page"];
+
+/// The items of a list response, v1 responses wrap them in `data` and v2 responses in a key named
+/// after the resource, next to the pagination metadata.
+pub fn envelope_items(value: &Value) -> Option<&Vec<Value>> {
+    let Value::Object(object) = value else {
+        return None;
+    };
+
+    let mut fields = object
+        .iter()
+        .filter(|(key, _)| !ENVELOPE_METADATA.contains(&key.as_str()));
+
+    match (fields.next(), fields.next()) {
+        (Some((_, Value::Array(items))), None) => Some(items),
+        _ => None,
+    }
+}
+
+fn items(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        value => match envelope_items(value) {
+            Some(items) => items.iter().collect(),
+            None => vec![value],
+        },
+    }
+}
+
+// keep only `fields` of the resource or of every item of a list, along with the paths none of them
+// have
+fn project_response(value: &Value, fields: &[String]) -> (Value, Vec<String>) {
+    let mut found = vec![false; fields.len()];
+    let mut project = |value: &Value| table::project(value, fields, &mut found);
+
+    let projected = match value {
+        Value::Array(items) => Value::Array(items.iter().map(&mut project).collect()),
+        Value::Object(object) => match (envelope_items(value), resource(object)) {
+            (Some(_), _) => Value::Object(
+                object
+                    .iter()
+                    .map(|(key, field)| match field {
+                        Value::Array(items) if !ENVELOPE_METADATA.contains(&key.as_str()) => (
+                            key.clone(),
+                            Value::Array(items.iter().map(&mut project).collect()),
+                        ),
+                        field => (key.clone(), field.clone()),
+                    })
+                    .collect(),
+            ),
+            (None, Some((key, resource))) => json!({ key: project(resource) }),
+            (None, None) => project(value),
+        },
+        value => value.clone(),
+    };
+
+    let unknown = fields
+        .iter()
+        .zip(found)
+        .filter(|(_, found)| !found)
+        .map(|(field, _)| field.clone())
+        .collect();
+
+    (projected, unknown)
+}
+
+// the resource of a response that wraps it in a single key such as `data` or the resource's name
+fn resource(object: &serde_json::Map<String, Value>) -> Option<(&String, &Value)> {
+    let mut fields = object.iter();
+
+    match (fields.next(), fields.next()) {
+        (Some((key, resource @ Value::Object(_))), None) => Some((key, resource)),
+        _ => None,
+    }
+}
+
+// create responses put the resource either at the top level or under a single key such as `data`
+// or the resource's name
+fn find_prn(value: &Value) -> Option<&str> {
+    if let Some(Value::String(prn)) = value.get("prn") {
+        return Some(prn);
+    }
+
+    match value {
+        Value::Object(object) => object
+            .values()
+            .find_map(|value| value.get("prn").and_then(Value::as_str)),
+        _ => None,
+    }
+}
+
+// the explicit --columns, warning about fields none of the items have, or a column per --fields,
+// or every top level field that isn't `nested` unless nested fields are wanted
+fn columns(items: &[&Value], nested: bool) -> Vec<Column> {
+    let settings = SETTINGS.get();
+
+    if let Some(fields) = settings.and_then(|s| s.fields.as_ref()) {
+        if settings.is_some_and(|s| s.columns.is_none()) {
+            return fields
+                .iter()
+                .map(|field| Column {
+                    header: field.clone(),
+                    path: field.clone(),
+                })
+                .collect();
+        }
+    }
+
+    let Some(columns) = settings.and_then(|s| s.columns.clone()) else {
+        let mut columns = table::default_columns(items);
+
+        if !nested {
+            columns.retain(|column| table::is_scalar(items, &column.path));
+        }
+
+        return columns;
+    };
+
+    for column in columns.iter() {
+        if !items.is_empty()
+            && items
+                .iter()
+                .all(|item| table::lookup(item, &column.path).is_none())
+        {
+            warn(format!(
+                "Unknown field for column {}: {}",
+                column.header, column.path
+            ));
+        }
+    }
+
+    columns
+}
+
+// YAML with the keys of every object sorted, whatever order the response had them in
+fn yaml(value: &Value) -> Result<String, Error> {
+    Ok(serde_yaml::to_string(&sorted(value))
+        .context(YamlSerializationSnafu)?
+        .trim_end()
+        .to_string())
+}
+
+fn sorted(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut fields: Vec<_> = object.iter().collect();
+            fields.sort_by_key(|(key, _)| *key);
+
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), sorted(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sorted).collect()),
+        value => value.clone(),
+    }
+}
+
+fn csv(items: &[&Value], columns: &[Column]) -> String {
+    let mut rows = vec![columns
+        .iter()
+        .map(|column| csv_field(&column.header))
+        .collect::<Vec<_>>()
+        .join(",")];
+
+    for item in items {
+        let row = columns
+            .iter()
+            .map(|column| csv_field(&table::cell(table::lookup(item, &column.path))))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        rows.push(row);
+    }
+
+    // RFC 4180 separates records with CRLF
+    rows.join("\r\n")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn highlighted_json_is_indented_like_pretty_json() {
+        let value = json!({
+            "device": { "identifier": "a\"b", "healthy": true, "tags": ["lab", 1] },
+            "empty": {},
+            "none": [],
+            "target": null,
+        });
+
+        let mut buffer = termcolor::Buffer::no_color();
+        highlighted(&value).write_styled(&mut buffer).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.into_inner()).unwrap(),
+            format!("{}\r\n", serde_json::to_string_pretty(&value).unwrap())
+        );
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        let items = [
+            json!({ "name": "plain", "note": "a, b" }),
+            json!({ "name": "say \"hi\"", "note": "two\nlines" }),
+        ];
+        let columns = table::default_columns(&items.iter().collect::<Vec<_>>());
+
+        assert_eq!(
+            csv(&items.iter().collect::<Vec<_>>(), &columns),
+            "name,note\r\nplain,\"a, b\"\r\n\"say \"\"hi\"\"\",\"two\nlines\""
+        );
+    }
+
+    #[test]
+    fn csv_nests_json_and_names_columns_by_header() {
+        let items = [json!({ "firmware": { "version": "1.0.0" }, "tags": ["a", "b"] })];
+        let columns = [
+            Column {
+                header: "Firmware".to_string(),
+                path: "firmware".to_string(),
+            },
+            Column {
+                header: "Tags".to_string(),
+                path: "tags".to_string(),
+            },
+            Column {
+                header: "Missing".to_string(),
+                path: "missing".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            csv(&items.iter().collect::<Vec<_>>(), &columns),
+            "Firmware,Tags,Missing\r\n\"{\"\"version\"\":\"\"1.0.0\"\"}\",\"[\"\"a\"\",\"\"b\"\"]\","
+        );
+    }
+
+    #[test]
+    fn created_prn_is_found_at_the_top_or_under_a_single_key() {
+        let prn = "prn:1:be4d30b4-de6b-47cd-85ea-a75e23fd63ef";
+
+        assert_eq!(find_prn(&json!({ "prn": prn, "name": "a" })), Some(prn));
+        assert_eq!(find_prn(&json!({ "cohort": { "prn": prn } })), Some(prn));
+        assert_eq!(find_prn(&json!({ "data": { "name": "a" } })), None);
+    }
+
+    #[test]
+    fn yaml_round_trips() {
+        let response = json!({
+            "data": [
+                { "name": "gateway", "tags": ["lab"], "firmware": { "version": "1.0.0" } },
+                { "name": "sensor", "tags": [], "firmware": null },
+            ],
+            "next_page": null,
+        });
+
+        let rendered = yaml(&response).unwrap();
+
+        assert_eq!(
+            rendered,
+            "data:\n\
+             - firmware:\n    version: 1.0.0\n  name: gateway\n  tags:\n  - lab\n\
+             - firmware: null\n  name: sensor\n  tags: []\n\
+             next_page: null"
+        );
+        assert_eq!(serde_yaml::from_str::<Value>(&rendered).unwrap(), response);
+    }
+
+    #[test]
+    fn flat_fields_are_projected() {
+        let response = json!({ "data": { "name": "gateway", "status": "online", "tags": [] } });
+
+        let (projected, unknown) = project_response(&response, &fields(&["name", "status"]));
+
+        assert_eq!(
+            projected,
+            json!({ "data": { "name": "gateway", "status": "online" } })
+        );
+        assert!(unknown.is_empty());
+    }
+
+    #[test]
+    fn nested_fields_are_projected_for_every_item() {
+        let response = json!({
+            "devices": [
+                { "name": "a", "firmware": { "version": "1.0.0", "size": 1 } },
+                { "name": "b", "firmware": { "version": "2.0.0", "size": 2 } },
+            ],
+            "next_page": "cursor",
+        });
+
+        let (projected, _) = project_response(&response, &fields(&["firmware.version"]));
+
+        assert_eq!(
+            projected,
+            json!({
+                "devices": [
+                    { "firmware": { "version": "1.0.0" } },
+                    { "firmware": { "version": "2.0.0" } },
+                ],
+                "next_page": "cursor",
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_omitted_and_reported_once() {
+        let response = json!([{ "name": "a" }, { "name": "b" }]);
+
+        let (projected, unknown) =
+            project_response(&response, &fields(&["name", "owner", "firmware.version"]));
+
+        assert_eq!(projected, json!([{ "name": "a" }, { "name": "b" }]));
+        assert_eq!(unknown, fields(&["owner", "firmware.version"]));
+    }
+}
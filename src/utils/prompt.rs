@@ -0,0 +1,215 @@
+use std::ffi::OsString;
+use std::io::{self, IsTerminal};
+
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+
+const INTERACTIVE_ARG: &str = "--interactive";
+
+/// Asks the user for the value of an argument.
+pub trait Prompter {
+    fn ask(&mut self, label: &str, secret: bool) -> io::Result<String>;
+}
+
+/// Asks on the terminal, without echoing the input of secrets.
+pub struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+    fn ask(&mut self, label: &str, secret: bool) -> io::Result<String> {
+        let term = console::Term::stderr();
+        term.write_str(&format!("{label}: "))?;
+
+        if secret {
+            term.read_secure_line()
+        } else {
+            term.read_line()
+        }
+    }
+}
+
+/// Whether missing arguments are asked for: `--interactive` was passed and stdin is a terminal,
+/// so scripts and pipes keep failing on them as before.
+pub fn enabled(interactive: bool) -> bool {
+    interactive && io::stdin().is_terminal()
+}
+
+/// Whether `--interactive` is among the raw `args`, which are checked before they parse.
+pub fn interactive_arg(args: &[OsString]) -> bool {
+    args.iter()
+        .skip(1)
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == INTERACTIVE_ARG)
+}
+
+/// Parse `args`, asking with `prompter` for the flags clap reports missing when `interactive`.
+///
+/// Any other error, and missing positional arguments, are returned as they are.
+pub fn parse<T>(
+    mut args: Vec<OsString>,
+    interactive: bool,
+    prompter: &mut impl Prompter,
+    parse: impl Fn(Vec<OsString>) -> Result<T, clap::Error>,
+) -> Result<T, clap::Error> {
+    loop {
+        let error = match parse(args.clone()) {
+            Ok(parsed) => return Ok(parsed),
+            Err(error) => error,
+        };
+
+        let flags = missing_flags(&error);
+
+        if !interactive || flags.is_empty() {
+            return Err(error);
+        }
+
+        // flags after `--` would be taken as positional values
+        let mut end = args
+            .iter()
+            .position(|arg| arg == "--")
+            .unwrap_or(args.len());
+
+        for flag in flags {
+            let value = match prompter.ask(flag.trim_start_matches('-'), is_secret(&flag)) {
+                Ok(value) => value,
+                Err(_) => return Err(error),
+            };
+
+            // the `=` form keeps values that start with a dash from being taken as flags
+            args.insert(end, OsString::from(format!("{flag}={value}")));
+            end += 1;
+        }
+    }
+}
+
+/// Whether `flag` takes a secret whose input must not be echoed.
+pub fn is_secret(flag: &str) -> bool {
+    flag.ends_with("api-key")
+        || ["password", "secret", "token"]
+            .iter()
+            .any(|secret| flag.contains(secret))
+}
+
+// the long flags of a missing required argument error, such as `--name`
+fn missing_flags(error: &clap::Error) -> Vec<String> {
+    if error.kind() != ErrorKind::MissingRequiredArgument {
+        return Vec::new();
+    }
+
+    match error.get(ContextKind::InvalidArg) {
+        Some(ContextValue::Strings(args)) => args
+            .iter()
+            .filter_map(|arg| arg.split_whitespace().next())
+            .filter(|flag| flag.starts_with("--"))
+            .map(str::to_owned)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser, Debug)]
+    struct Create {
+        #[arg(long)]
+        name: String,
+
+        #[arg(long)]
+        api_token: String,
+
+        #[arg(long)]
+        description: Option<String>,
+    }
+
+    // answers with the `answers` in order, keeping what it was asked
+    struct Scripted {
+        answers: Vec<&'static str>,
+        asked: Vec<(String, bool)>,
+    }
+
+    impl Prompter for Scripted {
+        fn ask(&mut self, label: &str, secret: bool) -> io::Result<String> {
+            self.asked.push((label.to_string(), secret));
+
+            Ok(self.answers.remove(0).to_string())
+        }
+    }
+
+    fn scripted(answers: &[&'static str]) -> Scripted {
+        Scripted {
+            answers: answers.to_vec(),
+            asked: Vec::new(),
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    fn create(args: Vec<OsString>) -> Result<Create, clap::Error> {
+        Create::try_parse_from(args)
+    }
+
+    #[test]
+    fn missing_flags_are_asked_for() {
+        let mut prompter = scripted(&["gateway", "-s3cret"]);
+
+        let parsed = parse(args(&["create"]), true, &mut prompter, create).unwrap();
+
+        assert_eq!(parsed.name, "gateway");
+        assert_eq!(parsed.api_token, "-s3cret");
+        assert_eq!(parsed.description, None);
+        assert_eq!(
+            prompter.asked,
+            [("name".to_string(), false), ("api-token".to_string(), true)]
+        );
+    }
+
+    #[test]
+    fn given_flags_are_not_asked_for() {
+        let mut prompter = scripted(&["s3cret"]);
+
+        let parsed = parse(
+            args(&["create", "--name", "gateway"]),
+            true,
+            &mut prompter,
+            create,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.name, "gateway");
+        assert_eq!(prompter.asked.len(), 1);
+    }
+
+    #[test]
+    fn without_interactive_the_error_is_returned() {
+        let mut prompter = scripted(&[]);
+
+        let error = parse(args(&["create"]), false, &mut prompter, create).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument);
+        assert!(prompter.asked.is_empty());
+    }
+
+    #[test]
+    fn other_errors_are_not_prompted_for() {
+        let mut prompter = scripted(&[]);
+
+        let error = parse(args(&["create", "--unknown"]), true, &mut prompter, create).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::UnknownArgument);
+        assert!(prompter.asked.is_empty());
+    }
+
+    #[test]
+    fn interactive_is_found_before_parsing() {
+        assert!(interactive_arg(&args(&[
+            "peridio",
+            "--interactive",
+            "devices"
+        ])));
+        assert!(!interactive_arg(&args(&["peridio", "devices"])));
+        assert!(!interactive_arg(&args(&["peridio", "--", "--interactive"])));
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+/// A token bucket that lets a burst of up to `rate` requests through, then `rate` per second.
+pub struct RateLimiter {
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate: rate.into(),
+            bucket: Mutex::new(Bucket {
+                tokens: rate.into(),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until a request may be sent.
+    pub async fn acquire(&self) {
+        let delay = self.reserve(Instant::now());
+
+        if !delay.is_zero() {
+            tracing::info!("rate limit reached, waiting {}ms", delay.as_millis());
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    // take a token and return how long to wait for it. The tokens go negative while requests
+    // wait, so concurrent requests queue up behind each other instead of all waking at once.
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+        bucket.updated = now;
+        bucket.tokens -= 1.0;
+
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        }
+    }
+}
+
+/// Limit the requests of this process to `rate` per second, see `--rate-limit`. Only the first
+/// call has an effect, so every PRN of a `--prn-file` batch shares the same limit.
+pub fn init(rate: Option<u32>) {
+    if let Some(rate) = rate {
+        LIMITER.get_or_init(|| RateLimiter::new(rate));
+    }
+}
+
+/// Wait until the next request may be sent, immediately without `--rate-limit`.
+pub async fn acquire() {
+    if let Some(limiter) = LIMITER.get() {
+        limiter.acquire().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_burst_of_rate_requests_is_not_delayed() {
+        let limiter = RateLimiter::new(5);
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            assert_eq!(limiter.reserve(now), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn waiting_requests_are_spaced_out() {
+        let limiter = RateLimiter::new(5);
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            limiter.reserve(now);
+        }
+
+        assert_eq!(limiter.reserve(now), Duration::from_millis(200));
+        assert_eq!(limiter.reserve(now), Duration::from_millis(400));
+
+        // by then both waiting requests were sent, so the next one waits a single interval
+        assert_eq!(
+            limiter.reserve(now + Duration::from_millis(400)),
+            Duration::from_millis(200)
+        );
+    }
+
+    #[test]
+    fn the_bucket_refills_up_to_rate() {
+        let limiter = RateLimiter::new(2);
+        let now = Instant::now() + Duration::from_secs(10);
+
+        assert_eq!(limiter.reserve(now), Duration::ZERO);
+        assert_eq!(limiter.reserve(now), Duration::ZERO);
+        assert_eq!(limiter.reserve(now), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn rate_plus_one_requests_take_a_token_interval() {
+        let limiter = RateLimiter::new(10);
+        let start = Instant::now();
+
+        for _ in 0..11 {
+            limiter.acquire().await;
+        }
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+    }
+}
@@ -0,0 +1,268 @@
+use std::io::IsTerminal;
+
+use serde_json::{Map, Value};
+
+/// A column of tabular output: the header to display and the dotted path of the field to show.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub header: String,
+    pub path: String,
+}
+
+/// The parsed `--columns` argument, a newtype so clap treats it as a single value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Columns(pub Vec<Column>);
+
+/// Parse a `--columns` value such as `Name:identifier,State:status,firmware_metadata.version`.
+///
+/// Columns keep the given order. A column without a `Header:` prefix uses its path as the header.
+pub fn parse_columns(value: &str) -> Result<Columns, String> {
+    value
+        .split(',')
+        .map(|column| {
+            let (header, path) = match column.split_once(':') {
+                Some((header, path)) => (header.trim(), path.trim()),
+                None => (column.trim(), column.trim()),
+            };
+
+            if path.is_empty() || header.is_empty() {
+                return Err(format!("expected HEADER:FIELD or FIELD, got '{column}'"));
+            }
+
+            Ok(Column {
+                header: header.to_string(),
+                path: path.to_string(),
+            })
+        })
+        .collect::<Result<_, _>>()
+        .map(Columns)
+}
+
+/// One column per top level field, in the order the fields first appear.
+pub fn default_columns(items: &[&Value]) -> Vec<Column> {
+    let mut columns: Vec<Column> = Vec::new();
+
+    for item in items {
+        if let Value::Object(object) = item {
+            for key in object.keys() {
+                if !columns.iter().any(|column| &column.path == key) {
+                    columns.push(Column {
+                        header: key.clone(),
+                        path: key.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    columns
+}
+
+/// Look up a dotted field path, numeric segments index into arrays.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |value, segment| match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+}
+
+/// Copy the fields at `paths` out of `value`, keeping their nesting.
+///
+/// `found` is set for every path that `value` has.
+pub fn project(value: &Value, paths: &[String], found: &mut [bool]) -> Value {
+    let mut projected = Value::Object(Map::new());
+
+    for (path, found) in paths.iter().zip(found.iter_mut()) {
+        let Some(field) = lookup(value, path) else {
+            continue;
+        };
+
+        *found = true;
+
+        let mut target = Some(&mut projected);
+        for segment in path.split('.') {
+            // a shorter path already copied a parent that is not an object
+            target = target.and_then(Value::as_object_mut).map(|object| {
+                object
+                    .entry(segment)
+                    .or_insert_with(|| Value::Object(Map::new()))
+            });
+        }
+
+        if let Some(target) = target {
+            *target = field.clone();
+        }
+    }
+
+    projected
+}
+
+/// The text shown for a field, strings without quotes and missing fields as nothing.
+pub fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// Whether any of `items` has a field at `path` that is neither an object nor an array, which
+/// don't fit in a table cell.
+pub fn is_scalar(items: &[&Value], path: &str) -> bool {
+    items
+        .iter()
+        .any(|item| lookup(item, path).is_some_and(|value| !value.is_object() && !value.is_array()))
+}
+
+// the space between two columns
+const COLUMN_GAP: usize = 2;
+
+// columns are not truncated below this width to fit the terminal
+const MIN_COLUMN_WIDTH: usize = 4;
+
+/// Render `items` as a table with aligned columns and a header row, truncated to the width of the
+/// terminal when stdout is one.
+pub fn render(items: &[&Value], columns: &[Column]) -> String {
+    let width = std::io::stdout()
+        .is_terminal()
+        .then(|| usize::from(console::Term::stdout().size().1));
+
+    render_to_width(items, columns, width)
+}
+
+/// Render `items` as a table, shrinking the widest columns until the rows fit `max_width`.
+///
+/// Numbers are right aligned and values that don't fit end with an ellipsis.
+pub fn render_to_width(items: &[&Value], columns: &[Column], max_width: Option<usize>) -> String {
+    // the text of each cell and whether it is right aligned
+    let mut rows: Vec<Vec<(String, bool)>> = vec![columns
+        .iter()
+        .map(|column| (column.header.clone(), false))
+        .collect()];
+
+    for item in items {
+        rows.push(
+            columns
+                .iter()
+                .map(|column| {
+                    let value = lookup(item, &column.path);
+                    (cell(value), value.is_some_and(Value::is_number))
+                })
+                .collect(),
+        );
+    }
+
+    let mut widths: Vec<usize> = (0..columns.len())
+        .map(|index| {
+            rows.iter()
+                .map(|row| row[index].0.chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    if let Some(max_width) = max_width {
+        let gaps = COLUMN_GAP * columns.len().saturating_sub(1);
+
+        while widths.iter().sum::<usize>() + gaps > max_width {
+            let Some(widest) = widths
+                .iter_mut()
+                .filter(|width| **width > MIN_COLUMN_WIDTH)
+                .max()
+            else {
+                break;
+            };
+
+            *widest -= 1;
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|((cell, numeric), width)| {
+                    let cell = truncate(cell, *width);
+
+                    if *numeric {
+                        format!("{cell:>width$}")
+                    } else {
+                        format!("{cell:width$}")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(&" ".repeat(COLUMN_GAP))
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn truncate(cell: &str, width: usize) -> String {
+    if cell.chars().count() <= width {
+        return cell.to_string();
+    }
+
+    let mut truncated: String = cell.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn columns(paths: &[&str]) -> Vec<Column> {
+        paths
+            .iter()
+            .map(|path| Column {
+                header: path.to_string(),
+                path: path.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn columns_are_aligned_and_numbers_right_aligned() {
+        let items = [
+            json!({ "name": "gateway", "size": 7 }),
+            json!({ "name": "sensor", "size": 1024 }),
+        ];
+
+        let table = render_to_width(
+            &items.iter().collect::<Vec<_>>(),
+            &columns(&["name", "size"]),
+            None,
+        );
+
+        assert_eq!(table, "name     size\ngateway     7\nsensor   1024");
+    }
+
+    #[test]
+    fn widest_columns_are_truncated_to_fit() {
+        let items = [json!({ "id": "a", "description": "a rather long description" })];
+
+        let table = render_to_width(
+            &items.iter().collect::<Vec<_>>(),
+            &columns(&["id", "description"]),
+            Some(16),
+        );
+
+        assert_eq!(table, "id  description\na   a rather lo…");
+        assert!(table.lines().all(|line| line.chars().count() <= 16));
+    }
+
+    #[test]
+    fn nested_fields_are_not_scalar() {
+        let items = [json!({ "name": "a", "tags": ["lab"], "firmware": { "version": "1" } })];
+        let items: Vec<_> = items.iter().collect();
+
+        assert!(is_scalar(&items, "name"));
+        assert!(is_scalar(&items, "firmware.version"));
+        assert!(!is_scalar(&items, "tags"));
+        assert!(!is_scalar(&items, "firmware"));
+    }
+}
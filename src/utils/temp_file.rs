@@ -0,0 +1,204 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+// temp files that have not been persisted yet, removed if the process is interrupted
+#[cfg(not(test))]
+static PENDING: std::sync::Mutex<Vec<PathBuf>> = std::sync::Mutex::new(Vec::new());
+
+// tests run in parallel on their own threads, each keeps its own pending files so one calling
+// `remove_pending` leaves the temp files of the others
+#[cfg(test)]
+thread_local! {
+    static PENDING: std::cell::RefCell<Vec<PathBuf>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+#[cfg(not(test))]
+fn with_pending<T>(f: impl FnOnce(&mut Vec<PathBuf>) -> T) -> Option<T> {
+    PENDING.lock().ok().map(|mut pending| f(&mut pending))
+}
+
+#[cfg(test)]
+fn with_pending<T>(f: impl FnOnce(&mut Vec<PathBuf>) -> T) -> Option<T> {
+    Some(PENDING.with(|pending| f(&mut pending.borrow_mut())))
+}
+
+/// A file written next to its destination and renamed into place once it is complete.
+///
+/// Creating the file in the destination's directory keeps the final rename on the same
+/// filesystem, so it is atomic and cannot fail with a cross-device error. Until
+/// [`TempFile::persist`] is called the file is removed when dropped, and by [`remove_pending`]
+/// when the process is interrupted.
+pub struct TempFile {
+    path: PathBuf,
+    destination: PathBuf,
+    file: Option<File>,
+    persisted: bool,
+}
+
+impl TempFile {
+    pub fn new(destination: &Path) -> io::Result<Self> {
+        let directory = match destination.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let file_name = destination
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let path = directory.join(format!(".{file_name}.{}.tmp", Uuid::new_v4().simple()));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+
+        // only the current user may read partial content
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let file = options.open(&path)?;
+
+        with_pending(|pending| pending.push(path.clone()));
+
+        Ok(Self {
+            path,
+            destination: destination.to_path_buf(),
+            file: Some(file),
+            persisted: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file(&mut self) -> &mut File {
+        self.file.as_mut().expect("temp file is already closed")
+    }
+
+    /// Flush the content to disk and move the file to its destination.
+    pub fn persist(mut self) -> io::Result<PathBuf> {
+        if let Some(file) = self.file.take() {
+            file.sync_all()?;
+        }
+
+        fs::rename(&self.path, &self.destination)?;
+
+        self.persisted = true;
+        unregister(&self.path);
+
+        Ok(self.destination.clone())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            // close the handle first, some platforms refuse to remove open files
+            self.file.take();
+            let _ = fs::remove_file(&self.path);
+            unregister(&self.path);
+        }
+    }
+}
+
+/// Remove every temp file that has not been persisted.
+///
+/// Used on paths that exit the process without unwinding, where `Drop` never runs.
+pub fn remove_pending() {
+    with_pending(|pending| {
+        for path in pending.drain(..) {
+            let _ = fs::remove_file(path);
+        }
+    });
+}
+
+fn unregister(path: &Path) {
+    with_pending(|pending| pending.retain(|pending_path| pending_path != path));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn pending(path: &Path) -> bool {
+        with_pending(|pending| pending.iter().any(|pending| pending == path)).unwrap()
+    }
+
+    #[test]
+    fn persisted_files_are_moved_to_their_destination() {
+        let directory = tempfile::tempdir().unwrap();
+        let destination = directory.path().join("firmware.bin");
+
+        let mut temp_file = TempFile::new(&destination).unwrap();
+        let path = temp_file.path().to_path_buf();
+        temp_file.file().write_all(b"content").unwrap();
+
+        assert_eq!(path.parent(), Some(directory.path()));
+        assert!(pending(&path));
+
+        assert_eq!(temp_file.persist().unwrap(), destination);
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "content");
+        assert!(!path.exists());
+        assert!(!pending(&path));
+    }
+
+    #[test]
+    fn dropped_files_are_removed() {
+        let directory = tempfile::tempdir().unwrap();
+        let destination = directory.path().join("firmware.bin");
+        fs::write(&destination, "previous").unwrap();
+
+        let mut temp_file = TempFile::new(&destination).unwrap();
+        let path = temp_file.path().to_path_buf();
+        temp_file.file().write_all(b"partial").unwrap();
+        drop(temp_file);
+
+        assert!(!path.exists());
+        assert!(!pending(&path));
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "previous");
+    }
+
+    #[test]
+    fn files_are_removed_when_writing_them_fails() {
+        let directory = tempfile::tempdir().unwrap();
+        let destination = directory.path().join("firmware.bin");
+        let mut path = PathBuf::new();
+
+        let write = |path: &mut PathBuf| -> io::Result<()> {
+            let mut temp_file = TempFile::new(&destination)?;
+            *path = temp_file.path().to_path_buf();
+            temp_file.file().write_all(b"partial")?;
+
+            // fails before it is persisted
+            Err(io::Error::other("interrupted"))
+        };
+
+        assert!(write(&mut path).is_err());
+        assert!(!path.exists());
+        assert!(!destination.exists());
+    }
+
+    #[test]
+    fn pending_files_are_removed() {
+        let directory = tempfile::tempdir().unwrap();
+        let temp_file = TempFile::new(&directory.path().join("firmware.bin")).unwrap();
+        let path = temp_file.path().to_path_buf();
+
+        // as if the process exited without dropping it
+        std::mem::forget(temp_file);
+        assert!(path.exists());
+
+        remove_pending();
+
+        assert!(!path.exists());
+        assert!(!pending(&path));
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::OnceLock;
+
+use uuid::Uuid;
+
+static TRACEPARENT: OnceLock<String> = OnceLock::new();
+
+/// Validate a W3C trace ID: 32 lowercase hex digits, not all zero.
+pub fn parse_trace_id(value: &str) -> Result<String, String> {
+    if is_hex_id(value, 32) {
+        Ok(value.to_string())
+    } else {
+        Err("expected 32 lowercase hex digits that are not all zero".to_string())
+    }
+}
+
+/// Validate a W3C `traceparent` header value, `00-<trace-id>-<parent-id>-<flags>`.
+pub fn parse_traceparent(value: &str) -> Result<String, String> {
+    let parts: Vec<&str> = value.split('-').collect();
+
+    match parts.as_slice() {
+        ["00", trace_id, parent_id, flags]
+            if is_hex_id(trace_id, 32) && is_hex_id(parent_id, 16) && is_hex(flags, 2) =>
+        {
+            Ok(value.to_string())
+        }
+        _ => Err("expected 00-<32 hex trace id>-<16 hex parent id>-<2 hex flags>".to_string()),
+    }
+}
+
+/// Resolve the `traceparent` sent with requests for this process, generating a random one when
+/// neither a trace ID nor a full traceparent was supplied. Only the first call has an effect.
+pub fn init(trace_id: Option<String>, traceparent: Option<String>) -> &'static str {
+    TRACEPARENT.get_or_init(|| {
+        if let Some(traceparent) = traceparent {
+            return traceparent;
+        }
+
+        let trace_id = trace_id.unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+        let parent_id = &Uuid::new_v4().simple().to_string()[..16];
+
+        // sampled, so the backend records the trace
+        format!("00-{trace_id}-{parent_id}-01")
+    })
+}
+
+pub fn traceparent() -> Option<&'static str> {
+    TRACEPARENT.get().map(String::as_str)
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+fn is_hex_id(value: &str, len: usize) -> bool {
+    // all zero ids are invalid per the spec
+    is_hex(value, len) && value.chars().any(|c| c != '0')
+}
@@ -0,0 +1,177 @@
+use std::future::Future;
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::time::Instant;
+
+use crate::utils::{output, parse_duration};
+use crate::{Error, WaitTimeoutSnafu};
+
+/// Where a resource keeps its state and the states it stays in until someone acts on it, so
+/// `--wait` stops polling there.
+pub struct Terminal {
+    /// The JSON pointer of the state in the response, such as `/binary/state`.
+    pub pointer: &'static str,
+    /// The states to stop at unless `--wait-for` names others.
+    pub states: &'static [&'static str],
+}
+
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct WaitArgs {
+    /// Poll the resource until it reaches a terminal state, then print it.
+    #[arg(long)]
+    pub wait: bool,
+
+    /// How long to wait before giving up, e.g. 30s or 10m.
+    #[arg(long, default_value = "10m", value_parser = parse_duration, requires = "wait")]
+    pub wait_timeout: Duration,
+
+    /// How often to poll while waiting, e.g. 2s.
+    #[arg(long, default_value = "2s", value_parser = parse_duration, requires = "wait")]
+    pub wait_interval: Duration,
+
+    /// The states to stop waiting at instead of the resource's terminal states, comma separated.
+    #[arg(long, value_delimiter = ',', requires = "wait")]
+    pub wait_for: Vec<String>,
+}
+
+impl WaitArgs {
+    // whether `state` ends the wait
+    fn is_terminal(&self, terminal: &Terminal, state: &str) -> bool {
+        if self.wait_for.is_empty() {
+            terminal.states.contains(&state)
+        } else {
+            self.wait_for.iter().any(|terminal| terminal == state)
+        }
+    }
+}
+
+/// Fetch the resource until its state is terminal and return the last response.
+///
+/// A spinner shows the current state on a terminal. Fails with a timeout when the resource is
+/// still not terminal after `--wait-timeout`.
+pub async fn until_terminal<F, Fut>(
+    args: &WaitArgs,
+    terminal: &Terminal,
+    mut fetch: F,
+) -> Result<Value, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Value, Error>>,
+{
+    let deadline = Instant::now() + args.wait_timeout;
+    let spinner = output::spinner();
+
+    let result = loop {
+        let resource = match fetch().await {
+            Ok(resource) => resource,
+            Err(error) => break Err(error),
+        };
+
+        let state = resource
+            .pointer(terminal.pointer)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        if args.is_terminal(terminal, &state) {
+            break Ok(resource);
+        }
+
+        let now = Instant::now();
+
+        if now >= deadline {
+            break WaitTimeoutSnafu {
+                state,
+                timeout: args.wait_timeout,
+            }
+            .fail();
+        }
+
+        spinner.set_message(format!("Waiting, the state is {state}"));
+        tokio::time::sleep(args.wait_interval.min(deadline - now)).await;
+    };
+
+    spinner.finish_and_clear();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::cell::Cell;
+
+    const TERMINAL: Terminal = Terminal {
+        pointer: "/binary/state",
+        states: &["signable", "signed"],
+    };
+
+    fn args(timeout: Duration) -> WaitArgs {
+        WaitArgs {
+            wait: true,
+            wait_timeout: timeout,
+            wait_interval: Duration::from_millis(5),
+            wait_for: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn polling_stops_at_a_terminal_state() {
+        let states = ["hashable", "hashing", "signable", "signed"];
+        let polls = Cell::new(0);
+
+        let resource = until_terminal(&args(Duration::from_secs(5)), &TERMINAL, || {
+            let state = states[polls.get()];
+            polls.set(polls.get() + 1);
+
+            async move { Ok(json!({ "binary": { "state": state } })) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(polls.get(), 3);
+        assert_eq!(resource, json!({ "binary": { "state": "signable" } }));
+    }
+
+    #[tokio::test]
+    async fn wait_for_overrides_the_terminal_states() {
+        let states = ["hashing", "signable", "signed"];
+        let polls = Cell::new(0);
+        let args = WaitArgs {
+            wait_for: vec!["signed".to_string()],
+            ..args(Duration::from_secs(5))
+        };
+
+        until_terminal(&args, &TERMINAL, || {
+            let state = states[polls.get()];
+            polls.set(polls.get() + 1);
+
+            async move { Ok(json!({ "binary": { "state": state } })) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(polls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn polling_gives_up_after_the_timeout() {
+        let start = Instant::now();
+
+        let result = until_terminal(&args(Duration::from_millis(50)), &TERMINAL, || async {
+            Ok(json!({ "binary": { "state": "hashing" } }))
+        })
+        .await;
+
+        assert!(
+            matches!(result, Err(Error::WaitTimeout { ref state, .. }) if state == "hashing"),
+            "{result:?}"
+        );
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+    }
+}